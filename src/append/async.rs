@@ -0,0 +1,612 @@
+//! The asynchronous appender.
+//!
+//! Requires the `async_appender` feature.
+//!
+//! An `AsyncAppender` wraps another appender and hands log records off to a
+//! background thread through a bounded queue, so that the logging thread does
+//! not wait on slow I/O such as a congested disk or a log file being rolled.
+//!
+//! Records are copied into an owned form before being queued. The MDC of the
+//! logging thread is captured along with the record and restored on the
+//! background thread before the wrapped appender is invoked. Information which
+//! encoders derive from the *current* thread, such as the thread name and ID,
+//! will refer to the background thread.
+
+use derive_more::Debug;
+use log::Record;
+use parking_lot::{Condvar, Mutex};
+use std::{
+    collections::VecDeque,
+    io,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle, ThreadId},
+};
+
+#[cfg(feature = "config_parsing")]
+use crate::{
+    append::AppenderConfig,
+    config::{Deserialize, Deserializers},
+};
+use crate::{
+    append::{owned::OwnedRecord, Append, Appenders},
+    handle_error,
+};
+
+const DEFAULT_CAPACITY: usize = 1024;
+
+/// The async appender's configuration.
+#[cfg(feature = "config_parsing")]
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct AsyncAppenderConfig {
+    appender: AppenderConfig,
+    capacity: Option<usize>,
+    overflow: Option<ConfigOverflowPolicy>,
+}
+
+#[cfg(feature = "config_parsing")]
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "schema", schemars(rename = "OverflowPolicy"))]
+enum ConfigOverflowPolicy {
+    #[serde(rename = "block")]
+    Block,
+    #[serde(rename = "drop_newest")]
+    DropNewest,
+    #[serde(rename = "drop_oldest")]
+    DropOldest,
+}
+
+/// The action taken when a record is appended while the queue is full.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub enum OverflowPolicy {
+    /// Wait for the background thread to make room in the queue.
+    #[default]
+    Block,
+    /// Discard the record being appended.
+    DropNewest,
+    /// Discard the oldest record in the queue to make room.
+    DropOldest,
+}
+
+/// A handle to the number of records discarded by an `AsyncAppender`.
+///
+/// The handle remains valid after the appender has been moved into a `Config`.
+#[derive(Clone, Debug, Default)]
+pub struct DroppedCounter(Arc<AtomicU64>);
+
+impl DroppedCounter {
+    /// Returns the number of records discarded so far.
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+struct State {
+    queue: VecDeque<OwnedRecord>,
+    // Records accepted into the queue, and records that have left it either by
+    // being appended or by being displaced under `OverflowPolicy::DropOldest`.
+    enqueued: u64,
+    completed: u64,
+    shutdown: bool,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    not_empty: Condvar,
+    not_full: Condvar,
+    drained: Condvar,
+    dropped: DroppedCounter,
+}
+
+/// An appender which forwards records to another appender on a background
+/// thread.
+#[derive(Debug)]
+pub struct AsyncAppender {
+    #[debug(skip)]
+    shared: Arc<Shared>,
+    appender: Arc<dyn Append>,
+    capacity: usize,
+    overflow: OverflowPolicy,
+    #[debug(skip)]
+    worker: Option<JoinHandle<()>>,
+    #[debug(skip)]
+    worker_id: ThreadId,
+}
+
+impl AsyncAppender {
+    /// Creates a new `AsyncAppender` builder.
+    pub fn builder() -> AsyncAppenderBuilder {
+        AsyncAppenderBuilder {
+            capacity: DEFAULT_CAPACITY,
+            overflow: OverflowPolicy::default(),
+        }
+    }
+
+    /// Returns the number of records discarded because the queue was full.
+    pub fn dropped(&self) -> u64 {
+        self.shared.dropped.get()
+    }
+
+    /// Returns a handle to the count of discarded records.
+    pub fn dropped_counter(&self) -> DroppedCounter {
+        self.shared.dropped.clone()
+    }
+
+    fn enqueue(&self, record: OwnedRecord) {
+        let mut state = self.shared.state.lock();
+
+        if state.queue.len() >= self.capacity {
+            // Blocking on the background thread's own queue would never finish,
+            // so records logged by the wrapped appender itself are discarded.
+            let overflow = if thread::current().id() == self.worker_id {
+                OverflowPolicy::DropNewest
+            } else {
+                self.overflow
+            };
+
+            match overflow {
+                OverflowPolicy::Block => {
+                    while state.queue.len() >= self.capacity && !state.shutdown {
+                        self.shared.not_full.wait(&mut state);
+                    }
+                }
+                OverflowPolicy::DropNewest => {
+                    self.shared.dropped.0.fetch_add(1, Ordering::Relaxed);
+                    return;
+                }
+                OverflowPolicy::DropOldest => {
+                    state.queue.pop_front();
+                    state.completed += 1;
+                    self.shared.dropped.0.fetch_add(1, Ordering::Relaxed);
+                    self.shared.drained.notify_all();
+                }
+            }
+        }
+
+        state.queue.push_back(record);
+        state.enqueued += 1;
+        self.shared.not_empty.notify_one();
+    }
+
+    fn wait_drained(&self) {
+        let mut state = self.shared.state.lock();
+        let target = state.enqueued;
+        while state.completed < target {
+            self.shared.drained.wait(&mut state);
+        }
+    }
+}
+
+impl Append for AsyncAppender {
+    fn append(&self, record: &Record<'_>) -> anyhow::Result<()> {
+        self.enqueue(OwnedRecord::new(record));
+        Ok(())
+    }
+
+    /// Waits for every record queued before the call to be appended, then
+    /// flushes the wrapped appender.
+    fn flush(&self) {
+        if thread::current().id() != self.worker_id {
+            self.wait_drained();
+        }
+        self.appender.flush();
+    }
+
+    fn resolve(&self, appenders: &Appenders<'_>) -> anyhow::Result<()> {
+        self.appender.resolve(appenders)
+    }
+
+    fn targets(&self) -> Vec<&str> {
+        self.appender.targets()
+    }
+
+    #[cfg(feature = "config_parsing")]
+    fn config_value(&self) -> Option<serde_value::Value> {
+        let config = AsyncAppenderConfig {
+            appender: AppenderConfig::describe(&*self.appender)?,
+            capacity: Some(self.capacity),
+            overflow: Some(match self.overflow {
+                OverflowPolicy::Block => ConfigOverflowPolicy::Block,
+                OverflowPolicy::DropNewest => ConfigOverflowPolicy::DropNewest,
+                OverflowPolicy::DropOldest => ConfigOverflowPolicy::DropOldest,
+            }),
+        };
+        crate::config::component_config("async", &config)
+    }
+}
+
+impl Drop for AsyncAppender {
+    fn drop(&mut self) {
+        self.shared.state.lock().shutdown = true;
+        self.shared.not_empty.notify_all();
+        self.shared.not_full.notify_all();
+
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+        self.appender.flush();
+    }
+}
+
+fn run_worker(shared: &Shared, appender: &dyn Append) {
+    loop {
+        let record = {
+            let mut state = shared.state.lock();
+            loop {
+                if let Some(record) = state.queue.pop_front() {
+                    shared.not_full.notify_one();
+                    break record;
+                }
+                if state.shutdown {
+                    return;
+                }
+                shared.not_empty.wait(&mut state);
+            }
+        };
+
+        if let Err(e) = record.append_to(appender) {
+            handle_error(&e);
+        }
+
+        shared.state.lock().completed += 1;
+        shared.drained.notify_all();
+    }
+}
+
+/// A builder for `AsyncAppender`s.
+pub struct AsyncAppenderBuilder {
+    capacity: usize,
+    overflow: OverflowPolicy,
+}
+
+impl AsyncAppenderBuilder {
+    /// Sets the maximum number of records waiting to be appended.
+    ///
+    /// Defaults to 1024.
+    pub fn capacity(mut self, capacity: usize) -> AsyncAppenderBuilder {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Sets the action taken when a record is appended while the queue is full.
+    ///
+    /// Defaults to `OverflowPolicy::Block`.
+    pub fn overflow(mut self, overflow: OverflowPolicy) -> AsyncAppenderBuilder {
+        self.overflow = overflow;
+        self
+    }
+
+    /// Consumes the `AsyncAppenderBuilder`, producing an `AsyncAppender`
+    /// which forwards records to `appender`.
+    ///
+    /// The background thread is started immediately.
+    pub fn build(self, appender: Box<dyn Append>) -> io::Result<AsyncAppender> {
+        let capacity = self.capacity.max(1);
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                queue: VecDeque::with_capacity(capacity),
+                enqueued: 0,
+                completed: 0,
+                shutdown: false,
+            }),
+            not_empty: Condvar::new(),
+            not_full: Condvar::new(),
+            drained: Condvar::new(),
+            dropped: DroppedCounter::default(),
+        });
+        let appender: Arc<dyn Append> = Arc::from(appender);
+
+        let worker = {
+            let shared = shared.clone();
+            let appender = appender.clone();
+            thread::Builder::new()
+                .name("log4rs async".to_owned())
+                .spawn(move || run_worker(&shared, &*appender))?
+        };
+
+        Ok(AsyncAppender {
+            shared,
+            appender,
+            capacity,
+            overflow: self.overflow,
+            worker_id: worker.thread().id(),
+            worker: Some(worker),
+        })
+    }
+}
+
+/// A deserializer for the `AsyncAppender`.
+///
+/// # Configuration
+///
+/// ```yaml
+/// kind: async
+///
+/// # The maximum number of records waiting to be appended. Defaults to 1024.
+/// capacity: 1024
+///
+/// # What to do with a record when the queue is full. One of `block`,
+/// # `drop_newest` or `drop_oldest`. Defaults to `block`.
+/// overflow: block
+///
+/// # The appender which records are forwarded to. Required.
+/// # Filters should be attached to the async appender itself rather than
+/// # to the wrapped appender.
+/// appender:
+///   kind: file
+///   path: log/foo.log
+/// ```
+#[cfg(feature = "config_parsing")]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct AsyncAppenderDeserializer;
+
+#[cfg(feature = "config_parsing")]
+impl Deserialize for AsyncAppenderDeserializer {
+    type Trait = dyn Append;
+
+    type Config = AsyncAppenderConfig;
+
+    fn deserialize(
+        &self,
+        config: AsyncAppenderConfig,
+        deserializers: &Deserializers,
+    ) -> anyhow::Result<Box<dyn Append>> {
+        if !config.appender.filters.is_empty() || config.appender.on_error.is_some() {
+            anyhow::bail!(
+                "filters and error policies must be attached to the async appender rather than the wrapped appender"
+            );
+        }
+
+        let mut builder = AsyncAppender::builder();
+        if let Some(capacity) = config.capacity {
+            builder = builder.capacity(capacity);
+        }
+        if let Some(overflow) = config.overflow {
+            let overflow = match overflow {
+                ConfigOverflowPolicy::Block => OverflowPolicy::Block,
+                ConfigOverflowPolicy::DropNewest => OverflowPolicy::DropNewest,
+                ConfigOverflowPolicy::DropOldest => OverflowPolicy::DropOldest,
+            };
+            builder = builder.overflow(overflow);
+        }
+
+        let appender = deserializers.deserialize(&config.appender.kind, config.appender.config)?;
+        Ok(Box::new(builder.build(appender)?))
+    }
+
+    fn check_components(
+        &self,
+        config: &AsyncAppenderConfig,
+        deserializers: &Deserializers,
+    ) -> anyhow::Result<()> {
+        config.appender.check("appender", deserializers)
+    }
+
+    fn targets(&self, config: &AsyncAppenderConfig, deserializers: &Deserializers) -> Vec<String> {
+        config.appender.targets(deserializers)
+    }
+
+    #[cfg(feature = "schema")]
+    fn schema(&self, generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        <AsyncAppenderConfig as schemars::JsonSchema>::json_schema(generator)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use derive_more::Debug;
+    use log::Level;
+    use std::sync::mpsc::{self, Receiver, Sender};
+
+    use super::*;
+
+    #[derive(Debug)]
+    struct VecAppender(Arc<Mutex<Vec<String>>>);
+
+    impl Append for VecAppender {
+        fn append(&self, record: &Record<'_>) -> anyhow::Result<()> {
+            let mut message = record.args().to_string();
+            log_mdc::get("id", |id| {
+                if let Some(id) = id {
+                    message = format!("{} {}", id, message);
+                }
+            });
+            self.0.lock().push(message);
+            Ok(())
+        }
+
+        fn flush(&self) {}
+    }
+
+    // Holds the background thread inside `append` until released.
+    #[derive(Debug)]
+    struct GatedAppender {
+        inner: VecAppender,
+        #[debug(skip)]
+        entered: Mutex<Sender<()>>,
+        #[debug(skip)]
+        release: Mutex<Receiver<()>>,
+    }
+
+    impl Append for GatedAppender {
+        fn append(&self, record: &Record<'_>) -> anyhow::Result<()> {
+            self.entered.lock().send(()).unwrap();
+            self.release.lock().recv().unwrap();
+            self.inner.append(record)
+        }
+
+        fn flush(&self) {}
+    }
+
+    fn log(appender: &AsyncAppender, message: &str) {
+        appender
+            .append(
+                &Record::builder()
+                    .level(Level::Info)
+                    .args(format_args!("{}", message))
+                    .build(),
+            )
+            .unwrap();
+    }
+
+    type Gated = (
+        AsyncAppender,
+        Arc<Mutex<Vec<String>>>,
+        Receiver<()>,
+        Sender<()>,
+    );
+
+    fn gated(overflow: OverflowPolicy) -> Gated {
+        let messages = Arc::new(Mutex::new(vec![]));
+        let (entered_tx, entered_rx) = mpsc::channel();
+        let (release_tx, release_rx) = mpsc::channel();
+        let appender = AsyncAppender::builder()
+            .capacity(2)
+            .overflow(overflow)
+            .build(Box::new(GatedAppender {
+                inner: VecAppender(messages.clone()),
+                entered: Mutex::new(entered_tx),
+                release: Mutex::new(release_rx),
+            }))
+            .unwrap();
+        (appender, messages, entered_rx, release_tx)
+    }
+
+    #[test]
+    fn flush_drains_queue() {
+        let messages = Arc::new(Mutex::new(vec![]));
+        let appender = AsyncAppender::builder()
+            .build(Box::new(VecAppender(messages.clone())))
+            .unwrap();
+
+        for i in 0..100 {
+            log(&appender, &i.to_string());
+        }
+        appender.flush();
+
+        let expected = (0..100).map(|i| i.to_string()).collect::<Vec<_>>();
+        assert_eq!(*messages.lock(), expected);
+        assert_eq!(appender.dropped(), 0);
+    }
+
+    #[test]
+    fn drop_drains_queue() {
+        let messages = Arc::new(Mutex::new(vec![]));
+        let appender = AsyncAppender::builder()
+            .build(Box::new(VecAppender(messages.clone())))
+            .unwrap();
+
+        log(&appender, "a");
+        log(&appender, "b");
+        drop(appender);
+
+        assert_eq!(*messages.lock(), ["a", "b"]);
+    }
+
+    #[test]
+    fn mdc_is_forwarded() {
+        let messages = Arc::new(Mutex::new(vec![]));
+        let appender = AsyncAppender::builder()
+            .build(Box::new(VecAppender(messages.clone())))
+            .unwrap();
+
+        log_mdc::insert("id", "42");
+        log(&appender, "with mdc");
+        log_mdc::remove("id");
+        log(&appender, "without mdc");
+        appender.flush();
+
+        assert_eq!(*messages.lock(), ["42 with mdc", "without mdc"]);
+    }
+
+    #[test]
+    fn drop_newest() {
+        let (appender, messages, entered, release) = gated(OverflowPolicy::DropNewest);
+
+        log(&appender, "0");
+        entered.recv().unwrap();
+        for i in 1..5 {
+            log(&appender, &i.to_string());
+        }
+        assert_eq!(appender.dropped(), 2);
+
+        for _ in 0..3 {
+            release.send(()).unwrap();
+        }
+        appender.flush();
+        assert_eq!(*messages.lock(), ["0", "1", "2"]);
+    }
+
+    #[test]
+    fn drop_oldest() {
+        let (appender, messages, entered, release) = gated(OverflowPolicy::DropOldest);
+        let counter = appender.dropped_counter();
+
+        log(&appender, "0");
+        entered.recv().unwrap();
+        for i in 1..5 {
+            log(&appender, &i.to_string());
+        }
+        assert_eq!(counter.get(), 2);
+
+        for _ in 0..3 {
+            release.send(()).unwrap();
+        }
+        appender.flush();
+        assert_eq!(*messages.lock(), ["0", "3", "4"]);
+    }
+
+    #[test]
+    #[cfg(feature = "ring_buffer_appender")]
+    fn flush_cycle() {
+        use crate::{
+            append::ring_buffer::RingBufferAppender,
+            config::{Appender, Config, Root},
+        };
+        use log::LevelFilter;
+
+        let appender = AsyncAppender::builder()
+            .build(Box::new(
+                RingBufferAppender::builder().flush_to("async").build(),
+            ))
+            .unwrap();
+        let errors = Config::builder()
+            .appender(Appender::builder().build("async", Box::new(appender)))
+            .build(Root::builder().build(LevelFilter::Info))
+            .unwrap_err();
+        assert_eq!(
+            errors.errors()[0].to_string(),
+            "Unable to resolve appender `async`: appender forwards records to itself"
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "yaml_format", feature = "file_appender"))]
+    fn deserialize() {
+        use crate::config::RawConfig;
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = format!(
+            "
+appenders:
+    foo:
+        kind: async
+        capacity: 16
+        overflow: drop_oldest
+        appender:
+            kind: file
+            path: {}/foo.log
+",
+            dir.path().display()
+        );
+
+        let config = ::serde_yaml::from_str::<RawConfig>(&config).unwrap();
+        let errors = config.appenders_lossy(&Deserializers::new()).1;
+        assert!(errors.is_empty());
+    }
+}
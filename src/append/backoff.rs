@@ -0,0 +1,86 @@
+//! Exponential backoff between connection attempts of network appenders.
+
+use std::time::{Duration, Instant};
+
+pub(crate) const DEFAULT_DELAY: Duration = Duration::from_millis(100);
+pub(crate) const DEFAULT_MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// Spaces out connection attempts after a failure, doubling the delay after
+/// every failed attempt up to a maximum.
+#[derive(Debug)]
+pub(crate) struct Backoff {
+    pub(crate) initial: Duration,
+    pub(crate) max: Duration,
+    delay: Duration,
+    next_attempt: Option<Instant>,
+}
+
+impl Backoff {
+    pub(crate) fn new(initial: Duration, max: Duration) -> Backoff {
+        Backoff {
+            initial,
+            max,
+            delay: initial,
+            next_attempt: None,
+        }
+    }
+
+    /// Returns whether an attempt may be made now.
+    pub(crate) fn ready(&self) -> bool {
+        self.next_attempt
+            .is_none_or(|next_attempt| Instant::now() >= next_attempt)
+    }
+
+    /// Records a failed attempt, or the loss of a connection.
+    ///
+    /// Returns whether this is the first failure since the last success.
+    pub(crate) fn failed(&mut self) -> bool {
+        let first = self.next_attempt.is_none();
+        self.next_attempt = Some(Instant::now() + self.delay);
+        self.delay = next_delay(self.delay, self.max);
+        first
+    }
+
+    /// Records a successful attempt.
+    pub(crate) fn succeeded(&mut self) {
+        self.delay = self.initial;
+        self.next_attempt = None;
+    }
+}
+
+fn next_delay(delay: Duration, max: Duration) -> Duration {
+    delay.saturating_mul(2).min(max)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn delays() {
+        let max = Duration::from_secs(1);
+        assert_eq!(
+            next_delay(Duration::from_millis(100), max),
+            Duration::from_millis(200)
+        );
+        assert_eq!(next_delay(Duration::from_millis(600), max), max);
+        assert_eq!(next_delay(Duration::ZERO, max), Duration::ZERO);
+    }
+
+    #[test]
+    fn backoff() {
+        let mut backoff = Backoff::new(Duration::from_secs(60), Duration::from_secs(120));
+        assert!(backoff.ready());
+
+        assert!(backoff.failed());
+        assert!(!backoff.ready());
+        assert!(!backoff.failed());
+        assert_eq!(backoff.delay, Duration::from_secs(120));
+
+        backoff.succeeded();
+        assert!(backoff.failed());
+        backoff.succeeded();
+        assert!(backoff.ready());
+        assert_eq!(backoff.delay, Duration::from_secs(60));
+    }
+}
@@ -0,0 +1,260 @@
+//! The capture appender, for asserting on log output in tests.
+//!
+//! Requires the `testing` feature.
+//!
+//! A `CaptureAppender` keeps an `OwnedRecord` copy of every record it is given,
+//! which can be inspected through a `CaptureHandle`:
+//!
+//! ```
+//! use log::{Level, LevelFilter};
+//! use log4rs::append::capture;
+//!
+//! let (records, _guard) = capture::capture(LevelFilter::Debug).unwrap();
+//! log::warn!("disk {} is full", "/dev/sda1");
+//!
+//! assert!(records.contains(Level::Warn, "is full"));
+//! ```
+//!
+//! `scoped` and `capture` install a global logger which dispatches records to
+//! a `Logger` chosen per thread. Since the test harness runs every test on its
+//! own thread, tests capturing logs this way do not see each other's records,
+//! but neither do they see records logged by threads they spawn. They cannot
+//! be combined with another global logger in the same test binary.
+
+use log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+use parking_lot::Mutex;
+use std::{cell::RefCell, marker::PhantomData, sync::Arc, sync::OnceLock};
+
+use crate::{
+    append::{owned::OwnedRecord, Append},
+    config::{Appender, Config, Root},
+    Logger,
+};
+
+/// A handle to the records seen by a `CaptureAppender`.
+///
+/// The handle remains valid after the appender has been moved into a `Config`.
+#[derive(Clone, Debug, Default)]
+pub struct CaptureHandle(Arc<Mutex<Vec<OwnedRecord>>>);
+
+impl CaptureHandle {
+    /// Returns a copy of the records seen so far, oldest first.
+    pub fn records(&self) -> Vec<OwnedRecord> {
+        self.0.lock().clone()
+    }
+
+    /// Returns the formatted messages of the records seen so far, oldest
+    /// first.
+    pub fn messages(&self) -> Vec<String> {
+        self.0
+            .lock()
+            .iter()
+            .map(|r| r.message().to_owned())
+            .collect()
+    }
+
+    /// Returns `true` if a record at `level` whose message contains
+    /// `substring` has been seen.
+    pub fn contains(&self, level: Level, substring: &str) -> bool {
+        self.0
+            .lock()
+            .iter()
+            .any(|r| r.level() == level && r.message().contains(substring))
+    }
+
+    /// Discards the records seen so far.
+    pub fn clear(&self) {
+        self.0.lock().clear();
+    }
+}
+
+/// An appender which keeps a copy of every record in memory.
+#[derive(Debug, Default)]
+pub struct CaptureAppender(CaptureHandle);
+
+impl CaptureAppender {
+    /// Creates a new `CaptureAppender`.
+    pub fn new() -> CaptureAppender {
+        CaptureAppender::default()
+    }
+
+    /// Returns a handle to the captured records.
+    pub fn handle(&self) -> CaptureHandle {
+        self.0.clone()
+    }
+}
+
+impl Append for CaptureAppender {
+    fn append(&self, record: &Record<'_>) -> anyhow::Result<()> {
+        let record = OwnedRecord::new(record);
+        self.0 .0.lock().push(record);
+        Ok(())
+    }
+
+    fn flush(&self) {}
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<Arc<Logger>>> = const { RefCell::new(None) };
+}
+
+struct Dispatcher;
+
+static DISPATCHER: Dispatcher = Dispatcher;
+
+impl Dispatcher {
+    fn with<F, T>(f: F) -> Option<T>
+    where
+        F: FnOnce(&Logger) -> T,
+    {
+        // The thread local is gone while the thread is being torn down.
+        CURRENT
+            .try_with(|current| current.borrow().clone())
+            .ok()
+            .flatten()
+            .map(|logger| f(&logger))
+    }
+}
+
+impl Log for Dispatcher {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        Dispatcher::with(|logger| logger.enabled(metadata)).unwrap_or(false)
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        Dispatcher::with(|logger| logger.log(record));
+    }
+
+    fn flush(&self) {
+        Dispatcher::with(Log::flush);
+    }
+}
+
+fn install() -> Result<(), SetLoggerError> {
+    static INSTALLED: OnceLock<bool> = OnceLock::new();
+
+    if *INSTALLED.get_or_init(|| log::set_logger(&DISPATCHER).is_ok()) {
+        // The level of each thread's logger is checked by the dispatcher.
+        log::set_max_level(LevelFilter::Trace);
+        Ok(())
+    } else {
+        // Another logger was installed first, so this fails the same way.
+        log::set_logger(&DISPATCHER)
+    }
+}
+
+/// A guard which routes the current thread's records to a `Logger` until it
+/// is dropped.
+///
+/// Created by `scoped` and `capture`.
+#[derive(Debug)]
+#[must_use = "the logger is uninstalled when the guard is dropped"]
+pub struct ScopedLogger {
+    previous: Option<Arc<Logger>>,
+    // The guard restores a thread local, so it must stay on its thread.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for ScopedLogger {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        let _ = CURRENT.try_with(|current| {
+            let logger = current.replace(previous);
+            if let Some(logger) = logger {
+                Log::flush(&*logger);
+            }
+        });
+    }
+}
+
+/// Routes records logged on the current thread to `logger` until the
+/// returned guard is dropped.
+///
+/// Guards may be nested, in which case dropping the inner guard restores the
+/// outer logger.
+///
+/// # Errors
+///
+/// Returns an error if a global logger other than the one used by this module
+/// has already been installed.
+pub fn scoped(logger: Logger) -> Result<ScopedLogger, SetLoggerError> {
+    install()?;
+    let previous = CURRENT.with(|current| current.replace(Some(Arc::new(logger))));
+    Ok(ScopedLogger {
+        previous,
+        _not_send: PhantomData,
+    })
+}
+
+/// Captures the records logged on the current thread at or above `level`
+/// until the returned guard is dropped.
+///
+/// # Errors
+///
+/// Returns an error if a global logger other than the one used by this module
+/// has already been installed.
+pub fn capture(level: LevelFilter) -> Result<(CaptureHandle, ScopedLogger), SetLoggerError> {
+    let appender = CaptureAppender::new();
+    let handle = appender.handle();
+    let config = Config::builder()
+        .appender(Appender::builder().build("capture", Box::new(appender)))
+        .build(Root::builder().appender("capture").build(level))
+        .expect("capture config is valid");
+
+    let guard = scoped(Logger::new(config))?;
+    Ok((handle, guard))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn appender() {
+        let appender = CaptureAppender::new();
+        let handle = appender.handle();
+
+        log_mdc::insert("request", "42");
+        appender
+            .append(
+                &Record::builder()
+                    .level(Level::Info)
+                    .target("target")
+                    .module_path(Some("module"))
+                    .file(Some("file.rs"))
+                    .line(Some(7))
+                    .args(format_args!("hello {}", "world"))
+                    .build(),
+            )
+            .unwrap();
+        log_mdc::remove("request");
+
+        let records = handle.records();
+        assert_eq!(records.len(), 1);
+        let record = &records[0];
+        assert_eq!(record.level(), Level::Info);
+        assert_eq!(record.target(), "target");
+        assert_eq!(record.message(), "hello world");
+        assert_eq!(record.module_path(), Some("module"));
+        assert_eq!(record.file(), Some("file.rs"));
+        assert_eq!(record.line(), Some(7));
+        assert_eq!(record.mdc_value("request"), Some("42"));
+
+        assert!(handle.contains(Level::Info, "o w"));
+        assert!(!handle.contains(Level::Warn, "o w"));
+        handle.clear();
+        assert!(handle.records().is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "log_kv")]
+    fn key_values() {
+        let appender = CaptureAppender::new();
+        let kvs = [("user", log::kv::Value::from(7))];
+        appender
+            .append(&Record::builder().key_values(&kvs).build())
+            .unwrap();
+
+        assert_eq!(appender.handle().records()[0].key_value("user"), Some("7"));
+    }
+}
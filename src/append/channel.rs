@@ -0,0 +1,213 @@
+//! The channel appender.
+//!
+//! Requires the `channel_appender` feature.
+//!
+//! A `ChannelAppender` sends each record into a bounded `std::sync::mpsc`
+//! channel, either as an `OwnedRecord` or as the bytes produced by an encoder,
+//! to be consumed by other code.
+
+use derive_more::Debug;
+use log::Record;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    mpsc::{self, Receiver, SyncSender, TrySendError},
+    Arc,
+};
+
+use crate::{
+    append::{owned::OwnedRecord, Append},
+    encode::{writer::simple::SimpleWriter, Encode},
+};
+
+const DEFAULT_CAPACITY: usize = 1024;
+
+/// A handle to the number of records discarded by a `ChannelAppender`.
+///
+/// The handle remains valid after the appender has been moved into a `Config`.
+#[derive(Clone, Debug, Default)]
+pub struct DroppedCounter(Arc<AtomicU64>);
+
+impl DroppedCounter {
+    /// Returns the number of records discarded so far.
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Debug)]
+enum Sender {
+    Records(SyncSender<OwnedRecord>),
+    Encoded(SyncSender<Vec<u8>>, Box<dyn Encode>),
+}
+
+/// An appender which sends records into a channel.
+#[derive(Debug)]
+pub struct ChannelAppender {
+    sender: Sender,
+    block: bool,
+    dropped: DroppedCounter,
+}
+
+impl ChannelAppender {
+    /// Creates a new `ChannelAppender` builder.
+    pub fn builder() -> ChannelAppenderBuilder {
+        ChannelAppenderBuilder {
+            capacity: DEFAULT_CAPACITY,
+            block: false,
+        }
+    }
+
+    /// Returns the number of records discarded because the channel was full.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.get()
+    }
+
+    /// Returns a handle to the count of discarded records.
+    pub fn dropped_counter(&self) -> DroppedCounter {
+        self.dropped.clone()
+    }
+
+    fn send<T>(&self, sender: &SyncSender<T>, message: T) -> anyhow::Result<()> {
+        if self.block {
+            return sender
+                .send(message)
+                .map_err(|_| anyhow::anyhow!("the channel receiver has been dropped"));
+        }
+
+        match sender.try_send(message) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => {
+                self.dropped.0.fetch_add(1, Ordering::Relaxed);
+                Ok(())
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                anyhow::bail!("the channel receiver has been dropped")
+            }
+        }
+    }
+}
+
+impl Append for ChannelAppender {
+    fn append(&self, record: &Record<'_>) -> anyhow::Result<()> {
+        match &self.sender {
+            Sender::Records(sender) => self.send(sender, OwnedRecord::new(record)),
+            Sender::Encoded(sender, encoder) => {
+                let mut buf = vec![];
+                encoder.encode(&mut SimpleWriter(&mut buf), record)?;
+                self.send(sender, buf)
+            }
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// A builder for `ChannelAppender`s.
+pub struct ChannelAppenderBuilder {
+    capacity: usize,
+    block: bool,
+}
+
+impl ChannelAppenderBuilder {
+    /// Sets the number of messages the channel can hold.
+    ///
+    /// Defaults to 1024.
+    pub fn capacity(mut self, capacity: usize) -> ChannelAppenderBuilder {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Sets whether appending waits for room in a full channel rather than
+    /// discarding the record.
+    ///
+    /// A blocked appender also blocks the thread which logged the record, so
+    /// the receiver must not log through the same appender.
+    ///
+    /// Defaults to `false`.
+    pub fn block(mut self, block: bool) -> ChannelAppenderBuilder {
+        self.block = block;
+        self
+    }
+
+    /// Consumes the `ChannelAppenderBuilder`, producing a `ChannelAppender`
+    /// which sends an `OwnedRecord` for each record, along with the receiving
+    /// end of its channel.
+    pub fn build_records(self) -> (ChannelAppender, Receiver<OwnedRecord>) {
+        let (sender, receiver) = mpsc::sync_channel(self.capacity);
+        (self.build(Sender::Records(sender)), receiver)
+    }
+
+    /// Consumes the `ChannelAppenderBuilder`, producing a `ChannelAppender`
+    /// which sends each record as encoded by `encoder`, along with the
+    /// receiving end of its channel.
+    pub fn build_encoded(self, encoder: Box<dyn Encode>) -> (ChannelAppender, Receiver<Vec<u8>>) {
+        let (sender, receiver) = mpsc::sync_channel(self.capacity);
+        (self.build(Sender::Encoded(sender, encoder)), receiver)
+    }
+
+    fn build(self, sender: Sender) -> ChannelAppender {
+        ChannelAppender {
+            sender,
+            block: self.block,
+            dropped: DroppedCounter::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use log::Level;
+
+    use super::*;
+
+    fn append(appender: &ChannelAppender, message: &str) -> anyhow::Result<()> {
+        appender.append(
+            &Record::builder()
+                .level(Level::Warn)
+                .target("target")
+                .args(format_args!("{}", message))
+                .build(),
+        )
+    }
+
+    #[test]
+    fn records() {
+        let (appender, receiver) = ChannelAppender::builder().build_records();
+
+        log_mdc::insert("channel::test::records", "1");
+        append(&appender, "hello").unwrap();
+        log_mdc::remove("channel::test::records");
+
+        let record = receiver.try_recv().unwrap();
+        assert_eq!(record.level(), Level::Warn);
+        assert_eq!(record.target(), "target");
+        assert_eq!(record.message(), "hello");
+        assert_eq!(record.mdc_value("channel::test::records"), Some("1"));
+    }
+
+    #[test]
+    #[cfg(feature = "pattern_encoder")]
+    fn encoded() {
+        use crate::encode::pattern::PatternEncoder;
+
+        let (appender, receiver) =
+            ChannelAppender::builder().build_encoded(Box::new(PatternEncoder::new("{l} {m}")));
+
+        append(&appender, "hello").unwrap();
+        assert_eq!(receiver.try_recv().unwrap(), b"WARN hello");
+    }
+
+    #[test]
+    fn full_and_disconnected() {
+        let (appender, receiver) = ChannelAppender::builder().capacity(1).build_records();
+        let dropped = appender.dropped_counter();
+
+        append(&appender, "one").unwrap();
+        append(&appender, "two").unwrap();
+        assert_eq!(dropped.get(), 1);
+        assert_eq!(receiver.try_recv().unwrap().message(), "one");
+
+        drop(receiver);
+        assert!(append(&appender, "three").is_err());
+    }
+}
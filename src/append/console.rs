@@ -29,8 +29,9 @@ use crate::{
 
 /// The console appender's configuration.
 #[cfg(feature = "config_parsing")]
-#[derive(Debug, serde::Deserialize)]
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
 #[serde(deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct ConsoleAppenderConfig {
     target: Option<ConfigTarget>,
     encoder: Option<EncoderConfig>,
@@ -38,7 +39,9 @@ pub struct ConsoleAppenderConfig {
 }
 
 #[cfg(feature = "config_parsing")]
-#[derive(Debug, serde::Deserialize)]
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "schema", schemars(rename = "ConsoleTarget"))]
 enum ConfigTarget {
     #[serde(rename = "stdout")]
     Stdout,
@@ -123,6 +126,10 @@ pub struct ConsoleAppender {
     writer: Writer,
     encoder: Box<dyn Encode>,
     do_write: bool,
+    #[cfg_attr(not(feature = "config_parsing"), allow(dead_code))]
+    target: Target,
+    #[cfg_attr(not(feature = "config_parsing"), allow(dead_code))]
+    tty_only: bool,
 }
 
 impl Append for ConsoleAppender {
@@ -136,6 +143,19 @@ impl Append for ConsoleAppender {
     }
 
     fn flush(&self) {}
+
+    #[cfg(feature = "config_parsing")]
+    fn config_value(&self) -> Option<serde_value::Value> {
+        let config = ConsoleAppenderConfig {
+            target: Some(match self.target {
+                Target::Stdout => ConfigTarget::Stdout,
+                Target::Stderr => ConfigTarget::Stderr,
+            }),
+            encoder: Some(EncoderConfig::describe(&*self.encoder)?),
+            tty_only: Some(self.tty_only),
+        };
+        crate::config::component_config("console", &config)
+    }
 }
 
 impl ConsoleAppender {
@@ -200,6 +220,8 @@ impl ConsoleAppenderBuilder {
                 .encoder
                 .unwrap_or_else(|| Box::<PatternEncoder>::default()),
             do_write,
+            target: self.target,
+            tty_only: self.tty_only,
         }
     }
 }
@@ -261,4 +283,24 @@ impl Deserialize for ConsoleAppenderDeserializer {
         }
         Ok(Box::new(appender.build()))
     }
+
+    fn check_components(
+        &self,
+        config: &ConsoleAppenderConfig,
+        deserializers: &Deserializers,
+    ) -> anyhow::Result<()> {
+        match &config.encoder {
+            Some(encoder) => deserializers.check_nested::<dyn Encode>(
+                "encoder",
+                &encoder.kind,
+                encoder.config.clone(),
+            ),
+            None => Ok(()),
+        }
+    }
+
+    #[cfg(feature = "schema")]
+    fn schema(&self, generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        <ConsoleAppenderConfig as schemars::JsonSchema>::json_schema(generator)
+    }
 }
@@ -0,0 +1,697 @@
+//! The failover appender.
+//!
+//! Requires the `failover_appender` feature.
+//!
+//! A `FailoverAppender` holds an ordered list of appenders. Records go to the
+//! first appender which accepts them: when the active appender returns an
+//! error, the following ones are tried in turn and the first to succeed
+//! becomes active. Appenders can be given inline or refer by name to other
+//! appenders of the same `Config`.
+//!
+//! Each time the active appender changes to a later one, the failover is
+//! counted and a `FailedOver` error is returned, which the logger reports
+//! through its error handler under the appender's error policy like any
+//! other. The record has been appended by then, and appenders which pass
+//! records on, such as another failover appender, do not treat the error as
+//! a failure. Otherwise an error is only returned if every appender fails.
+//! Optionally, the first appender is retried once a cooldown has passed since
+//! the failover.
+
+use derive_more::Debug;
+use log::Record;
+use parking_lot::Mutex;
+use std::{
+    fmt::Write as _,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::append::{Append, Appenders};
+#[cfg(feature = "config_parsing")]
+use crate::{
+    append::AppenderConfig,
+    config::{de_duration, ser_duration, Deserialize, Deserializers},
+};
+
+/// The error returned by a `FailoverAppender` which has failed over to a
+/// later appender.
+///
+/// The record was appended by that appender, so the error only reports the
+/// failover.
+#[derive(Debug, thiserror::Error)]
+#[error("{message}")]
+pub struct FailedOver {
+    message: String,
+    failovers: u64,
+}
+
+impl FailedOver {
+    /// Returns the number of times the appender had failed over, including
+    /// this time.
+    pub fn failovers(&self) -> u64 {
+        self.failovers
+    }
+}
+
+/// The failover appender's configuration.
+#[cfg(feature = "config_parsing")]
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct FailoverAppenderConfig {
+    appenders: Vec<ConfigMember>,
+    #[serde(
+        deserialize_with = "de_duration",
+        serialize_with = "ser_duration",
+        default
+    )]
+    #[cfg_attr(
+        feature = "schema",
+        schemars(schema_with = "crate::config::schema::duration")
+    )]
+    retry_after: Option<Duration>,
+}
+
+#[cfg(feature = "config_parsing")]
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+#[serde(untagged)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "schema", schemars(rename = "FailoverMember"))]
+enum ConfigMember {
+    Name(String),
+    Inline(AppenderConfig),
+}
+
+/// A handle to the number of times a `FailoverAppender` has failed over.
+///
+/// The handle remains valid after the appender has been moved into a `Config`.
+#[derive(Clone, Debug, Default)]
+pub struct FailoverCounter(Arc<AtomicU64>);
+
+impl FailoverCounter {
+    /// Returns the number of failovers so far.
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Debug)]
+enum Member {
+    Inline(Arc<dyn Append>),
+    Named(String, #[debug(skip)] Mutex<Option<Arc<dyn Append>>>),
+}
+
+impl Member {
+    fn appender(&self) -> Option<Arc<dyn Append>> {
+        match self {
+            Member::Inline(appender) => Some(appender.clone()),
+            Member::Named(_, appender) => appender.lock().clone(),
+        }
+    }
+
+    fn describe(&self, index: usize) -> String {
+        match self {
+            Member::Inline(_) => format!("appender #{}", index),
+            Member::Named(name, _) => format!("appender `{}`", name),
+        }
+    }
+}
+
+struct State {
+    active: usize,
+    // When the first appender was last found to be failing.
+    failed_at: Option<Instant>,
+}
+
+/// An appender which passes records to the first of several appenders that
+/// accepts them.
+#[derive(Debug)]
+pub struct FailoverAppender {
+    members: Vec<Member>,
+    retry_after: Option<Duration>,
+    failovers: FailoverCounter,
+    #[debug(skip)]
+    state: Mutex<State>,
+}
+
+impl FailoverAppender {
+    /// Creates a new `FailoverAppender` builder.
+    pub fn builder() -> FailoverAppenderBuilder {
+        FailoverAppenderBuilder {
+            members: vec![],
+            retry_after: None,
+        }
+    }
+
+    /// Returns the number of times the appender has failed over.
+    pub fn failovers(&self) -> u64 {
+        self.failovers.get()
+    }
+
+    /// Returns a handle to the count of failovers.
+    pub fn failover_counter(&self) -> FailoverCounter {
+        self.failovers.clone()
+    }
+
+    fn start(&self) -> usize {
+        let state = self.state.lock();
+        match (state.failed_at, self.retry_after) {
+            (Some(failed_at), Some(retry_after))
+                if state.active > 0 && failed_at.elapsed() >= retry_after =>
+            {
+                0
+            }
+            _ => state.active,
+        }
+    }
+
+    fn message(&self, mut message: String, errors: Vec<(usize, anyhow::Error)>) -> String {
+        for (index, error) in errors {
+            let member = &self.members[index];
+            let _ = write!(message, "; {}: {:#}", member.describe(index), error);
+        }
+        message
+    }
+}
+
+impl Append for FailoverAppender {
+    fn append(&self, record: &Record<'_>) -> anyhow::Result<()> {
+        let start = self.start();
+
+        let mut errors = vec![];
+        for (index, member) in self.members.iter().enumerate().skip(start) {
+            let result = match member.appender() {
+                Some(appender) => appender.append(record),
+                None => Err(anyhow::anyhow!("not resolved")),
+            };
+            let notice = match result {
+                Ok(()) => None,
+                // a member which failed over itself still appended the record
+                Err(error) if error.is::<FailedOver>() => Some(error),
+                Err(error) => {
+                    errors.push((index, error));
+                    continue;
+                }
+            };
+
+            let mut state = self.state.lock();
+            let previous = state.active;
+            state.active = index;
+            if index == 0 {
+                state.failed_at = None;
+            } else if start == 0 {
+                state.failed_at = Some(Instant::now());
+            }
+            drop(state);
+
+            if index <= previous {
+                return notice.map_or(Ok(()), Err);
+            }
+            let failovers = self.failovers.0.fetch_add(1, Ordering::Relaxed) + 1;
+            let message = format!(
+                "failed over to {} (failover {})",
+                member.describe(index),
+                failovers
+            );
+            let message = self.message(message, errors);
+            return Err(FailedOver { message, failovers }.into());
+        }
+
+        if start == 0 {
+            self.state.lock().failed_at = Some(Instant::now());
+        }
+        let message = self.message("every failover appender failed".to_owned(), errors);
+        Err(anyhow::anyhow!(message))
+    }
+
+    fn flush(&self) {
+        for member in &self.members {
+            if let Some(appender) = member.appender() {
+                appender.flush();
+            }
+        }
+    }
+
+    fn resolve(&self, appenders: &Appenders<'_>) -> anyhow::Result<()> {
+        for member in &self.members {
+            match member {
+                Member::Inline(appender) => appender.resolve(appenders)?,
+                Member::Named(name, target) => {
+                    let appender = match appenders.get(name) {
+                        Some(appender) => appender,
+                        None => anyhow::bail!("no appender named `{}` to fail over to", name),
+                    };
+                    *target.lock() = Some(appender);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn targets(&self) -> Vec<&str> {
+        self.members
+            .iter()
+            .flat_map(|member| match member {
+                Member::Inline(appender) => appender.targets(),
+                Member::Named(name, _) => vec![&**name],
+            })
+            .collect()
+    }
+
+    #[cfg(feature = "config_parsing")]
+    fn config_value(&self) -> Option<serde_value::Value> {
+        let appenders = self
+            .members
+            .iter()
+            .map(|member| match member {
+                Member::Inline(appender) => {
+                    AppenderConfig::describe(&**appender).map(ConfigMember::Inline)
+                }
+                Member::Named(name, _) => Some(ConfigMember::Name(name.clone())),
+            })
+            .collect::<Option<Vec<_>>>()?;
+        let config = FailoverAppenderConfig {
+            appenders,
+            retry_after: self.retry_after,
+        };
+        crate::config::component_config("failover", &config)
+    }
+}
+
+/// A builder for `FailoverAppender`s.
+pub struct FailoverAppenderBuilder {
+    members: Vec<Member>,
+    retry_after: Option<Duration>,
+}
+
+impl FailoverAppenderBuilder {
+    /// Adds an appender to the end of the list.
+    pub fn appender(mut self, appender: Box<dyn Append>) -> FailoverAppenderBuilder {
+        self.members.push(Member::Inline(Arc::from(appender)));
+        self
+    }
+
+    /// Adds a reference to another appender of the same `Config` to the end
+    /// of the list.
+    ///
+    /// The appender is looked up when the `Config` is built.
+    pub fn appender_ref(mut self, name: &str) -> FailoverAppenderBuilder {
+        self.members
+            .push(Member::Named(name.to_owned(), Mutex::new(None)));
+        self
+    }
+
+    /// Sets how long to wait after failing over from the first appender before
+    /// trying it again.
+    ///
+    /// Defaults to never retrying it.
+    pub fn retry_after(mut self, retry_after: Duration) -> FailoverAppenderBuilder {
+        self.retry_after = Some(retry_after);
+        self
+    }
+
+    /// Consumes the `FailoverAppenderBuilder`, producing a `FailoverAppender`.
+    pub fn build(self) -> FailoverAppender {
+        FailoverAppender {
+            members: self.members,
+            retry_after: self.retry_after,
+            failovers: FailoverCounter::default(),
+            state: Mutex::new(State {
+                active: 0,
+                failed_at: None,
+            }),
+        }
+    }
+}
+
+/// A deserializer for the `FailoverAppender`.
+///
+/// # Configuration
+///
+/// ```yaml
+/// kind: failover
+///
+/// # The appenders to try, in order. Each is either the name of another
+/// # appender or an inline appender configuration of any kind. Required.
+/// appenders:
+///   - file
+///   - kind: console
+///     target: stderr
+///
+/// # How long to wait after failing over from the first appender before trying
+/// # it again. By default it is not retried.
+/// retry_after: 30 seconds
+/// ```
+#[cfg(feature = "config_parsing")]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct FailoverAppenderDeserializer;
+
+#[cfg(feature = "config_parsing")]
+impl Deserialize for FailoverAppenderDeserializer {
+    type Trait = dyn Append;
+
+    type Config = FailoverAppenderConfig;
+
+    fn deserialize(
+        &self,
+        config: FailoverAppenderConfig,
+        deserializers: &Deserializers,
+    ) -> anyhow::Result<Box<dyn Append>> {
+        if config.appenders.is_empty() {
+            anyhow::bail!("a failover appender requires at least one appender");
+        }
+
+        let mut builder = FailoverAppender::builder();
+        for member in config.appenders {
+            builder = match member {
+                ConfigMember::Name(name) => builder.appender_ref(&name),
+                ConfigMember::Inline(appender) => {
+                    if !appender.filters.is_empty() || appender.on_error.is_some() {
+                        anyhow::bail!(
+                            "filters and error policies must be attached to the failover appender rather than an inline appender"
+                        );
+                    }
+                    builder.appender(deserializers.deserialize(&appender.kind, appender.config)?)
+                }
+            };
+        }
+        if let Some(retry_after) = config.retry_after {
+            builder = builder.retry_after(retry_after);
+        }
+        Ok(Box::new(builder.build()))
+    }
+
+    fn check_components(
+        &self,
+        config: &FailoverAppenderConfig,
+        deserializers: &Deserializers,
+    ) -> anyhow::Result<()> {
+        for (index, member) in config.appenders.iter().enumerate() {
+            if let ConfigMember::Inline(appender) = member {
+                appender.check(&format!("appenders.{}", index), deserializers)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn targets(
+        &self,
+        config: &FailoverAppenderConfig,
+        deserializers: &Deserializers,
+    ) -> Vec<String> {
+        config
+            .appenders
+            .iter()
+            .flat_map(|member| match member {
+                ConfigMember::Inline(appender) => appender.targets(deserializers),
+                ConfigMember::Name(name) => vec![name.clone()],
+            })
+            .collect()
+    }
+
+    #[cfg(feature = "schema")]
+    fn schema(&self, generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        <FailoverAppenderConfig as schemars::JsonSchema>::json_schema(generator)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use derive_more::Debug;
+    use log::LevelFilter;
+    use std::{sync::atomic::AtomicBool, thread};
+
+    use super::*;
+    use crate::config::{Appender, Config, Root};
+
+    #[derive(Debug)]
+    struct Flaky {
+        failing: Arc<AtomicBool>,
+        log: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Append for Flaky {
+        fn append(&self, record: &Record<'_>) -> anyhow::Result<()> {
+            if self.failing.load(Ordering::SeqCst) {
+                anyhow::bail!("disk full");
+            }
+            self.log.lock().push(record.args().to_string());
+            Ok(())
+        }
+
+        fn flush(&self) {}
+    }
+
+    type Parts = (Box<dyn Append>, Arc<AtomicBool>, Arc<Mutex<Vec<String>>>);
+
+    fn flaky() -> Parts {
+        let failing = Arc::new(AtomicBool::new(false));
+        let log = Arc::new(Mutex::new(vec![]));
+        let appender = Flaky {
+            failing: failing.clone(),
+            log: log.clone(),
+        };
+        (Box::new(appender), failing, log)
+    }
+
+    fn append(appender: &dyn Append, message: &str) -> anyhow::Result<()> {
+        appender.append(&Record::builder().args(format_args!("{}", message)).build())
+    }
+
+    #[test]
+    fn fails_over() {
+        let (primary, primary_failing, primary_log) = flaky();
+        let (secondary, _, secondary_log) = flaky();
+        let appender = FailoverAppender::builder()
+            .appender(primary)
+            .appender(secondary)
+            .build();
+
+        append(&appender, "one").unwrap();
+        primary_failing.store(true, Ordering::SeqCst);
+
+        // The record was delivered, so the error only reports the failover.
+        let err = append(&appender, "two").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "failed over to appender #1 (failover 1); appender #0: disk full"
+        );
+        assert_eq!(err.downcast_ref::<FailedOver>().unwrap().failovers(), 1);
+        assert_eq!(appender.failovers(), 1);
+        append(&appender, "three").unwrap();
+
+        // The primary is not retried without a cooldown.
+        primary_failing.store(false, Ordering::SeqCst);
+        append(&appender, "four").unwrap();
+
+        assert_eq!(*primary_log.lock(), ["one"]);
+        assert_eq!(*secondary_log.lock(), ["two", "three", "four"]);
+        assert_eq!(appender.failovers(), 1);
+    }
+
+    #[test]
+    fn retries_primary() {
+        let (primary, primary_failing, primary_log) = flaky();
+        let (secondary, _, secondary_log) = flaky();
+        let appender = FailoverAppender::builder()
+            .appender(primary)
+            .appender(secondary)
+            .retry_after(Duration::from_millis(50))
+            .build();
+
+        primary_failing.store(true, Ordering::SeqCst);
+        append(&appender, "one").unwrap_err();
+
+        // Still failing after the cooldown, so the secondary stays active.
+        thread::sleep(Duration::from_millis(60));
+        append(&appender, "two").unwrap();
+
+        primary_failing.store(false, Ordering::SeqCst);
+        append(&appender, "three").unwrap();
+        thread::sleep(Duration::from_millis(60));
+        append(&appender, "four").unwrap();
+
+        assert_eq!(*primary_log.lock(), ["four"]);
+        assert_eq!(*secondary_log.lock(), ["one", "two", "three"]);
+        assert_eq!(appender.failovers(), 1);
+    }
+
+    #[test]
+    fn all_fail() {
+        let (primary, primary_failing, _) = flaky();
+        let appender = FailoverAppender::builder()
+            .appender(primary)
+            .appender_ref("unresolved")
+            .build();
+
+        primary_failing.store(true, Ordering::SeqCst);
+        let err = append(&appender, "one").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "every failover appender failed; appender #0: disk full; \
+             appender `unresolved`: not resolved"
+        );
+    }
+
+    #[test]
+    fn named() {
+        let (primary, primary_failing, _) = flaky();
+        let (secondary, _, secondary_log) = flaky();
+        let appender = FailoverAppender::builder()
+            .appender(primary)
+            .appender_ref("secondary")
+            .build();
+
+        let config = Config::builder()
+            .appender(Appender::builder().build("failover", Box::new(appender)))
+            .appender(Appender::builder().build("secondary", secondary))
+            .build(
+                Root::builder()
+                    .appender("failover")
+                    .build(LevelFilter::Info),
+            )
+            .unwrap();
+
+        primary_failing.store(true, Ordering::SeqCst);
+        append(config.appenders()[0].appender(), "one").unwrap_err();
+        assert_eq!(*secondary_log.lock(), ["one"]);
+
+        let missing = FailoverAppender::builder().appender_ref("missing").build();
+        let errors = Config::builder()
+            .appender(Appender::builder().build("failover", Box::new(missing)))
+            .build(Root::builder().build(LevelFilter::Info))
+            .unwrap_err();
+        assert_eq!(errors.errors().len(), 1);
+    }
+
+    #[test]
+    fn nested() {
+        let (primary, primary_failing, _) = flaky();
+        let (secondary, _, secondary_log) = flaky();
+        let (fallback, _, fallback_log) = flaky();
+        let inner = FailoverAppender::builder()
+            .appender(primary)
+            .appender(secondary)
+            .build();
+        let appender = FailoverAppender::builder()
+            .appender(Box::new(inner))
+            .appender(fallback)
+            .build();
+
+        // The inner appender failing over is not a failure of the outer one.
+        primary_failing.store(true, Ordering::SeqCst);
+        let err = append(&appender, "one").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "failed over to appender #1 (failover 1); appender #0: disk full"
+        );
+        append(&appender, "two").unwrap();
+
+        assert_eq!(*secondary_log.lock(), ["one", "two"]);
+        assert!(fallback_log.lock().is_empty());
+        assert_eq!(appender.failovers(), 0);
+    }
+
+    #[test]
+    fn reported() {
+        use crate::{config::ErrorPolicy, Logger};
+        use log::Log;
+
+        for (policy, expected) in [(ErrorPolicy::Report, 1), (ErrorPolicy::Ignore, 0)] {
+            let (primary, primary_failing, _) = flaky();
+            let (secondary, _, secondary_log) = flaky();
+            let appender = FailoverAppender::builder()
+                .appender(primary)
+                .appender(secondary)
+                .build();
+            let config = Config::builder()
+                .appender(
+                    Appender::builder()
+                        .error_policy(policy)
+                        .build("failover", Box::new(appender)),
+                )
+                .build(
+                    Root::builder()
+                        .appender("failover")
+                        .build(LevelFilter::Info),
+                )
+                .unwrap();
+
+            let reported = Arc::new(Mutex::new(vec![]));
+            let logger = {
+                let reported = reported.clone();
+                Logger::new_with_err_handler(
+                    config,
+                    Box::new(move |e| reported.lock().push(e.to_string())),
+                )
+            };
+            primary_failing.store(true, Ordering::SeqCst);
+            for message in ["one", "two"] {
+                logger.log(
+                    &Record::builder()
+                        .level(log::Level::Info)
+                        .args(format_args!("{}", message))
+                        .build(),
+                );
+            }
+
+            assert_eq!(*secondary_log.lock(), ["one", "two"]);
+            assert_eq!(reported.lock().len(), expected);
+        }
+    }
+
+    #[test]
+    fn cycle() {
+        let (primary, _, _) = flaky();
+        let a = FailoverAppender::builder().appender_ref("b").build();
+        let b = FailoverAppender::builder()
+            .appender(primary)
+            .appender(Box::new(
+                FailoverAppender::builder().appender_ref("a").build(),
+            ))
+            .build();
+
+        let (config, errors) = Config::builder()
+            .appender(Appender::builder().build("a", Box::new(a)))
+            .appender(Appender::builder().build("b", Box::new(b)))
+            .build_lossy(Root::builder().build(LevelFilter::Info));
+
+        assert!(config.appenders().is_empty());
+        let errors = errors
+            .errors()
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            errors,
+            [
+                "Unable to resolve appender `a`: appender forwards records back to itself through `b`",
+                "Unable to resolve appender `b`: appender forwards records back to itself through `a`",
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "yaml_format", feature = "console_appender"))]
+    fn deserialize() {
+        use crate::config::RawConfig;
+
+        let config = "
+appenders:
+    foo:
+        kind: failover
+        retry_after: 30 seconds
+        appenders:
+            - bar
+            - kind: console
+              target: stderr
+";
+
+        let config = ::serde_yaml::from_str::<RawConfig>(config).unwrap();
+        let errors = config.appenders_lossy(&Deserializers::new()).1;
+        assert!(errors.is_empty());
+    }
+}
@@ -30,8 +30,9 @@ use crate::{
 
 /// The file appender's configuration.
 #[cfg(feature = "config_parsing")]
-#[derive(Clone, Eq, PartialEq, Hash, Debug, Default, serde::Deserialize)]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default, serde::Deserialize, serde::Serialize)]
 #[serde(deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct FileAppenderConfig {
     path: String,
     encoder: Option<EncoderConfig>,
@@ -46,6 +47,8 @@ pub struct FileAppender {
     #[debug(skip)]
     file: Mutex<SimpleWriter<BufWriter<File>>>,
     encoder: Box<dyn Encode>,
+    #[cfg_attr(not(feature = "config_parsing"), allow(dead_code))]
+    append: bool,
 }
 
 impl Append for FileAppender {
@@ -57,6 +60,16 @@ impl Append for FileAppender {
     }
 
     fn flush(&self) {}
+
+    #[cfg(feature = "config_parsing")]
+    fn config_value(&self) -> Option<serde_value::Value> {
+        let config = FileAppenderConfig {
+            path: self.path.to_str()?.to_owned(),
+            encoder: Some(EncoderConfig::describe(&*self.encoder)?),
+            append: Some(self.append),
+        };
+        crate::config::component_config("file", &config)
+    }
 }
 
 impl FileAppender {
@@ -124,6 +137,7 @@ impl FileAppenderBuilder {
             encoder: self
                 .encoder
                 .unwrap_or_else(|| Box::<PatternEncoder>::default()),
+            append: self.append,
         })
     }
 
@@ -204,6 +218,26 @@ impl Deserialize for FileAppenderDeserializer {
         }
         Ok(Box::new(appender.build(&config.path)?))
     }
+
+    fn check_components(
+        &self,
+        config: &FileAppenderConfig,
+        deserializers: &Deserializers,
+    ) -> anyhow::Result<()> {
+        match &config.encoder {
+            Some(encoder) => deserializers.check_nested::<dyn Encode>(
+                "encoder",
+                &encoder.kind,
+                encoder.config.clone(),
+            ),
+            None => Ok(()),
+        }
+    }
+
+    #[cfg(feature = "schema")]
+    fn schema(&self, generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        <FileAppenderConfig as schemars::JsonSchema>::json_schema(generator)
+    }
 }
 
 #[cfg(test)]
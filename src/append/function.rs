@@ -0,0 +1,110 @@
+//! The function appender.
+//!
+//! Requires the `function_appender` feature.
+
+use derive_more::Debug;
+use log::Record;
+
+use crate::append::Append;
+
+type AppendFn = dyn Fn(&Record<'_>) -> anyhow::Result<()> + Send + Sync;
+type FlushFn = dyn Fn() + Send + Sync;
+
+/// An appender which passes each record to a closure.
+///
+/// ```
+/// use log4rs::{append::function::FnAppender, config::Appender};
+///
+/// let appender = FnAppender::new(|record| {
+///     eprintln!("{}: {}", record.level(), record.args());
+///     Ok(())
+/// });
+/// let appender = Appender::builder().build("callback", Box::new(appender));
+/// ```
+#[derive(Debug)]
+pub struct FnAppender {
+    #[debug(skip)]
+    append: Box<AppendFn>,
+    #[debug(skip)]
+    flush: Option<Box<FlushFn>>,
+}
+
+impl FnAppender {
+    /// Creates a new `FnAppender` which calls `f` for every record.
+    ///
+    /// Errors returned by `f` are passed to the logger's error handler.
+    pub fn new<F>(f: F) -> FnAppender
+    where
+        F: Fn(&Record<'_>) -> anyhow::Result<()> + Send + Sync + 'static,
+    {
+        FnAppender {
+            append: Box::new(f),
+            flush: None,
+        }
+    }
+
+    /// Sets a closure to be called when the appender is flushed.
+    pub fn on_flush<F>(mut self, f: F) -> FnAppender
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        self.flush = Some(Box::new(f));
+        self
+    }
+}
+
+impl Append for FnAppender {
+    fn append(&self, record: &Record<'_>) -> anyhow::Result<()> {
+        (self.append)(record)
+    }
+
+    fn flush(&self) {
+        if let Some(flush) = &self.flush {
+            flush();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    };
+
+    use super::*;
+
+    #[test]
+    fn append_and_flush() {
+        let messages = Arc::new(Mutex::new(vec![]));
+        let flushes = Arc::new(AtomicUsize::new(0));
+        let appender = {
+            let messages = messages.clone();
+            let flushes = flushes.clone();
+            FnAppender::new(move |record| {
+                messages.lock().unwrap().push(record.args().to_string());
+                Ok(())
+            })
+            .on_flush(move || {
+                flushes.fetch_add(1, Ordering::SeqCst);
+            })
+        };
+
+        appender
+            .append(&Record::builder().args(format_args!("hello")).build())
+            .unwrap();
+        appender.flush();
+
+        assert_eq!(*messages.lock().unwrap(), ["hello"]);
+        assert_eq!(flushes.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn error() {
+        let appender = FnAppender::new(|_| anyhow::bail!("nope"));
+        let err = appender
+            .append(&Record::builder().args(format_args!("hello")).build())
+            .unwrap_err();
+        assert_eq!(err.to_string(), "nope");
+    }
+}
@@ -0,0 +1,652 @@
+//! The GELF appender.
+//!
+//! Requires the `gelf_appender` feature.
+//!
+//! Each record is encoded, by default as a [GELF][gelf] message, and sent to
+//! a Graylog input over UDP or TCP.
+//!
+//! Over UDP, each message is sent as a datagram, optionally compressed with
+//! zlib or gzip. Messages larger than the chunk size are split into chunks as
+//! described by the GELF specification, up to 128 chunks per message. Over
+//! TCP, which does not support compression, each message is terminated by a
+//! null byte.
+//!
+//! The socket is opened lazily when the first record is appended. After an
+//! error it is reopened by a later record, waiting between attempts for a
+//! delay which doubles after every failure, from 100 milliseconds up to 30
+//! seconds; records appended in the meantime are discarded with an error.
+//! Writes to a TCP connection time out after 5 seconds.
+//!
+//! [gelf]: https://go2docs.graylog.org/current/getting_in_log_data/gelf.html
+
+use derive_more::Debug;
+use flate2::write::{GzEncoder, ZlibEncoder};
+use log::Record;
+use parking_lot::Mutex;
+use std::{
+    collections::hash_map::RandomState,
+    hash::BuildHasher,
+    io::{self, Write},
+    net::{SocketAddr, TcpStream, ToSocketAddrs, UdpSocket},
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+#[cfg(feature = "config_parsing")]
+use crate::config::{Deserialize, Deserializers};
+#[cfg(feature = "config_parsing")]
+use crate::encode::EncoderConfig;
+use crate::{
+    append::{
+        backoff::{self, Backoff},
+        Append,
+    },
+    encode::{gelf::GelfEncoder, writer::simple::SimpleWriter, Encode},
+};
+
+const DEFAULT_CHUNK_SIZE: usize = 1420;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+const CHUNK_MAGIC: [u8; 2] = [0x1e, 0x0f];
+const CHUNK_HEADER_LEN: usize = 12;
+const MAX_CHUNKS: usize = 128;
+
+/// The GELF appender's configuration.
+#[cfg(feature = "config_parsing")]
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct GelfAppenderConfig {
+    address: String,
+    protocol: Option<Protocol>,
+    compression: Option<Compression>,
+    chunk_size: Option<usize>,
+    encoder: Option<EncoderConfig>,
+}
+
+/// The transport protocol used by a `GelfAppender`.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+#[cfg_attr(
+    feature = "config_parsing",
+    derive(serde::Deserialize, serde::Serialize)
+)]
+#[cfg_attr(feature = "config_parsing", serde(rename_all = "snake_case"))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "schema", schemars(rename = "GelfProtocol"))]
+pub enum Protocol {
+    /// Each message is sent as a UDP datagram, or as several chunks.
+    #[default]
+    Udp,
+    /// Messages are written to a TCP connection, each terminated by a null
+    /// byte.
+    Tcp,
+}
+
+/// The compression of messages sent over UDP.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+#[cfg_attr(
+    feature = "config_parsing",
+    derive(serde::Deserialize, serde::Serialize)
+)]
+#[cfg_attr(feature = "config_parsing", serde(rename_all = "snake_case"))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "schema", schemars(rename = "GelfCompression"))]
+pub enum Compression {
+    /// Messages are not compressed.
+    #[default]
+    None,
+    /// Messages are compressed with zlib.
+    Zlib,
+    /// Messages are compressed with gzip.
+    Gzip,
+}
+
+impl Compression {
+    fn compress(self, message: Vec<u8>) -> io::Result<Vec<u8>> {
+        match self {
+            Compression::None => Ok(message),
+            Compression::Zlib => {
+                let mut encoder = ZlibEncoder::new(vec![], flate2::Compression::default());
+                encoder.write_all(&message)?;
+                encoder.finish()
+            }
+            Compression::Gzip => {
+                let mut encoder = GzEncoder::new(vec![], flate2::Compression::default());
+                encoder.write_all(&message)?;
+                encoder.finish()
+            }
+        }
+    }
+}
+
+/// Splits `message` into GELF chunks of at most `chunk_size` bytes, including
+/// their headers, or returns it as is if it fits in one datagram.
+fn chunks(message: Vec<u8>, chunk_size: usize, id: u64) -> io::Result<Vec<Vec<u8>>> {
+    if message.len() <= chunk_size {
+        return Ok(vec![message]);
+    }
+
+    let payload = chunk_size - CHUNK_HEADER_LEN;
+    let count = message.len().div_ceil(payload);
+    if count > MAX_CHUNKS {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "a message of {} bytes needs {} chunks, more than GELF allows",
+                message.len(),
+                count
+            ),
+        ));
+    }
+
+    Ok(message
+        .chunks(payload)
+        .enumerate()
+        .map(|(seq, data)| {
+            let mut chunk = Vec::with_capacity(CHUNK_HEADER_LEN + data.len());
+            chunk.extend_from_slice(&CHUNK_MAGIC);
+            chunk.extend_from_slice(&id.to_be_bytes());
+            chunk.push(seq as u8);
+            chunk.push(count as u8);
+            chunk.extend_from_slice(data);
+            chunk
+        })
+        .collect())
+}
+
+enum Connection {
+    Udp(UdpSocket),
+    Tcp(TcpStream),
+}
+
+struct State {
+    connection: Option<Connection>,
+    backoff: Backoff,
+}
+
+/// An appender which sends GELF messages to Graylog over UDP or TCP.
+#[derive(Debug)]
+pub struct GelfAppender {
+    address: String,
+    protocol: Protocol,
+    compression: Compression,
+    chunk_size: usize,
+    encoder: Box<dyn Encode>,
+    #[debug(skip)]
+    state: Mutex<State>,
+    #[debug(skip)]
+    ids: RandomState,
+    #[debug(skip)]
+    next_id: AtomicU64,
+}
+
+impl Append for GelfAppender {
+    fn append(&self, record: &Record<'_>) -> anyhow::Result<()> {
+        let mut message = vec![];
+        self.encoder
+            .encode(&mut SimpleWriter(&mut message), record)?;
+        while matches!(message.last(), Some(b'\n' | b'\r')) {
+            message.pop();
+        }
+
+        let state = &mut *self.state.lock();
+        let connection = match &mut state.connection {
+            Some(connection) => connection,
+            None => {
+                if !state.backoff.ready() {
+                    return Err(anyhow::anyhow!(
+                        "discarding record while waiting to reconnect to `{}`",
+                        self.address
+                    ));
+                }
+                match self.connect() {
+                    Ok(connection) => {
+                        state.backoff.succeeded();
+                        state.connection.insert(connection)
+                    }
+                    Err(e) => {
+                        state.backoff.failed();
+                        return Err(e.into());
+                    }
+                }
+            }
+        };
+        if let Err(e) = self.send(connection, message) {
+            state.connection = None;
+            state.backoff.failed();
+            return Err(e.into());
+        }
+        Ok(())
+    }
+
+    fn flush(&self) {
+        if let Some(Connection::Tcp(stream)) = &mut self.state.lock().connection {
+            let _ = stream.flush();
+        }
+    }
+
+    #[cfg(feature = "config_parsing")]
+    fn config_value(&self) -> Option<serde_value::Value> {
+        let config = GelfAppenderConfig {
+            address: self.address.clone(),
+            protocol: Some(self.protocol),
+            compression: Some(self.compression),
+            chunk_size: Some(self.chunk_size),
+            encoder: Some(EncoderConfig::describe(&*self.encoder)?),
+        };
+        crate::config::component_config("gelf", &config)
+    }
+}
+
+impl GelfAppender {
+    /// Creates a new `GelfAppender` builder.
+    pub fn builder() -> GelfAppenderBuilder {
+        GelfAppenderBuilder {
+            protocol: Protocol::default(),
+            compression: Compression::default(),
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            encoder: None,
+        }
+    }
+
+    fn send(&self, connection: &mut Connection, mut message: Vec<u8>) -> io::Result<()> {
+        match connection {
+            Connection::Udp(socket) => {
+                let message = self.compression.compress(message)?;
+                let id = self
+                    .ids
+                    .hash_one(self.next_id.fetch_add(1, Ordering::Relaxed));
+                for chunk in chunks(message, self.chunk_size, id)? {
+                    socket.send(&chunk)?;
+                }
+                Ok(())
+            }
+            Connection::Tcp(stream) => {
+                message.push(0);
+                stream.write_all(&message)
+            }
+        }
+    }
+
+    fn connect(&self) -> io::Result<Connection> {
+        let mut last_err = None;
+        for addr in self.address.to_socket_addrs()? {
+            match self.connect_addr(addr) {
+                Ok(connection) => return Ok(connection),
+                Err(e) => last_err = Some(e),
+            }
+        }
+        Err(last_err.unwrap_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("`{}` did not resolve to any address", self.address),
+            )
+        }))
+    }
+
+    fn connect_addr(&self, addr: SocketAddr) -> io::Result<Connection> {
+        match self.protocol {
+            Protocol::Udp => {
+                let local: SocketAddr = if addr.is_ipv4() {
+                    ([0, 0, 0, 0], 0).into()
+                } else {
+                    ([0u16; 8], 0).into()
+                };
+                let socket = UdpSocket::bind(local)?;
+                socket.connect(addr)?;
+                Ok(Connection::Udp(socket))
+            }
+            Protocol::Tcp => {
+                let stream = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)?;
+                stream.set_nodelay(true)?;
+                stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+                Ok(Connection::Tcp(stream))
+            }
+        }
+    }
+}
+
+/// A builder for `GelfAppender`s.
+pub struct GelfAppenderBuilder {
+    protocol: Protocol,
+    compression: Compression,
+    chunk_size: usize,
+    encoder: Option<Box<dyn Encode>>,
+}
+
+impl GelfAppenderBuilder {
+    /// Sets the transport protocol.
+    ///
+    /// Defaults to `Protocol::Udp`.
+    pub fn protocol(mut self, protocol: Protocol) -> GelfAppenderBuilder {
+        self.protocol = protocol;
+        self
+    }
+
+    /// Sets the compression of messages sent over UDP. Messages sent over TCP
+    /// are never compressed.
+    ///
+    /// Defaults to `Compression::None`.
+    pub fn compression(mut self, compression: Compression) -> GelfAppenderBuilder {
+        self.compression = compression;
+        self
+    }
+
+    /// Sets the maximum size in bytes of a UDP datagram. Larger messages are
+    /// split into chunks of this size, including their 12 byte header.
+    ///
+    /// Defaults to 1420, which fits within the MTU of most networks. Sizes of
+    /// 12 bytes or less are raised to 13.
+    pub fn chunk_size(mut self, chunk_size: usize) -> GelfAppenderBuilder {
+        self.chunk_size = chunk_size.max(CHUNK_HEADER_LEN + 1);
+        self
+    }
+
+    /// Sets the output encoder for the `GelfAppender`.
+    ///
+    /// Defaults to a `GelfEncoder` with its default configuration.
+    pub fn encoder(mut self, encoder: Box<dyn Encode>) -> GelfAppenderBuilder {
+        self.encoder = Some(encoder);
+        self
+    }
+
+    /// Consumes the `GelfAppenderBuilder`, producing a `GelfAppender` which
+    /// sends messages to `address`, a `host:port` pair.
+    ///
+    /// No socket is opened until the first record is appended.
+    pub fn build<T>(self, address: T) -> GelfAppender
+    where
+        T: Into<String>,
+    {
+        GelfAppender {
+            address: address.into(),
+            protocol: self.protocol,
+            compression: match self.protocol {
+                Protocol::Udp => self.compression,
+                Protocol::Tcp => Compression::None,
+            },
+            chunk_size: self.chunk_size,
+            encoder: self
+                .encoder
+                .unwrap_or_else(|| Box::<GelfEncoder>::default()),
+            state: Mutex::new(State {
+                connection: None,
+                backoff: Backoff::new(backoff::DEFAULT_DELAY, backoff::DEFAULT_MAX_DELAY),
+            }),
+            ids: RandomState::new(),
+            next_id: AtomicU64::new(0),
+        }
+    }
+}
+
+/// A deserializer for the `GelfAppender`.
+///
+/// # Configuration
+///
+/// ```yaml
+/// kind: gelf
+///
+/// # The `host:port` address of the Graylog input. Required.
+/// address: graylog.local:12201
+///
+/// # The transport protocol. One of `udp` or `tcp`. Defaults to `udp`.
+/// protocol: udp
+///
+/// # The compression of messages sent over UDP. One of `none`, `zlib` or
+/// # `gzip`. Not supported over TCP. Defaults to `none`.
+/// compression: gzip
+///
+/// # The maximum size in bytes of a UDP datagram, above which messages are
+/// # split into chunks. Must be greater than 12. Defaults to 1420.
+/// chunk_size: 8154
+///
+/// # The encoder to use to format output. Defaults to `kind: gelf`.
+/// encoder:
+///   kind: gelf
+///   facility: api
+/// ```
+#[cfg(feature = "config_parsing")]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct GelfAppenderDeserializer;
+
+#[cfg(feature = "config_parsing")]
+impl Deserialize for GelfAppenderDeserializer {
+    type Trait = dyn Append;
+
+    type Config = GelfAppenderConfig;
+
+    fn deserialize(
+        &self,
+        config: GelfAppenderConfig,
+        deserializers: &Deserializers,
+    ) -> anyhow::Result<Box<dyn Append>> {
+        let mut appender = GelfAppender::builder();
+        if let Some(protocol) = config.protocol {
+            appender = appender.protocol(protocol);
+        }
+        if let Some(compression) = config.compression {
+            if config.protocol == Some(Protocol::Tcp) && compression != Compression::None {
+                anyhow::bail!("GELF messages cannot be compressed over TCP");
+            }
+            appender = appender.compression(compression);
+        }
+        if let Some(chunk_size) = config.chunk_size {
+            if chunk_size <= CHUNK_HEADER_LEN {
+                anyhow::bail!("the chunk size must be greater than {CHUNK_HEADER_LEN}");
+            }
+            appender = appender.chunk_size(chunk_size);
+        }
+        if let Some(encoder) = config.encoder {
+            appender = appender.encoder(deserializers.deserialize(&encoder.kind, encoder.config)?);
+        }
+        Ok(Box::new(appender.build(config.address)))
+    }
+
+    fn check_components(
+        &self,
+        config: &GelfAppenderConfig,
+        deserializers: &Deserializers,
+    ) -> anyhow::Result<()> {
+        match &config.encoder {
+            Some(encoder) => deserializers.check_nested::<dyn Encode>(
+                "encoder",
+                &encoder.kind,
+                encoder.config.clone(),
+            ),
+            None => Ok(()),
+        }
+    }
+
+    #[cfg(feature = "schema")]
+    fn schema(&self, generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        <GelfAppenderConfig as schemars::JsonSchema>::json_schema(generator)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use flate2::read::{GzDecoder, ZlibDecoder};
+    use std::{
+        io::Read,
+        net::{TcpListener, UdpSocket},
+    };
+
+    use log::Level;
+
+    use super::*;
+
+    fn append(appender: &GelfAppender, message: &str) -> anyhow::Result<()> {
+        appender.append(
+            &Record::builder()
+                .level(Level::Info)
+                .args(format_args!("{}", message))
+                .build(),
+        )
+    }
+
+    fn recv(server: &UdpSocket) -> Vec<u8> {
+        let mut buf = [0; 65536];
+        let len = server.recv(&mut buf).unwrap();
+        buf[..len].to_vec()
+    }
+
+    fn encoder() -> Box<dyn Encode> {
+        Box::new(GelfEncoder::builder().host("host").build())
+    }
+
+    #[test]
+    fn chunked() {
+        let message = (0..25u8).collect::<Vec<_>>();
+        let split = chunks(message.clone(), 22, 0x0102030405060708).unwrap();
+        assert_eq!(split.len(), 3);
+        for (seq, chunk) in split.iter().enumerate() {
+            assert!(chunk.len() <= 22);
+            assert_eq!(&chunk[..2], &CHUNK_MAGIC);
+            assert_eq!(&chunk[2..10], &[1, 2, 3, 4, 5, 6, 7, 8]);
+            assert_eq!(chunk[10], seq as u8);
+            assert_eq!(chunk[11], 3);
+        }
+        let data = split
+            .iter()
+            .flat_map(|chunk| chunk[CHUNK_HEADER_LEN..].to_vec())
+            .collect::<Vec<_>>();
+        assert_eq!(data, message);
+
+        assert_eq!(chunks(message.clone(), 25, 0).unwrap(), vec![message]);
+        assert!(chunks(vec![0; 129], 13, 0).is_err());
+        assert_eq!(chunks(vec![0; 128], 13, 0).unwrap().len(), 128);
+    }
+
+    #[test]
+    fn udp() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let appender = GelfAppender::builder()
+            .encoder(encoder())
+            .build(server.local_addr().unwrap().to_string());
+
+        append(&appender, "datagram").unwrap();
+
+        let message = serde_json::from_slice::<serde_json::Value>(&recv(&server)).unwrap();
+        assert_eq!(message["version"], "1.1");
+        assert_eq!(message["host"], "host");
+        assert_eq!(message["short_message"], "datagram");
+        assert_eq!(message["level"], 6);
+    }
+
+    #[test]
+    fn udp_compressed_chunks() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let text = (0..2000)
+            .map(|i| format!("{i:x}"))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        for compression in [Compression::None, Compression::Zlib, Compression::Gzip] {
+            let appender = GelfAppender::builder()
+                .compression(compression)
+                .chunk_size(512)
+                .encoder(encoder())
+                .build(server.local_addr().unwrap().to_string());
+            append(&appender, &text).unwrap();
+
+            let first = recv(&server);
+            assert_eq!(&first[..2], &CHUNK_MAGIC);
+            let count = first[11] as usize;
+            assert!(count > 1);
+            let mut data = first[CHUNK_HEADER_LEN..].to_vec();
+            for seq in 1..count {
+                let chunk = recv(&server);
+                assert!(chunk.len() <= 512);
+                assert_eq!(&chunk[2..10], &first[2..10]);
+                assert_eq!(chunk[10] as usize, seq);
+                data.extend_from_slice(&chunk[CHUNK_HEADER_LEN..]);
+            }
+
+            let mut json = vec![];
+            match compression {
+                Compression::None => json = data,
+                Compression::Zlib => {
+                    ZlibDecoder::new(&*data).read_to_end(&mut json).unwrap();
+                }
+                Compression::Gzip => {
+                    GzDecoder::new(&*data).read_to_end(&mut json).unwrap();
+                }
+            }
+            let message = serde_json::from_slice::<serde_json::Value>(&json).unwrap();
+            assert_eq!(message["short_message"], text);
+        }
+    }
+
+    #[test]
+    fn tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let appender = GelfAppender::builder()
+            .protocol(Protocol::Tcp)
+            .encoder(encoder())
+            .build(listener.local_addr().unwrap().to_string());
+
+        append(&appender, "foo").unwrap();
+        append(&appender, "bar").unwrap();
+        drop(appender);
+
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut buf = vec![];
+        stream.read_to_end(&mut buf).unwrap();
+        let messages = buf.split(|&b| b == 0).collect::<Vec<_>>();
+        assert_eq!(messages.len(), 3);
+        assert!(messages[2].is_empty());
+        for (message, text) in messages.iter().zip(["foo", "bar"]) {
+            let message = serde_json::from_slice::<serde_json::Value>(message).unwrap();
+            assert_eq!(message["short_message"], text);
+        }
+    }
+
+    #[test]
+    fn tcp_backoff() {
+        // Nothing listens on the port once the listener is dropped.
+        let address = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .to_string();
+        let appender = GelfAppender::builder()
+            .protocol(Protocol::Tcp)
+            .encoder(encoder())
+            .build(address);
+
+        let err = append(&appender, "foo").unwrap_err();
+        assert!(err.downcast_ref::<io::Error>().is_some());
+        let err = append(&appender, "bar").unwrap_err();
+        assert!(err.to_string().contains("waiting to reconnect"), "{err}");
+    }
+
+    #[test]
+    #[cfg(feature = "yaml_format")]
+    fn deserialize() {
+        use crate::config::RawConfig;
+
+        let config = "
+appenders:
+    foo:
+        kind: gelf
+        address: 127.0.0.1:12201
+        compression: gzip
+        chunk_size: 8154
+        encoder:
+            kind: gelf
+            facility: api
+    bar:
+        kind: gelf
+        address: 127.0.0.1:12201
+        protocol: tcp
+        compression: zlib
+";
+
+        let config = ::serde_yaml::from_str::<RawConfig>(config).unwrap();
+        let (appenders, errors) = config.appenders_lossy(&Deserializers::new());
+        // TCP messages cannot be compressed.
+        assert_eq!(appenders.len(), 1);
+        assert!(!errors.is_empty());
+    }
+}
@@ -2,24 +2,53 @@
 
 use log::{Log, Record};
 #[cfg(feature = "config_parsing")]
-use serde::{de, Deserialize, Deserializer};
+use serde::{de, ser, Deserialize, Deserializer, Serialize, Serializer};
 #[cfg(feature = "config_parsing")]
 use serde_value::Value;
 #[cfg(feature = "config_parsing")]
-use std::collections::BTreeMap;
-use std::fmt;
+use std::{collections::BTreeMap, time::Duration};
+use std::{collections::HashMap, fmt, sync::Arc};
 
 #[cfg(feature = "config_parsing")]
-use crate::config::Deserializable;
+use crate::config::{Deserializable, Deserializers};
 #[cfg(feature = "config_parsing")]
-use crate::filter::FilterConfig;
+use crate::filter::{Filter, FilterConfig};
 
+#[cfg(feature = "async_appender")]
+pub mod r#async;
+#[cfg(any(feature = "gelf_appender", feature = "socket_appender"))]
+mod backoff;
+#[cfg(feature = "testing")]
+pub mod capture;
+#[cfg(feature = "channel_appender")]
+pub mod channel;
 #[cfg(feature = "console_appender")]
 pub mod console;
+#[cfg(feature = "failover_appender")]
+pub mod failover;
 #[cfg(feature = "file_appender")]
 pub mod file;
+#[cfg(feature = "function_appender")]
+pub mod function;
+#[cfg(feature = "gelf_appender")]
+pub mod gelf;
+#[cfg(any(
+    feature = "async_appender",
+    feature = "channel_appender",
+    feature = "ring_buffer_appender",
+    feature = "testing"
+))]
+pub mod owned;
+#[cfg(feature = "ring_buffer_appender")]
+pub mod ring_buffer;
 #[cfg(feature = "rolling_file_appender")]
 pub mod rolling_file;
+#[cfg(feature = "sifting_appender")]
+pub mod sifting;
+#[cfg(feature = "socket_appender")]
+pub mod socket;
+#[cfg(feature = "syslog_appender")]
+pub mod syslog;
 
 #[cfg(any(feature = "file_appender", feature = "rolling_file_appender"))]
 mod env_util {
@@ -93,6 +122,57 @@ pub trait Append: fmt::Debug + Send + Sync + 'static {
 
     /// Flushes all in-flight records.
     fn flush(&self);
+
+    /// Looks up the other appenders this appender forwards records to.
+    ///
+    /// This is called for every appender when the `Config` containing it is
+    /// built, with all of the appenders in that `Config`. An error removes the
+    /// appender from the `Config`. The default implementation does nothing.
+    fn resolve(&self, appenders: &Appenders<'_>) -> anyhow::Result<()> {
+        let _ = appenders;
+        Ok(())
+    }
+
+    /// Returns the names of the other appenders this appender forwards
+    /// records to, which `resolve` looks up.
+    ///
+    /// Appenders which forward records back to themselves, directly or
+    /// through others, are removed from the `Config`. The default
+    /// implementation returns no names.
+    fn targets(&self) -> Vec<&str> {
+        vec![]
+    }
+
+    /// Returns the configuration of the appender as it would appear in a
+    /// config file, including its `kind`, if it can be described.
+    ///
+    /// This allows a `Config` containing the appender to be written out with
+    /// `RawConfig::from_config`. The default implementation returns `None`.
+    #[cfg(feature = "config_parsing")]
+    fn config_value(&self) -> Option<Value> {
+        None
+    }
+}
+
+/// The appenders of a `Config`, by name.
+#[derive(Debug)]
+pub struct Appenders<'a>(HashMap<&'a str, &'a Arc<dyn Append>>);
+
+impl<'a> Appenders<'a> {
+    pub(crate) fn new<I>(appenders: I) -> Appenders<'a>
+    where
+        I: IntoIterator<Item = (&'a str, &'a Arc<dyn Append>)>,
+    {
+        Appenders(appenders.into_iter().collect())
+    }
+
+    /// Returns the appender with the given name.
+    ///
+    /// Filters attached to the appender in the `Config` are not applied to
+    /// records appended to it directly.
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Append>> {
+        self.0.get(name).map(|&appender| appender.clone())
+    }
 }
 
 #[cfg(feature = "config_parsing")]
@@ -121,6 +201,8 @@ pub struct AppenderConfig {
     pub kind: String,
     /// The filters attached to the appender.
     pub filters: Vec<FilterConfig>,
+    /// How errors returned by the appender are reported.
+    pub on_error: Option<ErrorPolicyConfig>,
     /// The appender configuration.
     pub config: Value,
 }
@@ -143,14 +225,113 @@ impl<'de> Deserialize<'de> for AppenderConfig {
             None => vec![],
         };
 
+        let on_error = match map.remove(&Value::String("on_error".to_owned())) {
+            Some(on_error) => Some(on_error.deserialize_into().map_err(|e| e.into_error())?),
+            None => None,
+        };
+
         Ok(AppenderConfig {
             kind,
             filters,
+            on_error,
             config: Value::Map(map),
         })
     }
 }
 
+#[cfg(feature = "config_parsing")]
+impl Serialize for AppenderConfig {
+    fn serialize<S>(&self, s: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut config = match &self.config {
+            Value::Map(map) => map.clone(),
+            _ => BTreeMap::new(),
+        };
+        if !self.filters.is_empty() {
+            let filters = serde_value::to_value(&self.filters).map_err(ser::Error::custom)?;
+            config.insert(Value::String("filters".to_owned()), filters);
+        }
+        if let Some(on_error) = &self.on_error {
+            let on_error = serde_value::to_value(on_error).map_err(ser::Error::custom)?;
+            config.insert(Value::String("on_error".to_owned()), on_error);
+        }
+        crate::config::ser_component(&self.kind, &Value::Map(config), s)
+    }
+}
+
+#[cfg(feature = "schema")]
+impl schemars::JsonSchema for AppenderConfig {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "Appender".into()
+    }
+
+    fn json_schema(generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        crate::config::schema::component(generator)
+    }
+}
+
+#[cfg(feature = "config_parsing")]
+impl AppenderConfig {
+    /// Returns the configuration of `appender`, without filters or an error
+    /// policy, if it can be described.
+    #[allow(dead_code)] // unused when no wrapping appenders are enabled
+    pub(crate) fn describe(appender: &dyn Append) -> Option<AppenderConfig> {
+        appender.config_value()?.deserialize_into().ok()
+    }
+
+    /// Checks the configurations of the appender and its filters, nested in
+    /// another component under `key`, without building them.
+    #[allow(dead_code)] // unused when no wrapping appenders are enabled
+    pub(crate) fn check(&self, key: &str, deserializers: &Deserializers) -> anyhow::Result<()> {
+        for (index, filter) in self.filters.iter().enumerate() {
+            deserializers.check_nested::<dyn Filter>(
+                &format!("{}.filters.{}", key, index),
+                &filter.kind,
+                filter.config.clone(),
+            )?;
+        }
+        deserializers.check_nested::<dyn Append>(key, &self.kind, self.config.clone())
+    }
+
+    /// Returns the names of the appenders which the appender forwards records
+    /// to, without building it.
+    #[allow(dead_code)] // unused when no wrapping appenders are enabled
+    pub(crate) fn targets(&self, deserializers: &Deserializers) -> Vec<String> {
+        deserializers.targets(&self.kind, self.config.clone())
+    }
+}
+
+/// How errors returned by an appender are reported, as configured.
+///
+/// See `config::ErrorPolicy`.
+#[cfg(feature = "config_parsing")]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum ErrorPolicyConfig {
+    /// Every error is reported.
+    #[serde(rename = "report")]
+    Report,
+    /// Errors are not reported.
+    #[serde(rename = "ignore")]
+    Ignore,
+    /// Only the first error is reported.
+    #[serde(rename = "log_once")]
+    LogOnce,
+    /// At most one error per interval is reported.
+    #[serde(
+        rename = "rate_limit",
+        deserialize_with = "crate::config::de_required_duration",
+        serialize_with = "crate::config::ser_required_duration"
+    )]
+    #[cfg_attr(
+        feature = "schema",
+        schemars(schema_with = "crate::config::schema::duration")
+    )]
+    RateLimit(Duration),
+}
+
 #[cfg(test)]
 mod test {
     #[cfg(any(feature = "file_appender", feature = "rolling_file_appender"))]
@@ -0,0 +1,151 @@
+//! Owned copies of log records.
+//!
+//! Used by appenders which hold on to records after `append` has returned, or
+//! hand them to other code.
+
+use log::{Level, Record};
+
+use crate::append::Append;
+
+/// An owned copy of a `Record`, along with the MDC at the time it was logged.
+#[derive(Clone, Debug)]
+pub struct OwnedRecord {
+    level: Level,
+    target: String,
+    message: String,
+    module_path: Option<String>,
+    file: Option<String>,
+    line: Option<u32>,
+    mdc: Vec<(String, String)>,
+    #[cfg(feature = "log_kv")]
+    kvs: Vec<(String, String)>,
+}
+
+impl OwnedRecord {
+    /// Copies `record` and the MDC of the current thread.
+    pub fn new(record: &Record<'_>) -> OwnedRecord {
+        OwnedRecord {
+            level: record.level(),
+            target: record.target().to_owned(),
+            message: record.args().to_string(),
+            module_path: record.module_path().map(ToOwned::to_owned),
+            file: record.file().map(ToOwned::to_owned),
+            line: record.line(),
+            mdc: capture_mdc(),
+            #[cfg(feature = "log_kv")]
+            kvs: kv::collect(record.key_values()),
+        }
+    }
+
+    /// Returns the record's level.
+    pub fn level(&self) -> Level {
+        self.level
+    }
+
+    /// Returns the record's target.
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    /// Returns the record's formatted message.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Returns the module path of the logging call, if known.
+    pub fn module_path(&self) -> Option<&str> {
+        self.module_path.as_deref()
+    }
+
+    /// Returns the source file of the logging call, if known.
+    pub fn file(&self) -> Option<&str> {
+        self.file.as_deref()
+    }
+
+    /// Returns the source line of the logging call, if known.
+    pub fn line(&self) -> Option<u32> {
+        self.line
+    }
+
+    /// Returns the MDC of the logging thread at the time of the call.
+    pub fn mdc(&self) -> &[(String, String)] {
+        &self.mdc
+    }
+
+    /// Returns the value of `key` in the MDC at the time of the call.
+    pub fn mdc_value(&self, key: &str) -> Option<&str> {
+        find(&self.mdc, key)
+    }
+
+    /// Returns the record's key-values, formatted as strings.
+    #[cfg(feature = "log_kv")]
+    pub fn key_values(&self) -> &[(String, String)] {
+        &self.kvs
+    }
+
+    /// Returns the value of `key` in the record's key-values, formatted as a
+    /// string.
+    #[cfg(feature = "log_kv")]
+    pub fn key_value(&self, key: &str) -> Option<&str> {
+        find(&self.kvs, key)
+    }
+
+    /// Appends the record to `appender`.
+    ///
+    /// The record's MDC replaces that of the current thread.
+    #[cfg_attr(
+        not(any(feature = "async_appender", feature = "ring_buffer_appender")),
+        allow(dead_code)
+    )]
+    pub(crate) fn append_to(&self, appender: &dyn Append) -> anyhow::Result<()> {
+        log_mdc::clear();
+        log_mdc::extend(self.mdc.iter().cloned());
+
+        let mut builder = Record::builder();
+        builder
+            .level(self.level)
+            .target(&self.target)
+            .module_path(self.module_path.as_deref())
+            .file(self.file.as_deref())
+            .line(self.line);
+        #[cfg(feature = "log_kv")]
+        builder.key_values(&self.kvs);
+
+        appender.append(&builder.args(format_args!("{}", self.message)).build())
+    }
+}
+
+fn find<'a>(pairs: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    pairs
+        .iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v.as_str())
+}
+
+/// Returns a copy of the current thread's MDC.
+pub(crate) fn capture_mdc() -> Vec<(String, String)> {
+    let mut mdc = vec![];
+    log_mdc::iter(|k, v| mdc.push((k.to_owned(), v.to_owned())));
+    mdc
+}
+
+#[cfg(feature = "log_kv")]
+mod kv {
+    use log::kv::{Error, Key, Source, Value, VisitSource};
+
+    struct Collector(Vec<(String, String)>);
+
+    impl<'kvs> VisitSource<'kvs> for Collector {
+        fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), Error> {
+            self.0.push((key.to_string(), value.to_string()));
+            Ok(())
+        }
+    }
+
+    pub(super) fn collect(source: &dyn Source) -> Vec<(String, String)> {
+        let mut collector = Collector(vec![]);
+        // Collecting into a `Vec` cannot fail.
+        let _ = source.visit(&mut collector);
+        collector.0
+    }
+}
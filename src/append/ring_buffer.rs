@@ -0,0 +1,735 @@
+//! The ring buffer appender.
+//!
+//! Requires the `ring_buffer_appender` feature.
+//!
+//! A `RingBufferAppender` keeps the most recent encoded records in memory,
+//! discarding the oldest once a record count or byte limit is reached. The
+//! buffer is read through a `RingBufferHandle`, for example from a panic hook
+//! or a debug endpoint.
+//!
+//! The appender can also be given the name of another appender of the same
+//! `Config` to flush into. When a record at or above the flush level is
+//! appended, every buffered record is passed on to that appender and removed
+//! from the buffer, so that verbose logs are only written out when something
+//! goes wrong.
+
+use derive_more::Debug;
+use log::{Level, Record};
+use parking_lot::Mutex;
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, VecDeque},
+    io::{self, Write},
+    sync::Arc,
+};
+
+#[cfg(feature = "config_parsing")]
+use crate::config::{Deserialize, Deserializers};
+#[cfg(feature = "config_parsing")]
+use crate::encode::EncoderConfig;
+use crate::{
+    append::{
+        owned::{self, OwnedRecord},
+        Append, Appenders,
+    },
+    encode::{pattern::PatternEncoder, writer::simple::SimpleWriter, Encode},
+};
+
+const DEFAULT_CAPACITY: usize = 1024;
+
+static HANDLES: Mutex<BTreeMap<String, RingBufferHandle>> =
+    parking_lot::const_mutex(BTreeMap::new());
+
+/// Returns the handle of the ring buffer registered under `name`.
+///
+/// Ring buffers are registered with `RingBufferAppenderBuilder::name`, or the
+/// `name` field of their configuration. A handle remains registered until
+/// another ring buffer is registered under the same name. Those named by a
+/// configuration which fails to load or is rejected by a validator are
+/// unregistered again.
+pub fn handle(name: &str) -> Option<RingBufferHandle> {
+    HANDLES.lock().get(name).cloned()
+}
+
+// the names registered, along with the handles they replaced
+type Replaced = Vec<(String, Option<RingBufferHandle>)>;
+
+thread_local! {
+    // the handles replaced by registrations on this thread since a
+    // `Registrations` began
+    static REPLACED: RefCell<Option<Replaced>> = const { RefCell::new(None) };
+}
+
+fn register(name: &str, handle: RingBufferHandle) {
+    let replaced = HANDLES.lock().insert(name.to_owned(), handle);
+    REPLACED.with(|r| {
+        if let Some(r) = &mut *r.borrow_mut() {
+            r.push((name.to_owned(), replaced));
+        }
+    });
+}
+
+/// Tracks the ring buffers registered by the current thread while a config is
+/// built, so that the handles they replaced can be restored if the config is
+/// not applied.
+///
+/// The registrations are undone when this is dropped unless it has been
+/// committed.
+#[cfg(feature = "config_parsing")]
+pub(crate) struct Registrations(());
+
+#[cfg(feature = "config_parsing")]
+impl Registrations {
+    pub(crate) fn begin() -> Registrations {
+        REPLACED.with(|r| *r.borrow_mut() = Some(vec![]));
+        Registrations(())
+    }
+
+    pub(crate) fn commit(self) {
+        REPLACED.with(|r| r.borrow_mut().take());
+    }
+}
+
+#[cfg(feature = "config_parsing")]
+impl Drop for Registrations {
+    fn drop(&mut self) {
+        let replaced = match REPLACED.with(|r| r.borrow_mut().take()) {
+            Some(replaced) => replaced,
+            None => return,
+        };
+
+        let mut handles = HANDLES.lock();
+        for (name, handle) in replaced.into_iter().rev() {
+            match handle {
+                Some(handle) => handles.insert(name, handle),
+                None => handles.remove(&name),
+            };
+        }
+    }
+}
+
+/// The ring buffer appender's configuration.
+#[cfg(feature = "config_parsing")]
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct RingBufferAppenderConfig {
+    capacity: Option<usize>,
+    max_bytes: Option<usize>,
+    name: Option<String>,
+    flush_to: Option<String>,
+    #[serde(serialize_with = "ser_flush_level")]
+    #[cfg_attr(
+        feature = "schema",
+        schemars(schema_with = "crate::config::schema::level")
+    )]
+    flush_level: Option<Level>,
+    encoder: Option<EncoderConfig>,
+}
+
+#[cfg(feature = "config_parsing")]
+fn ser_flush_level<S>(level: &Option<Level>, s: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    match level {
+        Some(level) => crate::config::ser_level(&level.to_level_filter(), s),
+        None => s.serialize_none(),
+    }
+}
+
+struct Entry {
+    encoded: Vec<u8>,
+    record: Option<OwnedRecord>,
+}
+
+struct Buffer {
+    entries: VecDeque<Entry>,
+    bytes: usize,
+}
+
+impl Buffer {
+    fn take(&mut self) -> VecDeque<Entry> {
+        self.bytes = 0;
+        std::mem::take(&mut self.entries)
+    }
+}
+
+struct Shared {
+    buffer: Mutex<Buffer>,
+    capacity: usize,
+    max_bytes: Option<usize>,
+}
+
+/// A handle to the contents of a `RingBufferAppender`.
+///
+/// The handle remains valid after the appender has been moved into a `Config`.
+#[derive(Clone, Debug)]
+pub struct RingBufferHandle(#[debug(skip)] Arc<Shared>);
+
+impl RingBufferHandle {
+    /// Returns a copy of the buffered records, oldest first.
+    pub fn snapshot(&self) -> Vec<Vec<u8>> {
+        let buffer = self.0.buffer.lock();
+        buffer.entries.iter().map(|e| e.encoded.clone()).collect()
+    }
+
+    /// Removes and returns the buffered records, oldest first.
+    pub fn drain(&self) -> Vec<Vec<u8>> {
+        let entries = self.0.buffer.lock().take();
+        entries.into_iter().map(|e| e.encoded).collect()
+    }
+
+    /// Discards the buffered records.
+    pub fn clear(&self) {
+        self.0.buffer.lock().take();
+    }
+
+    /// Returns the number of buffered records.
+    pub fn len(&self) -> usize {
+        self.0.buffer.lock().entries.len()
+    }
+
+    /// Returns `true` if no records are buffered.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Writes the buffered records to `w`, oldest first, without removing
+    /// them.
+    pub fn write_to(&self, w: &mut dyn Write) -> io::Result<()> {
+        let buffer = self.0.buffer.lock();
+        for entry in &buffer.entries {
+            w.write_all(&entry.encoded)?;
+        }
+        w.flush()
+    }
+}
+
+/// An appender which keeps the most recent records in memory.
+#[derive(Debug)]
+pub struct RingBufferAppender {
+    #[debug(skip)]
+    shared: Arc<Shared>,
+    encoder: Box<dyn Encode>,
+    name: Option<String>,
+    flush_to: Option<String>,
+    flush_level: Level,
+    #[debug(skip)]
+    target: Mutex<Option<Arc<dyn Append>>>,
+}
+
+impl RingBufferAppender {
+    /// Creates a new `RingBufferAppender` builder.
+    pub fn builder() -> RingBufferAppenderBuilder {
+        RingBufferAppenderBuilder {
+            capacity: DEFAULT_CAPACITY,
+            max_bytes: None,
+            name: None,
+            flush_to: None,
+            flush_level: Level::Error,
+            encoder: None,
+        }
+    }
+
+    /// Returns a handle to the buffered records.
+    pub fn handle(&self) -> RingBufferHandle {
+        RingBufferHandle(self.shared.clone())
+    }
+
+    fn flush_into_target(&self, entries: VecDeque<Entry>) -> anyhow::Result<()> {
+        let target = match &*self.target.lock() {
+            Some(target) => target.clone(),
+            None => anyhow::bail!(
+                "ring buffer flush target `{}` has not been resolved",
+                self.flush_to.as_deref().unwrap_or_default()
+            ),
+        };
+
+        // Replaying records replaces the MDC of the current thread, which
+        // belongs to the caller.
+        let mdc = owned::capture_mdc();
+        let mut result = Ok(());
+        for record in entries.iter().filter_map(|e| e.record.as_ref()) {
+            if let Err(e) = record.append_to(&*target) {
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+        log_mdc::clear();
+        log_mdc::extend(mdc);
+
+        result
+    }
+}
+
+impl Append for RingBufferAppender {
+    fn append(&self, record: &Record<'_>) -> anyhow::Result<()> {
+        let mut encoded = vec![];
+        self.encoder
+            .encode(&mut SimpleWriter(&mut encoded), record)?;
+        let entry = Entry {
+            encoded,
+            record: self.flush_to.as_ref().map(|_| OwnedRecord::new(record)),
+        };
+        let triggered = self.flush_to.is_some() && record.level() <= self.flush_level;
+
+        let entries = {
+            let mut buffer = self.shared.buffer.lock();
+            buffer.bytes += entry.encoded.len();
+            buffer.entries.push_back(entry);
+            // the record which triggers a flush is always flushed
+            let kept = usize::from(triggered);
+            while buffer.entries.len() > kept.max(self.shared.capacity)
+                || (buffer.entries.len() > kept
+                    && self.shared.max_bytes.is_some_and(|max| buffer.bytes > max))
+            {
+                match buffer.entries.pop_front() {
+                    Some(dropped) => buffer.bytes -= dropped.encoded.len(),
+                    None => break,
+                }
+            }
+
+            if !triggered {
+                return Ok(());
+            }
+            buffer.take()
+        };
+
+        self.flush_into_target(entries)
+    }
+
+    fn flush(&self) {}
+
+    fn resolve(&self, appenders: &Appenders<'_>) -> anyhow::Result<()> {
+        let name = match &self.flush_to {
+            Some(name) => name,
+            None => return Ok(()),
+        };
+        let target = match appenders.get(name) {
+            Some(target) => target,
+            None => anyhow::bail!("no appender named `{}` to flush into", name),
+        };
+        *self.target.lock() = Some(target);
+        Ok(())
+    }
+
+    fn targets(&self) -> Vec<&str> {
+        self.flush_to.as_deref().into_iter().collect()
+    }
+
+    #[cfg(feature = "config_parsing")]
+    fn config_value(&self) -> Option<serde_value::Value> {
+        let config = RingBufferAppenderConfig {
+            capacity: Some(self.shared.capacity),
+            max_bytes: self.shared.max_bytes,
+            name: self.name.clone(),
+            flush_to: self.flush_to.clone(),
+            flush_level: Some(self.flush_level),
+            encoder: Some(EncoderConfig::describe(&*self.encoder)?),
+        };
+        crate::config::component_config("ring_buffer", &config)
+    }
+}
+
+/// A builder for `RingBufferAppender`s.
+pub struct RingBufferAppenderBuilder {
+    capacity: usize,
+    max_bytes: Option<usize>,
+    name: Option<String>,
+    flush_to: Option<String>,
+    flush_level: Level,
+    encoder: Option<Box<dyn Encode>>,
+}
+
+impl RingBufferAppenderBuilder {
+    /// Sets the maximum number of records kept.
+    ///
+    /// Defaults to 1024.
+    pub fn capacity(mut self, capacity: usize) -> RingBufferAppenderBuilder {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Sets the maximum total size of the encoded records kept, in bytes.
+    ///
+    /// Defaults to no limit.
+    pub fn max_bytes(mut self, max_bytes: usize) -> RingBufferAppenderBuilder {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Registers the appender's handle under `name`, to be retrieved with
+    /// `ring_buffer::handle`.
+    pub fn name(mut self, name: &str) -> RingBufferAppenderBuilder {
+        self.name = Some(name.to_owned());
+        self
+    }
+
+    /// Sets the name of the appender to flush the buffer into when a record at
+    /// or above the flush level is appended.
+    ///
+    /// The record which triggers the flush is always passed on, even if it
+    /// alone exceeds the capacity or byte limit of the buffer.
+    ///
+    /// The appender is looked up when the `Config` is built.
+    pub fn flush_to(mut self, appender: &str) -> RingBufferAppenderBuilder {
+        self.flush_to = Some(appender.to_owned());
+        self
+    }
+
+    /// Sets the least severe level which triggers a flush.
+    ///
+    /// Defaults to `Level::Error`.
+    pub fn flush_level(mut self, flush_level: Level) -> RingBufferAppenderBuilder {
+        self.flush_level = flush_level;
+        self
+    }
+
+    /// Sets the encoder used to format the buffered records.
+    pub fn encoder(mut self, encoder: Box<dyn Encode>) -> RingBufferAppenderBuilder {
+        self.encoder = Some(encoder);
+        self
+    }
+
+    /// Consumes the `RingBufferAppenderBuilder`, producing a
+    /// `RingBufferAppender`.
+    pub fn build(self) -> RingBufferAppender {
+        let appender = RingBufferAppender {
+            shared: Arc::new(Shared {
+                buffer: Mutex::new(Buffer {
+                    entries: VecDeque::new(),
+                    bytes: 0,
+                }),
+                capacity: self.capacity,
+                max_bytes: self.max_bytes,
+            }),
+            encoder: self
+                .encoder
+                .unwrap_or_else(|| Box::<PatternEncoder>::default()),
+            name: self.name,
+            flush_to: self.flush_to,
+            flush_level: self.flush_level,
+            target: Mutex::new(None),
+        };
+
+        if let Some(name) = &appender.name {
+            register(name, appender.handle());
+        }
+
+        appender
+    }
+}
+
+/// A deserializer for the `RingBufferAppender`.
+///
+/// # Configuration
+///
+/// ```yaml
+/// kind: ring_buffer
+///
+/// # The maximum number of records kept. Defaults to 1024.
+/// capacity: 1024
+///
+/// # The maximum total size of the encoded records kept, in bytes. Defaults
+/// # to no limit.
+/// max_bytes: 1048576
+///
+/// # The name under which the buffer's handle is registered, to be retrieved
+/// # with `log4rs::append::ring_buffer::handle`. Optional.
+/// name: crash_dump
+///
+/// # The name of another appender to flush the buffered records into when a
+/// # record at or above `flush_level` is appended. Optional.
+/// flush_to: file
+///
+/// # The least severe level which triggers a flush. Defaults to `error`.
+/// flush_level: error
+///
+/// # The encoder to use to format the buffered records. Defaults to
+/// # `kind: pattern`.
+/// encoder:
+///   kind: pattern
+/// ```
+#[cfg(feature = "config_parsing")]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct RingBufferAppenderDeserializer;
+
+#[cfg(feature = "config_parsing")]
+impl Deserialize for RingBufferAppenderDeserializer {
+    type Trait = dyn Append;
+
+    type Config = RingBufferAppenderConfig;
+
+    fn deserialize(
+        &self,
+        config: RingBufferAppenderConfig,
+        deserializers: &Deserializers,
+    ) -> anyhow::Result<Box<dyn Append>> {
+        let mut appender = RingBufferAppender::builder();
+        if let Some(capacity) = config.capacity {
+            appender = appender.capacity(capacity);
+        }
+        if let Some(max_bytes) = config.max_bytes {
+            appender = appender.max_bytes(max_bytes);
+        }
+        if let Some(name) = &config.name {
+            appender = appender.name(name);
+        }
+        if let Some(flush_to) = &config.flush_to {
+            appender = appender.flush_to(flush_to);
+        }
+        if let Some(flush_level) = config.flush_level {
+            appender = appender.flush_level(flush_level);
+        }
+        if let Some(encoder) = config.encoder {
+            appender = appender.encoder(deserializers.deserialize(&encoder.kind, encoder.config)?);
+        }
+        Ok(Box::new(appender.build()))
+    }
+
+    fn check_components(
+        &self,
+        config: &RingBufferAppenderConfig,
+        deserializers: &Deserializers,
+    ) -> anyhow::Result<()> {
+        match &config.encoder {
+            Some(encoder) => deserializers.check_nested::<dyn Encode>(
+                "encoder",
+                &encoder.kind,
+                encoder.config.clone(),
+            ),
+            None => Ok(()),
+        }
+    }
+
+    fn targets(&self, config: &RingBufferAppenderConfig, _: &Deserializers) -> Vec<String> {
+        config.flush_to.iter().cloned().collect()
+    }
+
+    #[cfg(feature = "schema")]
+    fn schema(&self, generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        <RingBufferAppenderConfig as schemars::JsonSchema>::json_schema(generator)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use derive_more::Debug;
+    use log::LevelFilter;
+
+    use super::*;
+    use crate::config::{Appender, Config, Root};
+
+    #[derive(Debug)]
+    struct VecAppender(Arc<Mutex<Vec<String>>>);
+
+    impl Append for VecAppender {
+        fn append(&self, record: &Record<'_>) -> anyhow::Result<()> {
+            self.0
+                .lock()
+                .push(format!("{} {}", record.level(), record.args()));
+            Ok(())
+        }
+
+        fn flush(&self) {}
+    }
+
+    fn append(appender: &dyn Append, level: Level, message: &str) {
+        appender
+            .append(
+                &Record::builder()
+                    .level(level)
+                    .args(format_args!("{}", message))
+                    .build(),
+            )
+            .unwrap();
+    }
+
+    fn messages(handle: &RingBufferHandle) -> Vec<String> {
+        handle
+            .snapshot()
+            .into_iter()
+            .map(|m| String::from_utf8(m).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn capacity() {
+        let appender = RingBufferAppender::builder()
+            .capacity(2)
+            .encoder(Box::new(PatternEncoder::new("{m}")))
+            .build();
+
+        append(&appender, Level::Info, "one");
+        append(&appender, Level::Info, "two");
+        append(&appender, Level::Info, "three");
+
+        assert_eq!(messages(&appender.handle()), ["two", "three"]);
+    }
+
+    #[test]
+    fn max_bytes() {
+        let appender = RingBufferAppender::builder()
+            .max_bytes(7)
+            .encoder(Box::new(PatternEncoder::new("{m}")))
+            .build();
+
+        append(&appender, Level::Info, "one");
+        append(&appender, Level::Info, "two");
+        append(&appender, Level::Info, "three");
+
+        assert_eq!(messages(&appender.handle()), ["three"]);
+    }
+
+    #[test]
+    fn handle() {
+        let appender = RingBufferAppender::builder()
+            .name("ring_buffer::test::handle")
+            .encoder(Box::new(PatternEncoder::new("{m}{n}")))
+            .build();
+        let handle = super::handle("ring_buffer::test::handle").unwrap();
+
+        append(&appender, Level::Info, "one");
+        append(&appender, Level::Info, "two");
+
+        let mut dump = vec![];
+        handle.write_to(&mut dump).unwrap();
+        assert_eq!(dump, b"one\ntwo\n");
+        assert_eq!(handle.len(), 2);
+
+        assert_eq!(handle.drain(), [b"one\n".to_vec(), b"two\n".to_vec()]);
+        assert!(handle.is_empty());
+
+        append(&appender, Level::Info, "three");
+        handle.clear();
+        assert!(handle.is_empty());
+    }
+
+    #[test]
+    fn flush_to() {
+        let log = Arc::new(Mutex::new(vec![]));
+        let ring_buffer = RingBufferAppender::builder()
+            .flush_to("target")
+            .encoder(Box::new(PatternEncoder::new("{m}")))
+            .build();
+        let handle = ring_buffer.handle();
+
+        let config = Config::builder()
+            .appender(Appender::builder().build("ring", Box::new(ring_buffer)))
+            .appender(Appender::builder().build("target", Box::new(VecAppender(log.clone()))))
+            .build(Root::builder().appender("ring").build(LevelFilter::Debug))
+            .unwrap();
+        let ring_buffer = config.appenders()[0].appender();
+
+        append(ring_buffer, Level::Debug, "one");
+        append(ring_buffer, Level::Warn, "two");
+        assert!(log.lock().is_empty());
+
+        append(ring_buffer, Level::Error, "three");
+        assert_eq!(*log.lock(), ["DEBUG one", "WARN two", "ERROR three"]);
+        assert!(handle.is_empty());
+    }
+
+    #[test]
+    fn flush_exceeding_limits() {
+        for builder in [
+            RingBufferAppender::builder().capacity(0),
+            RingBufferAppender::builder().max_bytes(3),
+        ] {
+            let log = Arc::new(Mutex::new(vec![]));
+            let ring_buffer = builder
+                .flush_to("target")
+                .encoder(Box::new(PatternEncoder::new("{m}")))
+                .build();
+            let handle = ring_buffer.handle();
+
+            let config = Config::builder()
+                .appender(Appender::builder().build("ring", Box::new(ring_buffer)))
+                .appender(Appender::builder().build("target", Box::new(VecAppender(log.clone()))))
+                .build(Root::builder().appender("ring").build(LevelFilter::Debug))
+                .unwrap();
+            let ring_buffer = config.appenders()[0].appender();
+
+            append(ring_buffer, Level::Debug, "one");
+            append(ring_buffer, Level::Error, "three");
+            assert_eq!(*log.lock(), ["ERROR three"]);
+            assert!(handle.is_empty());
+        }
+    }
+
+    #[test]
+    fn unresolved_flush_target() {
+        let ring_buffer = RingBufferAppender::builder().flush_to("missing").build();
+        let looped = RingBufferAppender::builder().flush_to("looped").build();
+
+        let (config, errors) = Config::builder()
+            .appender(Appender::builder().build("ring", Box::new(ring_buffer)))
+            .appender(Appender::builder().build("looped", Box::new(looped)))
+            .build_lossy(Root::builder().appender("ring").build(LevelFilter::Debug));
+
+        assert!(config.appenders().is_empty());
+        assert_eq!(errors.errors().len(), 3);
+    }
+
+    #[test]
+    fn flush_cycle() {
+        let ring_buffer = |target| RingBufferAppender::builder().flush_to(target).build();
+
+        let (config, errors) = Config::builder()
+            .appender(Appender::builder().build("a", Box::new(ring_buffer("b"))))
+            .appender(Appender::builder().build("b", Box::new(ring_buffer("c"))))
+            .appender(Appender::builder().build("c", Box::new(ring_buffer("a"))))
+            .appender(Appender::builder().build("d", Box::new(ring_buffer("a"))))
+            .appender(Appender::builder().build("e", Box::new(ring_buffer("f"))))
+            .appender(
+                Appender::builder().build("f", Box::new(RingBufferAppender::builder().build())),
+            )
+            .build_lossy(Root::builder().build(LevelFilter::Debug));
+
+        let names = config
+            .appenders()
+            .iter()
+            .map(|a| a.name())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["e", "f"]);
+        let errors = errors
+            .errors()
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            errors,
+            [
+                "Unable to resolve appender `a`: appender forwards records back to itself through `b`, `c`",
+                "Unable to resolve appender `b`: appender forwards records back to itself through `c`, `a`",
+                "Unable to resolve appender `c`: appender forwards records back to itself through `a`, `b`",
+                "Unable to resolve appender `d`: no appender named `a` to flush into",
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "yaml_format")]
+    fn deserialize() {
+        use crate::config::RawConfig;
+
+        let config = "
+appenders:
+    ring:
+        kind: ring_buffer
+        capacity: 100
+        max_bytes: 4096
+        name: crash_dump
+        flush_to: console
+        flush_level: warn
+        encoder:
+            kind: pattern
+";
+
+        let config = ::serde_yaml::from_str::<RawConfig>(config).unwrap();
+        let errors = config.appenders_lossy(&Deserializers::new()).1;
+        assert!(errors.is_empty());
+    }
+}
@@ -44,8 +44,9 @@ pub mod policy;
 
 /// Configuration for the rolling file appender.
 #[cfg(feature = "config_parsing")]
-#[derive(Clone, Eq, PartialEq, Hash, Debug, serde::Deserialize)]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, serde::Deserialize, serde::Serialize)]
 #[serde(deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct RollingFileAppenderConfig {
     path: String,
     append: Option<bool>,
@@ -80,6 +81,27 @@ impl<'de> serde::Deserialize<'de> for Policy {
     }
 }
 
+#[cfg(feature = "config_parsing")]
+impl serde::Serialize for Policy {
+    fn serialize<S>(&self, s: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        crate::config::ser_component(&self.kind, &self.config, s)
+    }
+}
+
+#[cfg(feature = "schema")]
+impl schemars::JsonSchema for Policy {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "Policy".into()
+    }
+
+    fn json_schema(generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        crate::config::schema::component(generator)
+    }
+}
+
 #[derive(Debug)]
 struct LogWriter {
     file: BufWriter<File>,
@@ -203,6 +225,17 @@ impl Append for RollingFileAppender {
     }
 
     fn flush(&self) {}
+
+    #[cfg(feature = "config_parsing")]
+    fn config_value(&self) -> Option<Value> {
+        let config = RollingFileAppenderConfig {
+            path: self.path.to_str()?.to_owned(),
+            append: Some(self.append),
+            encoder: Some(EncoderConfig::describe(&*self.encoder)?),
+            policy: self.policy.config_value()?.deserialize_into().ok()?,
+        };
+        crate::config::component_config("rolling_file", &config)
+    }
 }
 
 impl RollingFileAppender {
@@ -361,6 +394,30 @@ impl Deserialize for RollingFileAppenderDeserializer {
         let appender = builder.build(config.path, policy)?;
         Ok(Box::new(appender))
     }
+
+    fn check_components(
+        &self,
+        config: &RollingFileAppenderConfig,
+        deserializers: &Deserializers,
+    ) -> anyhow::Result<()> {
+        if let Some(encoder) = &config.encoder {
+            deserializers.check_nested::<dyn Encode>(
+                "encoder",
+                &encoder.kind,
+                encoder.config.clone(),
+            )?;
+        }
+        deserializers.check_nested::<dyn policy::Policy>(
+            "policy",
+            &config.policy.kind,
+            config.policy.config.clone(),
+        )
+    }
+
+    #[cfg(feature = "schema")]
+    fn schema(&self, generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        <RollingFileAppenderConfig as schemars::JsonSchema>::json_schema(generator)
+    }
 }
 
 #[cfg(test)]
@@ -20,8 +20,9 @@ pub mod trigger;
 
 /// Configuration for the compound policy.
 #[cfg(feature = "config_parsing")]
-#[derive(Clone, Eq, PartialEq, Hash, Debug, serde::Deserialize)]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, serde::Deserialize, serde::Serialize)]
 #[serde(deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct CompoundPolicyConfig {
     trigger: Trigger,
     roller: Roller,
@@ -54,6 +55,27 @@ impl<'de> serde::Deserialize<'de> for Trigger {
     }
 }
 
+#[cfg(feature = "config_parsing")]
+impl serde::Serialize for Trigger {
+    fn serialize<S>(&self, s: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        crate::config::ser_component(&self.kind, &self.config, s)
+    }
+}
+
+#[cfg(feature = "schema")]
+impl schemars::JsonSchema for Trigger {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "Trigger".into()
+    }
+
+    fn json_schema(generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        crate::config::schema::component(generator)
+    }
+}
+
 #[cfg(feature = "config_parsing")]
 #[derive(Clone, Eq, PartialEq, Hash, Debug)]
 struct Roller {
@@ -81,6 +103,27 @@ impl<'de> serde::Deserialize<'de> for Roller {
     }
 }
 
+#[cfg(feature = "config_parsing")]
+impl serde::Serialize for Roller {
+    fn serialize<S>(&self, s: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        crate::config::ser_component(&self.kind, &self.config, s)
+    }
+}
+
+#[cfg(feature = "schema")]
+impl schemars::JsonSchema for Roller {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "Roller".into()
+    }
+
+    fn json_schema(generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        crate::config::schema::component(generator)
+    }
+}
+
 /// A rolling policy which delegates to a "trigger" and "roller".
 ///
 /// The trigger determines if the log file should roll, for example, by checking
@@ -111,6 +154,15 @@ impl Policy for CompoundPolicy {
     fn is_pre_process(&self) -> bool {
         self.trigger.is_pre_process()
     }
+
+    #[cfg(feature = "config_parsing")]
+    fn config_value(&self) -> Option<Value> {
+        let config = CompoundPolicyConfig {
+            trigger: self.trigger.config_value()?.deserialize_into().ok()?,
+            roller: self.roller.config_value()?.deserialize_into().ok()?,
+        };
+        crate::config::component_config("compound", &config)
+    }
 }
 
 /// A deserializer for the `CompoundPolicyDeserializer`.
@@ -158,4 +210,26 @@ impl Deserialize for CompoundPolicyDeserializer {
         let roller = deserializers.deserialize(&config.roller.kind, config.roller.config)?;
         Ok(Box::new(CompoundPolicy::new(trigger, roller)))
     }
+
+    fn check_components(
+        &self,
+        config: &CompoundPolicyConfig,
+        deserializers: &Deserializers,
+    ) -> anyhow::Result<()> {
+        deserializers.check_nested::<dyn trigger::Trigger>(
+            "trigger",
+            &config.trigger.kind,
+            config.trigger.config.clone(),
+        )?;
+        deserializers.check_nested::<dyn Roll>(
+            "roller",
+            &config.roller.kind,
+            config.roller.config.clone(),
+        )
+    }
+
+    #[cfg(feature = "schema")]
+    fn schema(&self, generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        <CompoundPolicyConfig as schemars::JsonSchema>::json_schema(generator)
+    }
 }
@@ -10,10 +10,13 @@ use crate::config::{Deserialize, Deserializers};
 
 /// Configuration for the delete roller.
 #[cfg(feature = "config_parsing")]
-#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default, serde::Deserialize)]
+#[derive(
+    Copy, Clone, Eq, PartialEq, Hash, Debug, Default, serde::Deserialize, serde::Serialize,
+)]
 #[serde(deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct DeleteRollerConfig {
-    #[serde(skip_deserializing)]
+    #[serde(skip)]
     _p: (),
 }
 
@@ -25,6 +28,11 @@ impl Roll for DeleteRoller {
     fn roll(&self, file: &Path) -> anyhow::Result<()> {
         fs::remove_file(file).map_err(Into::into)
     }
+
+    #[cfg(feature = "config_parsing")]
+    fn config_value(&self) -> Option<serde_value::Value> {
+        crate::config::component_config("delete", &DeleteRollerConfig::default())
+    }
 }
 
 impl DeleteRoller {
@@ -58,4 +66,9 @@ impl Deserialize for DeleteRollerDeserializer {
     ) -> anyhow::Result<Box<dyn Roll>> {
         Ok(Box::<DeleteRoller>::default())
     }
+
+    #[cfg(feature = "schema")]
+    fn schema(&self, generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        <DeleteRollerConfig as schemars::JsonSchema>::json_schema(generator)
+    }
 }
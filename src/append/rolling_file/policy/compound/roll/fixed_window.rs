@@ -18,8 +18,9 @@ use crate::config::{Deserialize, Deserializers};
 
 /// Configuration for the fixed window roller.
 #[cfg(feature = "config_parsing")]
-#[derive(Clone, Eq, PartialEq, Hash, Debug, Default, serde::Deserialize)]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default, serde::Deserialize, serde::Serialize)]
 #[serde(deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct FixedWindowRollerConfig {
     pattern: String,
     base: Option<u32>,
@@ -170,6 +171,16 @@ impl Roll for FixedWindowRoller {
 
         Ok(())
     }
+
+    #[cfg(feature = "config_parsing")]
+    fn config_value(&self) -> Option<serde_value::Value> {
+        let config = FixedWindowRollerConfig {
+            pattern: self.pattern.clone(),
+            base: Some(self.base),
+            count: self.count,
+        };
+        crate::config::component_config("fixed_window", &config)
+    }
 }
 
 fn move_file<P, Q>(src: P, dst: Q) -> io::Result<()>
@@ -352,6 +363,11 @@ impl Deserialize for FixedWindowRollerDeserializer {
 
         Ok(Box::new(builder.build(&config.pattern, config.count)?))
     }
+
+    #[cfg(feature = "schema")]
+    fn schema(&self, generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        <FixedWindowRollerConfig as schemars::JsonSchema>::json_schema(generator)
+    }
 }
 
 #[cfg(test)]
@@ -2,6 +2,9 @@
 
 use std::{fmt, path::Path};
 
+#[cfg(feature = "config_parsing")]
+use serde_value::Value;
+
 #[cfg(feature = "config_parsing")]
 use crate::config::Deserializable;
 
@@ -20,6 +23,15 @@ pub trait Roll: fmt::Debug + Send + Sync + 'static {
     /// If this method returns successfully, there *must* no longer be a file
     /// at the specified location.
     fn roll(&self, file: &Path) -> anyhow::Result<()>;
+
+    /// Returns the configuration of the roller as it would appear in a
+    /// config file, including its `kind`, if it can be described.
+    ///
+    /// The default implementation returns `None`.
+    #[cfg(feature = "config_parsing")]
+    fn config_value(&self) -> Option<Value> {
+        None
+    }
 }
 
 #[cfg(feature = "config_parsing")]
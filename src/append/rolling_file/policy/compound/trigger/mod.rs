@@ -2,6 +2,9 @@
 
 use std::fmt;
 
+#[cfg(feature = "config_parsing")]
+use serde_value::Value;
+
 use crate::append::rolling_file::LogFile;
 #[cfg(feature = "config_parsing")]
 use crate::config::Deserializable;
@@ -24,6 +27,15 @@ pub trait Trigger: fmt::Debug + Send + Sync + 'static {
     ///
     /// Defaults to true for time triggers and false for size triggers
     fn is_pre_process(&self) -> bool;
+
+    /// Returns the configuration of the trigger as it would appear in a
+    /// config file, including its `kind`, if it can be described.
+    ///
+    /// The default implementation returns `None`.
+    #[cfg(feature = "config_parsing")]
+    fn config_value(&self) -> Option<Value> {
+        None
+    }
 }
 
 #[cfg(feature = "config_parsing")]
@@ -11,8 +11,11 @@ use crate::config::{Deserialize, Deserializers};
 
 /// Configuration for the onstartup trigger.
 #[cfg(feature = "config_parsing")]
-#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default, serde::Deserialize)]
+#[derive(
+    Copy, Clone, Eq, PartialEq, Hash, Debug, Default, serde::Deserialize, serde::Serialize,
+)]
 #[serde(deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct OnStartUpTriggerConfig {
     #[serde(default = "default_min_size")]
     min_size: u64,
@@ -54,6 +57,14 @@ impl Trigger for OnStartUpTrigger {
     fn is_pre_process(&self) -> bool {
         true
     }
+
+    #[cfg(feature = "config_parsing")]
+    fn config_value(&self) -> Option<serde_value::Value> {
+        let config = OnStartUpTriggerConfig {
+            min_size: self.min_size,
+        };
+        crate::config::component_config("onstartup", &config)
+    }
 }
 
 /// A deserializer for the `OnStartUpTrigger`.
@@ -81,6 +92,11 @@ impl Deserialize for OnStartUpTriggerDeserializer {
     ) -> anyhow::Result<Box<dyn Trigger>> {
         Ok(Box::new(OnStartUpTrigger::new(config.min_size)))
     }
+
+    #[cfg(feature = "schema")]
+    fn schema(&self, generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        <OnStartUpTriggerConfig as schemars::JsonSchema>::json_schema(generator)
+    }
 }
 
 #[cfg(test)]
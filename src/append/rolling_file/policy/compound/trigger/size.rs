@@ -14,10 +14,17 @@ use crate::config::{Deserialize, Deserializers};
 
 /// Configuration for the size trigger.
 #[cfg(feature = "config_parsing")]
-#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default, serde::Deserialize)]
+#[derive(
+    Copy, Clone, Eq, PartialEq, Hash, Debug, Default, serde::Deserialize, serde::Serialize,
+)]
 #[serde(deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct SizeTriggerConfig {
     #[serde(deserialize_with = "deserialize_limit")]
+    #[cfg_attr(
+        feature = "schema",
+        schemars(schema_with = "crate::config::schema::size")
+    )]
     limit: u64,
 }
 
@@ -121,6 +128,12 @@ impl Trigger for SizeTrigger {
     fn is_pre_process(&self) -> bool {
         false
     }
+
+    #[cfg(feature = "config_parsing")]
+    fn config_value(&self) -> Option<serde_value::Value> {
+        let config = SizeTriggerConfig { limit: self.limit };
+        crate::config::component_config("size", &config)
+    }
 }
 
 /// A deserializer for the `SizeTrigger`.
@@ -152,6 +165,11 @@ impl Deserialize for SizeTriggerDeserializer {
     ) -> anyhow::Result<Box<dyn Trigger>> {
         Ok(Box::new(SizeTrigger::new(config.limit)))
     }
+
+    #[cfg(feature = "schema")]
+    fn schema(&self, generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        <SizeTriggerConfig as schemars::JsonSchema>::json_schema(generator)
+    }
 }
 
 #[cfg(test)]
@@ -17,8 +17,11 @@ use crate::config::{Deserialize, Deserializers};
 
 #[cfg(feature = "config_parsing")]
 /// Configuration for the time trigger.
-#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default, serde::Deserialize)]
+#[derive(
+    Copy, Clone, Eq, PartialEq, Hash, Debug, Default, serde::Deserialize, serde::Serialize,
+)]
 #[serde(deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct TimeTriggerConfig {
     /// The date/time interval between log file rolls.
     pub interval: TimeTriggerInterval,
@@ -195,6 +198,42 @@ impl<'de> serde::Deserialize<'de> for TimeTriggerInterval {
     }
 }
 
+#[cfg(feature = "config_parsing")]
+impl serde::Serialize for TimeTriggerInterval {
+    fn serialize<S>(&self, s: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let (n, unit) = match *self {
+            TimeTriggerInterval::Second(n) => (n, "seconds"),
+            TimeTriggerInterval::Minute(n) => (n, "minutes"),
+            TimeTriggerInterval::Hour(n) => (n, "hours"),
+            TimeTriggerInterval::Day(n) => (n, "days"),
+            TimeTriggerInterval::Week(n) => (n, "weeks"),
+            TimeTriggerInterval::Month(n) => (n, "months"),
+            TimeTriggerInterval::Year(n) => (n, "years"),
+        };
+        s.collect_str(&format_args!("{} {}", n, unit))
+    }
+}
+
+#[cfg(feature = "schema")]
+impl schemars::JsonSchema for TimeTriggerInterval {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "TimeTriggerInterval".into()
+    }
+
+    fn json_schema(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        schemars::json_schema!({
+            "description": "An interval such as `1 day`, in seconds if no unit is given.",
+            "anyOf": [
+                { "type": "integer", "minimum": 0 },
+                { "type": "string" }
+            ]
+        })
+    }
+}
+
 impl TimeTrigger {
     /// Returns a new trigger which rolls the log once it has passed the
     /// specified time.
@@ -311,6 +350,11 @@ impl Trigger for TimeTrigger {
     fn is_pre_process(&self) -> bool {
         true
     }
+
+    #[cfg(feature = "config_parsing")]
+    fn config_value(&self) -> Option<serde_value::Value> {
+        crate::config::component_config("time", &self.config)
+    }
 }
 
 /// A deserializer for the `TimeTrigger`.
@@ -342,6 +386,11 @@ impl Deserialize for TimeTriggerDeserializer {
     ) -> anyhow::Result<Box<dyn Trigger>> {
         Ok(Box::new(TimeTrigger::new(config)))
     }
+
+    #[cfg(feature = "schema")]
+    fn schema(&self, generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        <TimeTriggerConfig as schemars::JsonSchema>::json_schema(generator)
+    }
 }
 
 #[cfg(test)]
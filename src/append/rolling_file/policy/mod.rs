@@ -1,6 +1,9 @@
 //! Policies.
 use std::fmt;
 
+#[cfg(feature = "config_parsing")]
+use serde_value::Value;
+
 use crate::append::rolling_file::LogFile;
 
 #[cfg(feature = "config_parsing")]
@@ -18,6 +21,15 @@ pub trait Policy: Sync + Send + 'static + fmt::Debug {
     fn process(&self, log: &mut LogFile<'_>) -> anyhow::Result<()>;
     /// Return the config `Trigger.is_pre_process` value
     fn is_pre_process(&self) -> bool;
+
+    /// Returns the configuration of the policy as it would appear in a
+    /// config file, including its `kind`, if it can be described.
+    ///
+    /// The default implementation returns `None`.
+    #[cfg(feature = "config_parsing")]
+    fn config_value(&self) -> Option<Value> {
+        None
+    }
 }
 
 #[cfg(feature = "config_parsing")]
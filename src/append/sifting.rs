@@ -0,0 +1,883 @@
+//! The sifting appender.
+//!
+//! Requires the `sifting_appender` feature.
+//!
+//! A `SiftingAppender` routes each record to a child appender selected by a
+//! discriminating value, read from the MDC or from the record's key-values.
+//! Children are built lazily the first time a value is seen, for example to
+//! write each tenant's logs to its own file.
+//!
+//! Children which have not received a record within the idle timeout are
+//! flushed and dropped, as is the least recently used child when a new one
+//! would exceed the configured maximum. A dropped child is rebuilt if its
+//! value is seen again.
+//!
+//! Children are resolved against the appenders of the `Config` containing the
+//! sifting appender when they are built, so they may forward records to other
+//! appenders, such as a ring buffer appender's `flush_to`. The appenders
+//! children forward to are declared with `SiftingAppenderBuilder::targets`,
+//! or read from the template appender's configuration, and are checked when
+//! the `Config` is built.
+
+use derive_more::Debug;
+use log::Record;
+use parking_lot::Mutex;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Weak},
+    time::{Duration, Instant},
+};
+
+use crate::append::{Append, Appenders};
+#[cfg(feature = "config_parsing")]
+use crate::{
+    append::AppenderConfig,
+    config::{de_duration, ser_duration, Deserialize, Deserializers},
+};
+
+const DEFAULT_VALUE: &str = "unknown";
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+const DEFAULT_MAX_CHILDREN: usize = 100;
+
+/// The placeholder replaced with the discriminating value in the template
+/// appender's configuration.
+pub const KEY_PLACEHOLDER: &str = "$KEY";
+
+/// The sifting appender's configuration.
+#[cfg(feature = "config_parsing")]
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct SiftingAppenderConfig {
+    key: String,
+    source: Option<ConfigSource>,
+    default: Option<String>,
+    #[serde(
+        deserialize_with = "de_duration",
+        serialize_with = "ser_duration",
+        default
+    )]
+    #[cfg_attr(
+        feature = "schema",
+        schemars(schema_with = "crate::config::schema::duration")
+    )]
+    idle_timeout: Option<Duration>,
+    max_children: Option<usize>,
+    appender: AppenderConfig,
+}
+
+#[cfg(feature = "config_parsing")]
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "schema", schemars(rename = "SiftingSource"))]
+enum ConfigSource {
+    #[serde(rename = "mdc")]
+    Mdc,
+    #[cfg(feature = "log_kv")]
+    #[serde(rename = "kv")]
+    Kv,
+}
+
+/// Where the discriminating value of a record is read from.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub enum Source {
+    /// The value is read from the logging thread's MDC.
+    #[default]
+    Mdc,
+    /// The value is read from the record's key-values.
+    ///
+    /// Requires the `log_kv` feature.
+    #[cfg(feature = "log_kv")]
+    Kv,
+}
+
+/// A function building the child appender for a discriminating value.
+pub type ChildFactory = dyn Fn(&str) -> anyhow::Result<Box<dyn Append>> + Send + Sync;
+
+struct Child {
+    appender: Arc<dyn Append>,
+    last_used: Instant,
+}
+
+/// An appender which routes records to child appenders by a discriminating
+/// value.
+#[derive(Debug)]
+pub struct SiftingAppender {
+    key: String,
+    source: Source,
+    default: String,
+    idle_timeout: Option<Duration>,
+    max_children: usize,
+    targets: Vec<String>,
+    #[debug(skip)]
+    factory: Box<ChildFactory>,
+    #[debug(skip)]
+    children: Mutex<HashMap<String, Child>>,
+    // The appenders of the `Config` children are resolved against. They are
+    // held weakly since they include this appender.
+    #[debug(skip)]
+    appenders: Mutex<Vec<(String, Weak<dyn Append>)>>,
+    // The configuration children are built from, if known.
+    #[cfg(feature = "config_parsing")]
+    #[debug(skip)]
+    template: Option<AppenderConfig>,
+}
+
+impl Append for SiftingAppender {
+    fn append(&self, record: &Record<'_>) -> anyhow::Result<()> {
+        let value = self.value(record);
+        let child = self.child(value.as_deref().unwrap_or(&self.default))?;
+        // The lock is not held here so that children do not wait on each
+        // other's I/O.
+        child.append(record)
+    }
+
+    fn flush(&self) {
+        let children = self
+            .children
+            .lock()
+            .values()
+            .map(|c| c.appender.clone())
+            .collect::<Vec<_>>();
+        for child in children {
+            child.flush();
+        }
+    }
+
+    fn resolve(&self, appenders: &Appenders<'_>) -> anyhow::Result<()> {
+        for name in self.targets() {
+            if appenders.get(name).is_none() {
+                anyhow::bail!("no appender named `{}` for children to forward to", name);
+            }
+        }
+
+        *self.appenders.lock() = appenders
+            .0
+            .iter()
+            .map(|(&name, &appender)| (name.to_owned(), Arc::downgrade(appender)))
+            .collect();
+
+        // children kept across a reload are resolved against the new appenders
+        let children = self
+            .children
+            .lock()
+            .values()
+            .map(|c| c.appender.clone())
+            .collect::<Vec<_>>();
+        for child in children {
+            child.resolve(appenders)?;
+        }
+        Ok(())
+    }
+
+    fn targets(&self) -> Vec<&str> {
+        // names built from the discriminating value are only known once a
+        // child is built
+        self.targets
+            .iter()
+            .map(String::as_str)
+            .filter(|name| !name.contains(KEY_PLACEHOLDER))
+            .collect()
+    }
+
+    #[cfg(feature = "config_parsing")]
+    fn config_value(&self) -> Option<serde_value::Value> {
+        let config = SiftingAppenderConfig {
+            key: self.key.clone(),
+            source: Some(match self.source {
+                Source::Mdc => ConfigSource::Mdc,
+                #[cfg(feature = "log_kv")]
+                Source::Kv => ConfigSource::Kv,
+            }),
+            default: Some(self.default.clone()),
+            // children are only kept until the maximum is reached when no
+            // timeout is configured, which a config file cannot express
+            idle_timeout: Some(self.idle_timeout?),
+            max_children: Some(self.max_children),
+            appender: self.template.clone()?,
+        };
+        crate::config::component_config("sifting", &config)
+    }
+}
+
+impl SiftingAppender {
+    /// Creates a new `SiftingAppender` builder.
+    pub fn builder() -> SiftingAppenderBuilder {
+        SiftingAppenderBuilder {
+            source: Source::default(),
+            default: DEFAULT_VALUE.to_owned(),
+            idle_timeout: Some(DEFAULT_IDLE_TIMEOUT),
+            max_children: DEFAULT_MAX_CHILDREN,
+            targets: vec![],
+        }
+    }
+
+    /// Returns the number of child appenders currently alive.
+    pub fn children(&self) -> usize {
+        self.children.lock().len()
+    }
+
+    #[cfg_attr(not(feature = "log_kv"), allow(unused_variables))]
+    fn value(&self, record: &Record<'_>) -> Option<String> {
+        match self.source {
+            Source::Mdc => log_mdc::get(&self.key, |v| v.map(ToOwned::to_owned)),
+            #[cfg(feature = "log_kv")]
+            Source::Kv => {
+                use log::kv::ToKey;
+                record
+                    .key_values()
+                    .get(self.key.to_key())
+                    .map(|v| v.to_string())
+            }
+        }
+    }
+
+    fn child(&self, value: &str) -> anyhow::Result<Arc<dyn Append>> {
+        let now = Instant::now();
+        let mut evicted = vec![];
+
+        let mut children = self.children.lock();
+        if let Some(idle_timeout) = self.idle_timeout {
+            children.retain(|_, child| {
+                let idle = now.saturating_duration_since(child.last_used) >= idle_timeout;
+                if idle {
+                    evicted.push(child.appender.clone());
+                }
+                !idle
+            });
+        }
+
+        let appender = match children.get_mut(value) {
+            Some(child) => {
+                child.last_used = now;
+                Ok(child.appender.clone())
+            }
+            None => {
+                while !children.is_empty() && children.len() >= self.max_children {
+                    let lru = children
+                        .iter()
+                        .min_by_key(|(_, child)| child.last_used)
+                        .map(|(value, _)| value.clone())
+                        .unwrap();
+                    evicted.extend(children.remove(&lru).map(|c| c.appender));
+                }
+
+                (self.factory)(value).and_then(|appender| {
+                    let appender = Arc::<dyn Append>::from(appender);
+                    self.resolve_child(&*appender)?;
+                    children.insert(
+                        value.to_owned(),
+                        Child {
+                            appender: appender.clone(),
+                            last_used: now,
+                        },
+                    );
+                    Ok(appender)
+                })
+            }
+        };
+        drop(children);
+
+        for child in evicted {
+            child.flush();
+        }
+
+        appender
+    }
+
+    fn resolve_child(&self, child: &dyn Append) -> anyhow::Result<()> {
+        let appenders = self
+            .appenders
+            .lock()
+            .iter()
+            .filter_map(|(name, appender)| Some((name.clone(), appender.upgrade()?)))
+            .collect::<Vec<_>>();
+        child.resolve(&Appenders::new(
+            appenders
+                .iter()
+                .map(|(name, appender)| (name.as_str(), appender)),
+        ))
+    }
+}
+
+/// A builder for `SiftingAppender`s.
+pub struct SiftingAppenderBuilder {
+    source: Source,
+    default: String,
+    idle_timeout: Option<Duration>,
+    max_children: usize,
+    targets: Vec<String>,
+}
+
+impl SiftingAppenderBuilder {
+    /// Sets where the discriminating value is read from.
+    ///
+    /// Defaults to `Source::Mdc`.
+    pub fn source(mut self, source: Source) -> SiftingAppenderBuilder {
+        self.source = source;
+        self
+    }
+
+    /// Sets the value used for records which do not carry the key.
+    ///
+    /// Defaults to `unknown`.
+    pub fn default_value(mut self, default: &str) -> SiftingAppenderBuilder {
+        self.default = default.to_owned();
+        self
+    }
+
+    /// Sets how long a child may go without receiving a record before it is
+    /// dropped. `None` keeps children until the maximum is reached.
+    ///
+    /// Defaults to 30 minutes.
+    pub fn idle_timeout(mut self, idle_timeout: Option<Duration>) -> SiftingAppenderBuilder {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    /// Sets the maximum number of children alive at once.
+    ///
+    /// Defaults to 100.
+    pub fn max_children(mut self, max_children: usize) -> SiftingAppenderBuilder {
+        self.max_children = max_children;
+        self
+    }
+
+    /// Sets the names of the other appenders of the `Config` which children
+    /// forward records to, such as a ring buffer appender's `flush_to`.
+    ///
+    /// They must exist, and must not forward records back to the sifting
+    /// appender, for the sifting appender to be resolved. Names containing
+    /// `$KEY` are not checked. Defaults to none.
+    pub fn targets(mut self, targets: Vec<String>) -> SiftingAppenderBuilder {
+        self.targets = targets;
+        self
+    }
+
+    /// Consumes the `SiftingAppenderBuilder`, producing a `SiftingAppender`
+    /// which routes on `key` and builds children with `factory`.
+    ///
+    /// Each child is resolved against the appenders of the `Config` once it
+    /// is built. Errors returned by `factory` or resolving the child are
+    /// reported as errors appending the record which caused it to be built.
+    pub fn build<K, F>(self, key: K, factory: F) -> SiftingAppender
+    where
+        K: Into<String>,
+        F: Fn(&str) -> anyhow::Result<Box<dyn Append>> + Send + Sync + 'static,
+    {
+        SiftingAppender {
+            key: key.into(),
+            source: self.source,
+            default: self.default,
+            idle_timeout: self.idle_timeout,
+            max_children: self.max_children,
+            targets: self.targets,
+            factory: Box::new(factory),
+            children: Mutex::new(HashMap::new()),
+            appenders: Mutex::new(vec![]),
+            #[cfg(feature = "config_parsing")]
+            template: None,
+        }
+    }
+}
+
+/// Replaces every occurrence of `$KEY` in the string values of `config`
+/// with `value`, escaped by `escape`.
+#[cfg(feature = "config_parsing")]
+fn substitute(config: &serde_value::Value, value: &str) -> serde_value::Value {
+    substitute_escaped(config, &escape(value))
+}
+
+#[cfg(feature = "config_parsing")]
+fn substitute_escaped(config: &serde_value::Value, value: &str) -> serde_value::Value {
+    use serde_value::Value;
+
+    match config {
+        Value::String(s) => Value::String(s.replace(KEY_PLACEHOLDER, value)),
+        Value::Option(Some(v)) => Value::Option(Some(Box::new(substitute_escaped(v, value)))),
+        Value::Newtype(v) => Value::Newtype(Box::new(substitute_escaped(v, value))),
+        Value::Seq(vs) => Value::Seq(vs.iter().map(|v| substitute_escaped(v, value)).collect()),
+        Value::Map(m) => Value::Map(
+            m.iter()
+                .map(|(k, v)| (k.clone(), substitute_escaped(v, value)))
+                .collect(),
+        ),
+        v => v.clone(),
+    }
+}
+
+/// Percent-encodes the characters of a discriminating value which could
+/// escape the directory of a path or be expanded by the child appender:
+/// path separators, `..`, `$`, braces, `%` and control characters.
+#[cfg(feature = "config_parsing")]
+fn escape(value: &str) -> String {
+    use std::fmt::Write;
+
+    let mut escaped = String::with_capacity(value.len());
+    let mut chars = value.chars().peekable();
+    let mut prev_dot = false;
+    while let Some(c) = chars.next() {
+        let dots = c == '.' && (prev_dot || chars.peek() == Some(&'.'));
+        prev_dot = c == '.';
+        if dots || matches!(c, '/' | '\\' | '$' | '{' | '}' | '%') || c.is_control() {
+            let mut buf = [0; 4];
+            for b in c.encode_utf8(&mut buf).bytes() {
+                let _ = write!(escaped, "%{:02X}", b);
+            }
+        } else {
+            escaped.push(c);
+        }
+    }
+    escaped
+}
+
+/// A deserializer for the `SiftingAppender`.
+///
+/// # Configuration
+///
+/// ```yaml
+/// kind: sifting
+///
+/// # The key holding the discriminating value. Required.
+/// key: tenant
+///
+/// # Where the key is read from. One of `mdc` or `kv`, which requires the
+/// # `log_kv` feature. Defaults to `mdc`.
+/// source: mdc
+///
+/// # The value used for records without the key. Defaults to `unknown`.
+/// default: unknown
+///
+/// # How long a child may go without receiving a record before it is closed.
+/// # Defaults to 30 minutes.
+/// idle_timeout: 30 minutes
+///
+/// # The maximum number of children open at once. When a new child would
+/// # exceed it, the least recently used child is closed. Defaults to 100.
+/// max_children: 100
+///
+/// # The template for child appenders, which may be of any kind. Every
+/// # occurrence of `$KEY` in its string values is replaced with the
+/// # discriminating value, in which path separators, `..`, `$`, braces, `%`
+/// # and control characters are percent-encoded. Children may forward records
+/// # to other appenders, such as a ring buffer appender's `flush_to`. Required.
+/// appender:
+///   kind: file
+///   path: log/$KEY.log
+/// ```
+#[cfg(feature = "config_parsing")]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct SiftingAppenderDeserializer;
+
+#[cfg(feature = "config_parsing")]
+impl Deserialize for SiftingAppenderDeserializer {
+    type Trait = dyn Append;
+
+    type Config = SiftingAppenderConfig;
+
+    fn deserialize(
+        &self,
+        config: SiftingAppenderConfig,
+        deserializers: &Deserializers,
+    ) -> anyhow::Result<Box<dyn Append>> {
+        if !config.appender.filters.is_empty() || config.appender.on_error.is_some() {
+            anyhow::bail!(
+                "filters and error policies must be attached to the sifting appender rather than the template appender"
+            );
+        }
+
+        let mut builder = SiftingAppender::builder();
+        if let Some(source) = config.source {
+            let source = match source {
+                ConfigSource::Mdc => Source::Mdc,
+                #[cfg(feature = "log_kv")]
+                ConfigSource::Kv => Source::Kv,
+            };
+            builder = builder.source(source);
+        }
+        if let Some(default) = &config.default {
+            builder = builder.default_value(default);
+        }
+        if let Some(idle_timeout) = config.idle_timeout {
+            builder = builder.idle_timeout(Some(idle_timeout));
+        }
+        if let Some(max_children) = config.max_children {
+            builder = builder.max_children(max_children);
+        }
+        builder = builder.targets(config.appender.targets(deserializers));
+
+        let kind = config.appender.kind.clone();
+        let template = config.appender.config.clone();
+        let deserializers = deserializers.clone();
+        let mut appender = builder.build(config.key, move |value: &str| {
+            deserializers.deserialize(&kind, substitute(&template, value))
+        });
+        appender.template = Some(config.appender);
+        Ok(Box::new(appender))
+    }
+
+    fn check_components(
+        &self,
+        config: &SiftingAppenderConfig,
+        deserializers: &Deserializers,
+    ) -> anyhow::Result<()> {
+        config.appender.check("appender", deserializers)
+    }
+
+    fn targets(
+        &self,
+        config: &SiftingAppenderConfig,
+        deserializers: &Deserializers,
+    ) -> Vec<String> {
+        config.appender.targets(deserializers)
+    }
+
+    #[cfg(feature = "schema")]
+    fn schema(&self, generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        <SiftingAppenderConfig as schemars::JsonSchema>::json_schema(generator)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{sync::atomic::AtomicUsize, sync::atomic::Ordering, thread};
+
+    use derive_more::Debug;
+    use log::Level;
+
+    use super::*;
+
+    #[derive(Debug)]
+    struct Recorder {
+        value: String,
+        log: Arc<Mutex<Vec<String>>>,
+        flushes: Arc<AtomicUsize>,
+    }
+
+    impl Append for Recorder {
+        fn append(&self, record: &Record<'_>) -> anyhow::Result<()> {
+            self.log
+                .lock()
+                .push(format!("{}: {}", self.value, record.args()));
+            Ok(())
+        }
+
+        fn flush(&self) {
+            self.flushes.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    type Recorded = (SiftingAppender, Arc<Mutex<Vec<String>>>, Arc<AtomicUsize>);
+
+    fn recording(builder: SiftingAppenderBuilder) -> Recorded {
+        let log = Arc::new(Mutex::new(vec![]));
+        let flushes = Arc::new(AtomicUsize::new(0));
+        let appender = {
+            let log = log.clone();
+            let flushes = flushes.clone();
+            builder.build("tenant", move |value: &str| {
+                Ok(Box::new(Recorder {
+                    value: value.to_owned(),
+                    log: log.clone(),
+                    flushes: flushes.clone(),
+                }) as Box<dyn Append>)
+            })
+        };
+        (appender, log, flushes)
+    }
+
+    fn append(appender: &SiftingAppender, tenant: Option<&str>, message: &str) {
+        // Each test runs on its own thread, so the MDC is not shared.
+        log_mdc::clear();
+        if let Some(tenant) = tenant {
+            log_mdc::insert("tenant", tenant);
+        }
+        appender
+            .append(
+                &Record::builder()
+                    .level(Level::Info)
+                    .args(format_args!("{}", message))
+                    .build(),
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn routes_by_mdc() {
+        let (appender, log, _) = recording(SiftingAppender::builder());
+
+        append(&appender, Some("a"), "one");
+        append(&appender, Some("b"), "two");
+        append(&appender, Some("a"), "three");
+        append(&appender, None, "four");
+
+        assert_eq!(appender.children(), 3);
+        assert_eq!(
+            *log.lock(),
+            ["a: one", "b: two", "a: three", "unknown: four"]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "log_kv")]
+    fn routes_by_kv() {
+        let (appender, log, _) = recording(SiftingAppender::builder().source(Source::Kv));
+
+        let kvs = [("tenant", log::kv::Value::from("acme"))];
+        appender
+            .append(
+                &Record::builder()
+                    .args(format_args!("hello"))
+                    .key_values(&kvs)
+                    .build(),
+            )
+            .unwrap();
+
+        assert_eq!(*log.lock(), ["acme: hello"]);
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let (appender, log, flushes) = recording(SiftingAppender::builder().max_children(2));
+
+        append(&appender, Some("a"), "1");
+        append(&appender, Some("b"), "2");
+        append(&appender, Some("a"), "3");
+        append(&appender, Some("c"), "4");
+
+        assert_eq!(appender.children(), 2);
+        assert_eq!(flushes.load(Ordering::SeqCst), 1);
+
+        // "b" was evicted, so it is rebuilt and "a" goes in its place.
+        append(&appender, Some("b"), "5");
+        assert_eq!(appender.children(), 2);
+        assert_eq!(log.lock().len(), 5);
+    }
+
+    #[test]
+    fn evicts_idle() {
+        let (appender, _, flushes) =
+            recording(SiftingAppender::builder().idle_timeout(Some(Duration::from_millis(50))));
+
+        append(&appender, Some("a"), "1");
+        append(&appender, Some("b"), "2");
+        thread::sleep(Duration::from_millis(100));
+        append(&appender, Some("c"), "3");
+
+        assert_eq!(appender.children(), 1);
+        assert_eq!(flushes.load(Ordering::SeqCst), 2);
+    }
+
+    // Forwards records to the appender named `target`.
+    #[derive(Debug)]
+    struct Forwarder {
+        #[debug(skip)]
+        target: Mutex<Option<Arc<dyn Append>>>,
+    }
+
+    impl Append for Forwarder {
+        fn append(&self, record: &Record<'_>) -> anyhow::Result<()> {
+            match &*self.target.lock() {
+                Some(target) => target.append(record),
+                None => anyhow::bail!("has not been resolved"),
+            }
+        }
+
+        fn flush(&self) {}
+
+        fn resolve(&self, appenders: &Appenders<'_>) -> anyhow::Result<()> {
+            *self.target.lock() = appenders.get("target");
+            Ok(())
+        }
+
+        fn targets(&self) -> Vec<&str> {
+            vec!["target"]
+        }
+    }
+
+    type Forwarding = (
+        Result<crate::Config, crate::config::runtime::ConfigErrors>,
+        Arc<Mutex<Vec<String>>>,
+    );
+
+    fn forwarding(targets: &[&str]) -> Forwarding {
+        use crate::config::{Appender, Config, Root};
+        use log::LevelFilter;
+
+        let log = Arc::new(Mutex::new(vec![]));
+        let target = Recorder {
+            value: "target".to_owned(),
+            log: log.clone(),
+            flushes: Arc::new(AtomicUsize::new(0)),
+        };
+        let sifting = SiftingAppender::builder()
+            .targets(targets.iter().map(|&name| name.to_owned()).collect())
+            .build("tenant", |_: &str| {
+                Ok(Box::new(Forwarder {
+                    target: Mutex::new(None),
+                }) as Box<dyn Append>)
+            });
+        let config = Config::builder()
+            .appender(Appender::builder().build("target", Box::new(target)))
+            .appender(Appender::builder().build("sift", Box::new(sifting)))
+            .build(Root::builder().build(LevelFilter::Info));
+        (config, log)
+    }
+
+    #[test]
+    fn resolves_children() {
+        let (config, log) = forwarding(&["target"]);
+        let config = config.unwrap();
+        let sifting = config
+            .appenders()
+            .iter()
+            .find(|appender| appender.name() == "sift")
+            .unwrap()
+            .appender();
+        log_mdc::insert("tenant", "a");
+        sifting
+            .append(&Record::builder().args(format_args!("one")).build())
+            .unwrap();
+        log_mdc::clear();
+        assert_eq!(*log.lock(), ["target: one"]);
+    }
+
+    #[test]
+    fn checks_targets() {
+        let errors = forwarding(&["sift"]).0.unwrap_err();
+        assert_eq!(
+            errors.errors()[0].to_string(),
+            "Unable to resolve appender `sift`: appender forwards records to itself"
+        );
+
+        let errors = forwarding(&["missing"]).0.unwrap_err();
+        assert_eq!(
+            errors.errors()[0].to_string(),
+            "Unable to resolve appender `sift`: no appender named `missing` for children to \
+             forward to"
+        );
+
+        // names built from the discriminating value are not checked
+        forwarding(&["$KEY"]).0.unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "config_parsing")]
+    fn substitutes_key() {
+        use serde_value::Value;
+        use std::collections::BTreeMap;
+
+        let mut config = BTreeMap::new();
+        config.insert(
+            Value::String("path".to_owned()),
+            Value::String("log/$KEY/$KEY.log".to_owned()),
+        );
+        config.insert(Value::String("append".to_owned()), Value::Bool(true));
+
+        let mut expected = BTreeMap::new();
+        expected.insert(
+            Value::String("path".to_owned()),
+            Value::String("log/acme/acme.log".to_owned()),
+        );
+        expected.insert(Value::String("append".to_owned()), Value::Bool(true));
+
+        assert_eq!(
+            substitute(&Value::Map(config), "acme"),
+            Value::Map(expected)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "config_parsing")]
+    fn escapes_hostile_values() {
+        use serde_value::Value;
+        use std::collections::BTreeMap;
+
+        assert_eq!(escape("acme-1.prod"), "acme-1.prod");
+        assert_eq!(escape("../../etc/x"), "%2E%2E%2F%2E%2E%2Fetc%2Fx");
+        assert_eq!(escape("a/b\\c"), "a%2Fb%5Cc");
+        assert_eq!(escape("..."), "%2E%2E%2E");
+        assert_eq!(escape("a\0b\n"), "a%00b%0A");
+        assert_eq!(escape("$ENV{HOME}"), "%24ENV%7BHOME%7D");
+        assert_eq!(escape("{d}"), "%7Bd%7D");
+        assert_eq!(escape("100%"), "100%25");
+
+        let mut config = BTreeMap::new();
+        config.insert(
+            Value::String("$KEY".to_owned()),
+            Value::String("log/$KEY.log".to_owned()),
+        );
+        let mut expected = BTreeMap::new();
+        expected.insert(
+            Value::String("$KEY".to_owned()),
+            Value::String("log/%2E%2E%2Fx.log".to_owned()),
+        );
+        assert_eq!(
+            substitute(&Value::Map(config), "../x"),
+            Value::Map(expected)
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "yaml_format", feature = "file_appender"))]
+    fn deserialize() {
+        use crate::config::RawConfig;
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = format!(
+            "
+appenders:
+    foo:
+        kind: sifting
+        key: tenant
+        default: none
+        idle_timeout: 1 minute
+        max_children: 10
+        appender:
+            kind: file
+            path: {}/$KEY.log
+            encoder:
+                pattern: \"{{m}}{{n}}\"
+",
+            dir.path().display()
+        );
+
+        let config = ::serde_yaml::from_str::<RawConfig>(&config).unwrap();
+        let (appenders, errors) = config.appenders_lossy(&Deserializers::new());
+        assert!(errors.is_empty());
+
+        log_mdc::insert("tenant", "acme");
+        appenders[0]
+            .appender()
+            .append(&Record::builder().args(format_args!("hello")).build())
+            .unwrap();
+        log_mdc::clear();
+        appenders[0].appender().flush();
+
+        let contents = std::fs::read_to_string(dir.path().join("acme.log")).unwrap();
+        assert_eq!(contents, "hello\n");
+    }
+
+    #[test]
+    #[cfg(all(feature = "yaml_format", feature = "ring_buffer_appender"))]
+    fn template_targets() {
+        let config = ::serde_yaml::from_str::<SiftingAppenderConfig>(
+            "
+key: tenant
+appender:
+    kind: ring_buffer
+    flush_to: target
+",
+        )
+        .unwrap();
+        let deserializers = Deserializers::default();
+        assert_eq!(
+            SiftingAppenderDeserializer.targets(&config, &deserializers),
+            ["target"]
+        );
+        let appender = SiftingAppenderDeserializer
+            .deserialize(config, &deserializers)
+            .unwrap();
+        assert_eq!(appender.targets(), ["target"]);
+    }
+}
@@ -0,0 +1,759 @@
+//! The network socket appender.
+//!
+//! Requires the `socket_appender` feature.
+//!
+//! Each record is encoded, optionally framed, and written to a TCP connection
+//! or sent as a UDP datagram. This is useful for shipping logs to a local
+//! collector such as Vector or Fluent Bit.
+//!
+//! The connection is opened lazily when the first record is appended. If it
+//! cannot be established, or is lost, encoded records are buffered in memory
+//! up to a configurable number of bytes, discarding the oldest first, and
+//! reconnection is retried with exponential backoff. Reconnection attempts are
+//! made from within `append`; wrap the appender in an `async` appender to keep
+//! them off the logging thread. Writes to a TCP connection time out, so that a
+//! collector which stops reading doesn't block logging indefinitely. A record which cannot be sent for another
+//! reason, such as a UDP datagram too large to send, is reported and
+//! discarded.
+
+use derive_more::Debug;
+use log::Record;
+use parking_lot::Mutex;
+use std::{
+    collections::VecDeque,
+    io::{self, Write},
+    net::{SocketAddr, TcpStream, ToSocketAddrs, UdpSocket},
+    time::Duration,
+};
+
+#[cfg(feature = "config_parsing")]
+use crate::config::{de_duration, ser_duration, Deserialize, Deserializers};
+#[cfg(feature = "config_parsing")]
+use crate::encode::EncoderConfig;
+use crate::{
+    append::{
+        backoff::{self, Backoff},
+        Append,
+    },
+    encode::{pattern::PatternEncoder, writer::simple::SimpleWriter, Encode},
+};
+
+const DEFAULT_BUFFER_SIZE: usize = 1024 * 1024;
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The socket appender's configuration.
+#[cfg(feature = "config_parsing")]
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct SocketAppenderConfig {
+    address: String,
+    protocol: Option<ConfigProtocol>,
+    framing: Option<ConfigFraming>,
+    buffer_size: Option<usize>,
+    #[serde(
+        deserialize_with = "de_duration",
+        serialize_with = "ser_duration",
+        default
+    )]
+    #[cfg_attr(
+        feature = "schema",
+        schemars(schema_with = "crate::config::schema::duration")
+    )]
+    reconnect_delay: Option<Duration>,
+    #[serde(
+        deserialize_with = "de_duration",
+        serialize_with = "ser_duration",
+        default
+    )]
+    #[cfg_attr(
+        feature = "schema",
+        schemars(schema_with = "crate::config::schema::duration")
+    )]
+    max_reconnect_delay: Option<Duration>,
+    #[serde(
+        deserialize_with = "de_duration",
+        serialize_with = "ser_duration",
+        default
+    )]
+    #[cfg_attr(
+        feature = "schema",
+        schemars(schema_with = "crate::config::schema::duration")
+    )]
+    connect_timeout: Option<Duration>,
+    #[serde(
+        deserialize_with = "de_duration",
+        serialize_with = "ser_duration",
+        default
+    )]
+    #[cfg_attr(
+        feature = "schema",
+        schemars(schema_with = "crate::config::schema::duration")
+    )]
+    write_timeout: Option<Duration>,
+    encoder: Option<EncoderConfig>,
+}
+
+#[cfg(feature = "config_parsing")]
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "schema", schemars(rename = "SocketProtocol"))]
+enum ConfigProtocol {
+    #[serde(rename = "tcp")]
+    Tcp,
+    #[serde(rename = "udp")]
+    Udp,
+}
+
+#[cfg(feature = "config_parsing")]
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "schema", schemars(rename = "SocketFraming"))]
+enum ConfigFraming {
+    #[serde(rename = "none")]
+    None,
+    #[serde(rename = "newline")]
+    Newline,
+    #[serde(rename = "length_prefix")]
+    LengthPrefix,
+}
+
+/// The transport protocol used by a `SocketAppender`.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub enum Protocol {
+    /// Records are written to a TCP connection.
+    #[default]
+    Tcp,
+    /// Each record is sent as a single UDP datagram.
+    Udp,
+}
+
+/// How encoded records are delimited on the wire.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub enum Framing {
+    /// The encoder's output is written as is.
+    #[default]
+    None,
+    /// A newline is appended unless the encoder's output already ends in one.
+    Newline,
+    /// The encoder's output is preceded by its length as a big-endian `u32`.
+    LengthPrefix,
+}
+
+impl Framing {
+    fn frame(self, mut buf: Vec<u8>) -> Vec<u8> {
+        match self {
+            Framing::None => buf,
+            Framing::Newline => {
+                if buf.last() != Some(&b'\n') {
+                    buf.push(b'\n');
+                }
+                buf
+            }
+            Framing::LengthPrefix => {
+                let mut framed = Vec::with_capacity(buf.len() + 4);
+                framed.extend_from_slice(&(buf.len() as u32).to_be_bytes());
+                framed.append(&mut buf);
+                framed
+            }
+        }
+    }
+}
+
+enum Connection {
+    Tcp(TcpStream),
+    Udp(UdpSocket),
+}
+
+impl Connection {
+    fn send(&mut self, message: &[u8]) -> io::Result<()> {
+        match self {
+            Connection::Tcp(stream) => stream.write_all(message),
+            Connection::Udp(socket) => loop {
+                match socket.send(message) {
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    result => return result.map(drop),
+                }
+            },
+        }
+    }
+
+    /// Returns whether `e`, returned by `send`, means that the connection
+    /// must be established again. Otherwise the message itself could not be
+    /// sent, for example a datagram which is too large.
+    fn lost(&self, e: &io::Error) -> bool {
+        match self {
+            // a stream may have been partially written to
+            Connection::Tcp(_) => true,
+            Connection::Udp(_) => matches!(
+                e.kind(),
+                io::ErrorKind::ConnectionRefused
+                    | io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::NotConnected
+                    | io::ErrorKind::AddrNotAvailable
+                    | io::ErrorKind::TimedOut
+            ),
+        }
+    }
+}
+
+struct State {
+    connection: Option<Connection>,
+    pending: VecDeque<Vec<u8>>,
+    pending_len: usize,
+    backoff: Backoff,
+}
+
+/// An appender which writes encoded records to a TCP or UDP socket.
+#[derive(Debug)]
+pub struct SocketAppender {
+    address: String,
+    protocol: Protocol,
+    framing: Framing,
+    buffer_size: usize,
+    connect_timeout: Duration,
+    write_timeout: Duration,
+    encoder: Box<dyn Encode>,
+    #[debug(skip)]
+    state: Mutex<State>,
+}
+
+impl Append for SocketAppender {
+    fn append(&self, record: &Record<'_>) -> anyhow::Result<()> {
+        let mut buf = vec![];
+        self.encoder.encode(&mut SimpleWriter(&mut buf), record)?;
+        let message = self.framing.frame(buf);
+
+        let mut state = self.state.lock();
+        self.push(&mut state, message);
+        self.send_pending(&mut state)?;
+        Ok(())
+    }
+
+    fn flush(&self) {
+        if let Some(Connection::Tcp(stream)) = &mut self.state.lock().connection {
+            let _ = stream.flush();
+        }
+    }
+
+    #[cfg(feature = "config_parsing")]
+    fn config_value(&self) -> Option<serde_value::Value> {
+        let backoff = &self.state.lock().backoff;
+        let config = SocketAppenderConfig {
+            address: self.address.clone(),
+            protocol: Some(match self.protocol {
+                Protocol::Tcp => ConfigProtocol::Tcp,
+                Protocol::Udp => ConfigProtocol::Udp,
+            }),
+            framing: Some(match self.framing {
+                Framing::None => ConfigFraming::None,
+                Framing::Newline => ConfigFraming::Newline,
+                Framing::LengthPrefix => ConfigFraming::LengthPrefix,
+            }),
+            buffer_size: Some(self.buffer_size),
+            reconnect_delay: Some(backoff.initial),
+            max_reconnect_delay: Some(backoff.max),
+            connect_timeout: Some(self.connect_timeout),
+            write_timeout: Some(self.write_timeout),
+            encoder: Some(EncoderConfig::describe(&*self.encoder)?),
+        };
+        crate::config::component_config("socket", &config)
+    }
+}
+
+impl SocketAppender {
+    /// Creates a new `SocketAppender` builder.
+    pub fn builder() -> SocketAppenderBuilder {
+        SocketAppenderBuilder {
+            protocol: Protocol::default(),
+            framing: Framing::default(),
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            reconnect_delay: backoff::DEFAULT_DELAY,
+            max_reconnect_delay: backoff::DEFAULT_MAX_DELAY,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            write_timeout: None,
+            encoder: None,
+        }
+    }
+
+    fn push(&self, state: &mut State, message: Vec<u8>) {
+        state.pending_len += message.len();
+        state.pending.push_back(message);
+        while state.pending_len > self.buffer_size {
+            match state.pending.pop_front() {
+                Some(dropped) => state.pending_len -= dropped.len(),
+                None => break,
+            }
+        }
+    }
+
+    fn send_pending(&self, state: &mut State) -> io::Result<()> {
+        if state.connection.is_none() {
+            if !state.backoff.ready() {
+                return Ok(());
+            }
+
+            match self.connect() {
+                Ok(connection) => {
+                    state.connection = Some(connection);
+                    state.backoff.succeeded();
+                }
+                Err(e) => {
+                    // Only the first failure after losing the connection is
+                    // reported, to avoid flooding the error handler.
+                    let first = state.backoff.failed();
+                    return if first { Err(e) } else { Ok(()) };
+                }
+            }
+        }
+
+        let mut result = Ok(());
+        while let Some(message) = state.pending.front() {
+            // `connection` was either present or has just been established.
+            let connection = state.connection.as_mut().unwrap();
+            match connection.send(message) {
+                Ok(()) => {}
+                Err(e) if connection.lost(&e) => {
+                    state.connection = None;
+                    state.backoff.failed();
+                    return Err(e);
+                }
+                // The message is discarded rather than retried forever ahead
+                // of the others.
+                Err(e) => {
+                    if result.is_ok() {
+                        result = Err(e);
+                    }
+                }
+            }
+            let len = message.len();
+            state.pending.pop_front();
+            state.pending_len -= len;
+        }
+
+        result
+    }
+
+    fn connect(&self) -> io::Result<Connection> {
+        let mut last_err = None;
+        for addr in self.address.to_socket_addrs()? {
+            match self.connect_addr(addr) {
+                Ok(connection) => return Ok(connection),
+                Err(e) => last_err = Some(e),
+            }
+        }
+        Err(last_err.unwrap_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("`{}` did not resolve to any address", self.address),
+            )
+        }))
+    }
+
+    fn connect_addr(&self, addr: SocketAddr) -> io::Result<Connection> {
+        match self.protocol {
+            Protocol::Tcp => {
+                let stream = TcpStream::connect_timeout(&addr, self.connect_timeout)?;
+                stream.set_nodelay(true)?;
+                stream.set_write_timeout(Some(self.write_timeout))?;
+                Ok(Connection::Tcp(stream))
+            }
+            Protocol::Udp => {
+                let local: SocketAddr = if addr.is_ipv4() {
+                    ([0, 0, 0, 0], 0).into()
+                } else {
+                    ([0u16; 8], 0).into()
+                };
+                let socket = UdpSocket::bind(local)?;
+                socket.connect(addr)?;
+                Ok(Connection::Udp(socket))
+            }
+        }
+    }
+}
+
+/// A builder for `SocketAppender`s.
+pub struct SocketAppenderBuilder {
+    protocol: Protocol,
+    framing: Framing,
+    buffer_size: usize,
+    reconnect_delay: Duration,
+    max_reconnect_delay: Duration,
+    connect_timeout: Duration,
+    write_timeout: Option<Duration>,
+    encoder: Option<Box<dyn Encode>>,
+}
+
+impl SocketAppenderBuilder {
+    /// Sets the transport protocol.
+    ///
+    /// Defaults to `Protocol::Tcp`.
+    pub fn protocol(mut self, protocol: Protocol) -> SocketAppenderBuilder {
+        self.protocol = protocol;
+        self
+    }
+
+    /// Sets how encoded records are delimited.
+    ///
+    /// Defaults to `Framing::None`.
+    pub fn framing(mut self, framing: Framing) -> SocketAppenderBuilder {
+        self.framing = framing;
+        self
+    }
+
+    /// Sets the maximum number of bytes buffered while disconnected.
+    ///
+    /// Defaults to 1 MiB.
+    pub fn buffer_size(mut self, buffer_size: usize) -> SocketAppenderBuilder {
+        self.buffer_size = buffer_size;
+        self
+    }
+
+    /// Sets the delay before the first reconnection attempt. The delay doubles
+    /// after every failed attempt.
+    ///
+    /// Defaults to 100 milliseconds.
+    pub fn reconnect_delay(mut self, reconnect_delay: Duration) -> SocketAppenderBuilder {
+        self.reconnect_delay = reconnect_delay;
+        self
+    }
+
+    /// Sets the upper bound on the delay between reconnection attempts.
+    ///
+    /// Defaults to 30 seconds.
+    pub fn max_reconnect_delay(mut self, max_reconnect_delay: Duration) -> SocketAppenderBuilder {
+        self.max_reconnect_delay = max_reconnect_delay;
+        self
+    }
+
+    /// Sets the timeout for establishing a TCP connection.
+    ///
+    /// Defaults to 5 seconds.
+    pub fn connect_timeout(mut self, connect_timeout: Duration) -> SocketAppenderBuilder {
+        self.connect_timeout = connect_timeout;
+        self
+    }
+
+    /// Sets the timeout for writing a record to a TCP connection, after which
+    /// the connection is considered lost, so that a peer which stops reading
+    /// does not block logging indefinitely.
+    ///
+    /// Defaults to the connect timeout.
+    pub fn write_timeout(mut self, write_timeout: Duration) -> SocketAppenderBuilder {
+        self.write_timeout = Some(write_timeout);
+        self
+    }
+
+    /// Sets the output encoder for the `SocketAppender`.
+    pub fn encoder(mut self, encoder: Box<dyn Encode>) -> SocketAppenderBuilder {
+        self.encoder = Some(encoder);
+        self
+    }
+
+    /// Consumes the `SocketAppenderBuilder`, producing a `SocketAppender`
+    /// which sends records to `address`, a `host:port` pair.
+    ///
+    /// No connection is made until the first record is appended.
+    pub fn build<T>(self, address: T) -> SocketAppender
+    where
+        T: Into<String>,
+    {
+        SocketAppender {
+            address: address.into(),
+            protocol: self.protocol,
+            framing: self.framing,
+            buffer_size: self.buffer_size,
+            connect_timeout: self.connect_timeout,
+            write_timeout: self.write_timeout.unwrap_or(self.connect_timeout),
+            encoder: self
+                .encoder
+                .unwrap_or_else(|| Box::<PatternEncoder>::default()),
+            state: Mutex::new(State {
+                connection: None,
+                pending: VecDeque::new(),
+                pending_len: 0,
+                backoff: Backoff::new(self.reconnect_delay, self.max_reconnect_delay),
+            }),
+        }
+    }
+}
+
+/// A deserializer for the `SocketAppender`.
+///
+/// # Configuration
+///
+/// ```yaml
+/// kind: socket
+///
+/// # The `host:port` address to send records to. Required.
+/// address: 127.0.0.1:9000
+///
+/// # The transport protocol. One of `tcp` or `udp`. Defaults to `tcp`.
+/// protocol: tcp
+///
+/// # How records are delimited. One of `none`, `newline` or `length_prefix`,
+/// # where the length is a big-endian 32 bit integer. Defaults to `none`.
+/// framing: newline
+///
+/// # The maximum number of bytes buffered while the connection is down. The
+/// # oldest records are discarded first. Defaults to 1048576.
+/// buffer_size: 1048576
+///
+/// # The delay before the first reconnection attempt, which doubles after
+/// # every failed attempt up to `max_reconnect_delay`. Default to 100
+/// # milliseconds and 30 seconds.
+/// reconnect_delay: 100 ms
+/// max_reconnect_delay: 30 seconds
+///
+/// # The timeout for establishing a TCP connection. Defaults to 5 seconds.
+/// connect_timeout: 5 seconds
+///
+/// # The timeout for writing a record to a TCP connection, after which the
+/// # connection is considered lost. Defaults to `connect_timeout`.
+/// write_timeout: 5 seconds
+///
+/// # The encoder to use to format output. Defaults to `kind: pattern`.
+/// encoder:
+///   kind: json
+/// ```
+#[cfg(feature = "config_parsing")]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct SocketAppenderDeserializer;
+
+#[cfg(feature = "config_parsing")]
+impl Deserialize for SocketAppenderDeserializer {
+    type Trait = dyn Append;
+
+    type Config = SocketAppenderConfig;
+
+    fn deserialize(
+        &self,
+        config: SocketAppenderConfig,
+        deserializers: &Deserializers,
+    ) -> anyhow::Result<Box<dyn Append>> {
+        let mut appender = SocketAppender::builder();
+        if let Some(protocol) = config.protocol {
+            let protocol = match protocol {
+                ConfigProtocol::Tcp => Protocol::Tcp,
+                ConfigProtocol::Udp => Protocol::Udp,
+            };
+            appender = appender.protocol(protocol);
+        }
+        if let Some(framing) = config.framing {
+            let framing = match framing {
+                ConfigFraming::None => Framing::None,
+                ConfigFraming::Newline => Framing::Newline,
+                ConfigFraming::LengthPrefix => Framing::LengthPrefix,
+            };
+            appender = appender.framing(framing);
+        }
+        if let Some(buffer_size) = config.buffer_size {
+            appender = appender.buffer_size(buffer_size);
+        }
+        if let Some(reconnect_delay) = config.reconnect_delay {
+            appender = appender.reconnect_delay(reconnect_delay);
+        }
+        if let Some(max_reconnect_delay) = config.max_reconnect_delay {
+            appender = appender.max_reconnect_delay(max_reconnect_delay);
+        }
+        if let Some(connect_timeout) = config.connect_timeout {
+            appender = appender.connect_timeout(connect_timeout);
+        }
+        if let Some(write_timeout) = config.write_timeout {
+            appender = appender.write_timeout(write_timeout);
+        }
+        if let Some(encoder) = config.encoder {
+            appender = appender.encoder(deserializers.deserialize(&encoder.kind, encoder.config)?);
+        }
+        Ok(Box::new(appender.build(config.address)))
+    }
+
+    fn check_components(
+        &self,
+        config: &SocketAppenderConfig,
+        deserializers: &Deserializers,
+    ) -> anyhow::Result<()> {
+        match &config.encoder {
+            Some(encoder) => deserializers.check_nested::<dyn Encode>(
+                "encoder",
+                &encoder.kind,
+                encoder.config.clone(),
+            ),
+            None => Ok(()),
+        }
+    }
+
+    #[cfg(feature = "schema")]
+    fn schema(&self, generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        <SocketAppenderConfig as schemars::JsonSchema>::json_schema(generator)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{io::Read, net::TcpListener, time::Instant};
+
+    use log::Level;
+
+    use super::*;
+
+    fn append(appender: &SocketAppender, message: &str) -> anyhow::Result<()> {
+        appender.append(
+            &Record::builder()
+                .level(Level::Info)
+                .args(format_args!("{}", message))
+                .build(),
+        )
+    }
+
+    fn read_all(listener: &TcpListener) -> Vec<u8> {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut buf = vec![];
+        stream.read_to_end(&mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn frames() {
+        assert_eq!(Framing::None.frame(b"a".to_vec()), b"a");
+        assert_eq!(Framing::Newline.frame(b"a".to_vec()), b"a\n");
+        assert_eq!(Framing::Newline.frame(b"a\n".to_vec()), b"a\n");
+        assert_eq!(
+            Framing::LengthPrefix.frame(b"abc".to_vec()),
+            b"\x00\x00\x00\x03abc"
+        );
+    }
+
+    #[test]
+    fn tcp_newline() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let appender = SocketAppender::builder()
+            .framing(Framing::Newline)
+            .encoder(Box::new(PatternEncoder::new("{m}")))
+            .build(listener.local_addr().unwrap().to_string());
+
+        append(&appender, "foo").unwrap();
+        append(&appender, "bar").unwrap();
+        drop(appender);
+
+        assert_eq!(read_all(&listener), b"foo\nbar\n");
+    }
+
+    #[test]
+    fn tcp_length_prefix() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let appender = SocketAppender::builder()
+            .framing(Framing::LengthPrefix)
+            .encoder(Box::new(PatternEncoder::new("{m}")))
+            .build(listener.local_addr().unwrap().to_string());
+
+        append(&appender, "hello").unwrap();
+        drop(appender);
+
+        assert_eq!(read_all(&listener), b"\x00\x00\x00\x05hello");
+    }
+
+    #[test]
+    fn udp() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let appender = SocketAppender::builder()
+            .protocol(Protocol::Udp)
+            .encoder(Box::new(PatternEncoder::new("{l} {m}")))
+            .build(server.local_addr().unwrap().to_string());
+
+        append(&appender, "datagram").unwrap();
+
+        let mut buf = [0; 64];
+        let len = server.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"INFO datagram");
+    }
+
+    #[test]
+    fn udp_oversized() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let appender = SocketAppender::builder()
+            .protocol(Protocol::Udp)
+            .encoder(Box::new(PatternEncoder::new("{m}")))
+            .build(server.local_addr().unwrap().to_string());
+
+        // larger than any datagram
+        assert!(append(&appender, &"x".repeat(70_000)).is_err());
+        append(&appender, "next").unwrap();
+
+        let mut buf = [0; 64];
+        let len = server.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"next");
+        assert_eq!(appender.state.lock().pending_len, 0);
+    }
+
+    #[test]
+    fn buffers_until_reconnected() {
+        let address = {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            listener.local_addr().unwrap()
+        };
+        let appender = SocketAppender::builder()
+            .framing(Framing::Newline)
+            .reconnect_delay(Duration::ZERO)
+            .buffer_size(11)
+            .encoder(Box::new(PatternEncoder::new("{m}")))
+            .build(address.to_string());
+
+        // The first failure is reported, subsequent ones are not.
+        assert!(append(&appender, "one").is_err());
+        append(&appender, "two").unwrap();
+        append(&appender, "three").unwrap();
+
+        let listener = TcpListener::bind(address).unwrap();
+        append(&appender, "four").unwrap();
+        drop(appender);
+
+        // "one" and "two" were discarded to stay within the buffer size.
+        assert_eq!(read_all(&listener), b"three\nfour\n");
+    }
+
+    #[test]
+    fn write_timeout() {
+        // the collector accepts the connection but never reads from it
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let appender = SocketAppender::builder()
+            .write_timeout(Duration::from_millis(50))
+            .reconnect_delay(Duration::from_secs(60))
+            .encoder(Box::new(PatternEncoder::new("{m}")))
+            .build(listener.local_addr().unwrap().to_string());
+
+        let message = "x".repeat(1024 * 1024);
+        let start = Instant::now();
+        let failed = (0..256).any(|_| append(&appender, &message).is_err());
+        assert!(failed);
+        assert!(start.elapsed() < Duration::from_secs(30));
+        drop(listener);
+    }
+
+    #[test]
+    #[cfg(feature = "yaml_format")]
+    fn deserialize() {
+        use crate::config::RawConfig;
+
+        let config = "
+appenders:
+    foo:
+        kind: socket
+        address: 127.0.0.1:9000
+        protocol: udp
+        framing: length_prefix
+        buffer_size: 4096
+        reconnect_delay: 1 second
+        max_reconnect_delay: 1 minute
+        connect_timeout: 2 seconds
+        write_timeout: 1 second
+        encoder:
+            kind: pattern
+";
+
+        let config = ::serde_yaml::from_str::<RawConfig>(config).unwrap();
+        let errors = config.appenders_lossy(&Deserializers::new()).1;
+        assert!(errors.is_empty());
+    }
+}
@@ -0,0 +1,874 @@
+//! The syslog appender.
+//!
+//! Requires the `syslog_appender` feature.
+//!
+//! Messages are formatted according to either [RFC 5424] or the older BSD
+//! format described by [RFC 3164], and delivered over a Unix domain socket
+//! (such as `/dev/log`), UDP or TCP. Over TCP, messages are framed by octet
+//! counting as described by [RFC 6587]. Over a Unix domain socket, the
+//! appender connects again when the daemon recreates its socket, as it does
+//! when restarted.
+//!
+//! The message body is produced by the appender's encoder. Any trailing
+//! newline written by the encoder is removed.
+//!
+//! [RFC 5424]: https://datatracker.ietf.org/doc/html/rfc5424
+//! [RFC 3164]: https://datatracker.ietf.org/doc/html/rfc3164
+//! [RFC 6587]: https://datatracker.ietf.org/doc/html/rfc6587#section-3.4.1
+
+use chrono::{DateTime, Local};
+use derive_more::Debug;
+use log::{Level, Record};
+use parking_lot::Mutex;
+#[cfg(unix)]
+use std::os::unix::net::UnixDatagram;
+use std::{
+    env,
+    io::{self, Write},
+    net::{SocketAddr, TcpStream, ToSocketAddrs, UdpSocket},
+    path::{Path, PathBuf},
+    process,
+};
+
+#[cfg(feature = "config_parsing")]
+use crate::config::{Deserialize, Deserializers};
+#[cfg(feature = "config_parsing")]
+use crate::encode::EncoderConfig;
+use crate::{
+    append::Append,
+    encode::{pattern::PatternEncoder, writer::simple::SimpleWriter, Encode},
+    priv_host,
+};
+
+/// The syslog appender's configuration.
+#[cfg(feature = "config_parsing")]
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct SyslogAppenderConfig {
+    protocol: Option<ConfigProtocol>,
+    transport: Option<ConfigTransport>,
+    address: Option<String>,
+    facility: Option<Facility>,
+    app_name: Option<String>,
+    hostname: Option<String>,
+    encoder: Option<EncoderConfig>,
+}
+
+#[cfg(feature = "config_parsing")]
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "schema", schemars(rename = "SyslogProtocol"))]
+enum ConfigProtocol {
+    #[serde(rename = "rfc5424")]
+    Rfc5424,
+    #[serde(rename = "rfc3164")]
+    Rfc3164,
+}
+
+#[cfg(feature = "config_parsing")]
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "schema", schemars(rename = "SyslogTransport"))]
+enum ConfigTransport {
+    #[serde(rename = "unix")]
+    Unix,
+    #[serde(rename = "udp")]
+    Udp,
+    #[serde(rename = "tcp")]
+    Tcp,
+}
+
+/// The syslog message format.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub enum Protocol {
+    /// The format described by RFC 5424.
+    #[default]
+    Rfc5424,
+    /// The BSD format described by RFC 3164.
+    Rfc3164,
+}
+
+/// A syslog facility.
+#[allow(missing_docs)]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+#[cfg_attr(
+    feature = "config_parsing",
+    derive(serde::Deserialize, serde::Serialize)
+)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "config_parsing", serde(rename_all = "lowercase"))]
+pub enum Facility {
+    Kern,
+    #[default]
+    User,
+    Mail,
+    Daemon,
+    Auth,
+    Syslog,
+    Lpr,
+    News,
+    Uucp,
+    Cron,
+    Authpriv,
+    Ftp,
+    Local0,
+    Local1,
+    Local2,
+    Local3,
+    Local4,
+    Local5,
+    Local6,
+    Local7,
+}
+
+impl Facility {
+    fn code(self) -> u8 {
+        match self {
+            Facility::Kern => 0,
+            Facility::User => 1,
+            Facility::Mail => 2,
+            Facility::Daemon => 3,
+            Facility::Auth => 4,
+            Facility::Syslog => 5,
+            Facility::Lpr => 6,
+            Facility::News => 7,
+            Facility::Uucp => 8,
+            Facility::Cron => 9,
+            Facility::Authpriv => 10,
+            Facility::Ftp => 11,
+            Facility::Local0 => 16,
+            Facility::Local1 => 17,
+            Facility::Local2 => 18,
+            Facility::Local3 => 19,
+            Facility::Local4 => 20,
+            Facility::Local5 => 21,
+            Facility::Local6 => 22,
+            Facility::Local7 => 23,
+        }
+    }
+}
+
+/// Returns the syslog severity corresponding to a log level.
+///
+/// `Trace` and `Debug` both map to the `debug` severity.
+pub fn severity(level: Level) -> u8 {
+    match level {
+        Level::Error => 3,
+        Level::Warn => 4,
+        Level::Info => 6,
+        Level::Debug | Level::Trace => 7,
+    }
+}
+
+/// The socket used to deliver messages to the syslog daemon.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Transport {
+    /// A Unix domain datagram socket at the specified path.
+    #[cfg(unix)]
+    Unix(PathBuf),
+    /// A UDP socket connected to the specified address.
+    Udp(String),
+    /// A TCP connection to the specified address, using octet-counted framing.
+    Tcp(String),
+}
+
+impl Default for Transport {
+    #[cfg(unix)]
+    fn default() -> Transport {
+        Transport::Unix(PathBuf::from("/dev/log"))
+    }
+
+    #[cfg(not(unix))]
+    fn default() -> Transport {
+        Transport::Udp("127.0.0.1:514".to_owned())
+    }
+}
+
+enum Connection {
+    #[cfg(unix)]
+    Unix {
+        path: PathBuf,
+        socket: Option<UnixDatagram>,
+    },
+    Udp(UdpSocket),
+    Tcp {
+        address: String,
+        stream: Option<TcpStream>,
+    },
+}
+
+impl Connection {
+    fn open(transport: &Transport) -> io::Result<Connection> {
+        match transport {
+            #[cfg(unix)]
+            Transport::Unix(path) => Ok(Connection::Unix {
+                socket: Some(connect_unix(path)?),
+                path: path.clone(),
+            }),
+            Transport::Udp(address) => {
+                let mut last_err = None;
+                for addr in address.to_socket_addrs()? {
+                    match connect_udp(addr) {
+                        Ok(socket) => return Ok(Connection::Udp(socket)),
+                        Err(e) => last_err = Some(e),
+                    }
+                }
+                Err(last_err.unwrap_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("`{}` did not resolve to any address", address),
+                    )
+                }))
+            }
+            Transport::Tcp(address) => Ok(Connection::Tcp {
+                stream: Some(TcpStream::connect(address)?),
+                address: address.clone(),
+            }),
+        }
+    }
+
+    fn send(&mut self, message: &[u8]) -> io::Result<()> {
+        match self {
+            #[cfg(unix)]
+            Connection::Unix { path, socket } => {
+                let result = match socket {
+                    Some(s) => s.send(message).map(drop),
+                    None => Err(io::ErrorKind::NotConnected.into()),
+                };
+                match result {
+                    // The daemon has restarted and recreated its socket, or
+                    // hasn't yet, so connect again and retry.
+                    Err(e) if is_disconnected(&e) => {
+                        *socket = None;
+                        let s = socket.insert(connect_unix(path)?);
+                        s.send(message).map(drop)
+                    }
+                    result => result,
+                }
+            }
+            Connection::Udp(socket) => socket.send(message).map(drop),
+            Connection::Tcp { address, stream } => {
+                // Reconnect if the previous write failed.
+                let s = match stream {
+                    Some(s) => s,
+                    None => stream.insert(TcpStream::connect(&*address)?),
+                };
+                let result = write!(s, "{} ", message.len())
+                    .and_then(|()| s.write_all(message))
+                    .and_then(|()| s.flush());
+                if result.is_err() {
+                    *stream = None;
+                }
+                result
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn connect_unix(path: &Path) -> io::Result<UnixDatagram> {
+    let socket = UnixDatagram::unbound()?;
+    socket.connect(path)?;
+    Ok(socket)
+}
+
+#[cfg(unix)]
+fn is_disconnected(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::ConnectionRefused | io::ErrorKind::NotFound | io::ErrorKind::NotConnected
+    )
+}
+
+fn connect_udp(addr: SocketAddr) -> io::Result<UdpSocket> {
+    let local: SocketAddr = if addr.is_ipv4() {
+        ([0, 0, 0, 0], 0).into()
+    } else {
+        ([0u16; 8], 0).into()
+    };
+    let socket = UdpSocket::bind(local)?;
+    socket.connect(addr)?;
+    Ok(socket)
+}
+
+/// An appender which sends log messages to a syslog daemon.
+#[derive(Debug)]
+pub struct SyslogAppender {
+    #[debug(skip)]
+    connection: Mutex<Connection>,
+    #[cfg_attr(not(feature = "config_parsing"), allow(dead_code))]
+    transport: Transport,
+    protocol: Protocol,
+    facility: Facility,
+    app_name: String,
+    hostname: String,
+    pid: u32,
+    encoder: Box<dyn Encode>,
+}
+
+impl Append for SyslogAppender {
+    fn append(&self, record: &Record<'_>) -> anyhow::Result<()> {
+        let message = self.format(Local::now(), record)?;
+        self.connection.lock().send(&message)?;
+        Ok(())
+    }
+
+    fn flush(&self) {}
+
+    #[cfg(feature = "config_parsing")]
+    fn config_value(&self) -> Option<serde_value::Value> {
+        let (transport, address) = match &self.transport {
+            #[cfg(unix)]
+            Transport::Unix(path) => (ConfigTransport::Unix, path.to_str()?.to_owned()),
+            Transport::Udp(address) => (ConfigTransport::Udp, address.clone()),
+            Transport::Tcp(address) => (ConfigTransport::Tcp, address.clone()),
+        };
+        let config = SyslogAppenderConfig {
+            protocol: Some(match self.protocol {
+                Protocol::Rfc5424 => ConfigProtocol::Rfc5424,
+                Protocol::Rfc3164 => ConfigProtocol::Rfc3164,
+            }),
+            transport: Some(transport),
+            address: Some(address),
+            facility: Some(self.facility),
+            app_name: Some(self.app_name.clone()),
+            hostname: Some(self.hostname.clone()),
+            encoder: Some(EncoderConfig::describe(&*self.encoder)?),
+        };
+        crate::config::component_config("syslog", &config)
+    }
+}
+
+impl SyslogAppender {
+    /// Creates a new `SyslogAppender` builder.
+    pub fn builder() -> SyslogAppenderBuilder {
+        SyslogAppenderBuilder {
+            protocol: Protocol::default(),
+            facility: Facility::default(),
+            app_name: None,
+            hostname: None,
+            encoder: None,
+        }
+    }
+
+    fn format(&self, time: DateTime<Local>, record: &Record<'_>) -> anyhow::Result<Vec<u8>> {
+        let mut buf = vec![];
+        let priority = self.facility.code() * 8 + severity(record.level());
+
+        match self.protocol {
+            Protocol::Rfc5424 => write!(
+                buf,
+                "<{}>1 {} {} {} {} - - ",
+                priority,
+                // RFC 5424 allows at most microseconds
+                time.format("%Y-%m-%dT%H:%M:%S%.6f%:z"),
+                self.hostname,
+                self.app_name,
+                self.pid
+            )?,
+            Protocol::Rfc3164 => write!(
+                buf,
+                "<{}>{} {} {}[{}]: ",
+                priority,
+                time.format("%b %e %H:%M:%S"),
+                self.hostname,
+                self.app_name,
+                self.pid
+            )?,
+        }
+
+        self.encoder.encode(&mut SimpleWriter(&mut buf), record)?;
+        while buf.last() == Some(&b'\n') || buf.last() == Some(&b'\r') {
+            buf.pop();
+        }
+
+        Ok(buf)
+    }
+}
+
+/// A builder for `SyslogAppender`s.
+pub struct SyslogAppenderBuilder {
+    protocol: Protocol,
+    facility: Facility,
+    app_name: Option<String>,
+    hostname: Option<String>,
+    encoder: Option<Box<dyn Encode>>,
+}
+
+impl SyslogAppenderBuilder {
+    /// Sets the message format.
+    ///
+    /// Defaults to `Protocol::Rfc5424`.
+    pub fn protocol(mut self, protocol: Protocol) -> SyslogAppenderBuilder {
+        self.protocol = protocol;
+        self
+    }
+
+    /// Sets the facility attached to every message.
+    ///
+    /// Defaults to `Facility::User`.
+    pub fn facility(mut self, facility: Facility) -> SyslogAppenderBuilder {
+        self.facility = facility;
+        self
+    }
+
+    /// Sets the application name attached to every message.
+    ///
+    /// Characters other than printable ASCII, including spaces, are replaced
+    /// with `_`, and the name is truncated to 48 characters, or 32 for
+    /// `Protocol::Rfc3164`.
+    ///
+    /// Defaults to the file name of the current executable.
+    pub fn app_name<T>(mut self, app_name: T) -> SyslogAppenderBuilder
+    where
+        T: Into<String>,
+    {
+        self.app_name = Some(app_name.into());
+        self
+    }
+
+    /// Sets the hostname attached to every message.
+    ///
+    /// Characters other than printable ASCII, including spaces, are replaced
+    /// with `_`, and the hostname is truncated to 255 characters.
+    ///
+    /// Defaults to the hostname of the machine.
+    pub fn hostname<T>(mut self, hostname: T) -> SyslogAppenderBuilder
+    where
+        T: Into<String>,
+    {
+        self.hostname = Some(hostname.into());
+        self
+    }
+
+    /// Sets the encoder used to format the message body.
+    ///
+    /// Defaults to a `PatternEncoder` with the pattern `{m}`.
+    pub fn encoder(mut self, encoder: Box<dyn Encode>) -> SyslogAppenderBuilder {
+        self.encoder = Some(encoder);
+        self
+    }
+
+    /// Consumes the `SyslogAppenderBuilder`, producing a `SyslogAppender`
+    /// which delivers messages over `transport`.
+    ///
+    /// The socket is opened immediately.
+    pub fn build(self, transport: Transport) -> io::Result<SyslogAppender> {
+        // RFC 5424 uses a dash for unknown values; RFC 3164 has no such
+        // convention.
+        let (nil, max_app_name) = match self.protocol {
+            Protocol::Rfc5424 => ("-", 48),
+            Protocol::Rfc3164 => ("localhost", 32),
+        };
+        let hostname = self
+            .hostname
+            .or_else(priv_host::hostname)
+            .map(|hostname| header_field(&hostname, 255))
+            .filter(|hostname| !hostname.is_empty())
+            .unwrap_or_else(|| nil.to_owned());
+        let app_name = self
+            .app_name
+            .or_else(|| {
+                env::current_exe()
+                    .ok()?
+                    .file_name()?
+                    .to_str()
+                    .map(ToOwned::to_owned)
+            })
+            .map(|app_name| header_field(&app_name, max_app_name))
+            .filter(|app_name| !app_name.is_empty())
+            .unwrap_or_else(|| "log4rs".to_owned());
+
+        Ok(SyslogAppender {
+            connection: Mutex::new(Connection::open(&transport)?),
+            transport,
+            protocol: self.protocol,
+            facility: self.facility,
+            app_name,
+            hostname,
+            pid: process::id(),
+            encoder: self
+                .encoder
+                .unwrap_or_else(|| Box::new(PatternEncoder::new("{m}"))),
+        })
+    }
+}
+
+/// Replaces the characters of `value` which may not appear in a header field,
+/// anything but printable ASCII, and truncates it to `max` characters.
+fn header_field(value: &str, max: usize) -> String {
+    value
+        .chars()
+        .take(max)
+        .map(|c| if c.is_ascii_graphic() { c } else { '_' })
+        .collect()
+}
+
+/// A deserializer for the `SyslogAppender`.
+///
+/// # Configuration
+///
+/// ```yaml
+/// kind: syslog
+///
+/// # The message format. One of `rfc5424` or `rfc3164`. Defaults to `rfc5424`.
+/// protocol: rfc5424
+///
+/// # The socket used to reach the syslog daemon. One of `unix`, `udp` or
+/// # `tcp`. Defaults to `unix`.
+/// transport: udp
+///
+/// # The socket path for the `unix` transport, or the `host:port` address for
+/// # the `udp` and `tcp` transports. Defaults to `/dev/log` for `unix` and is
+/// # required otherwise.
+/// address: 127.0.0.1:514
+///
+/// # The facility attached to every message. One of `kern`, `user`, `mail`,
+/// # `daemon`, `auth`, `syslog`, `lpr`, `news`, `uucp`, `cron`, `authpriv`,
+/// # `ftp` or `local0` through `local7`. Defaults to `user`.
+/// facility: daemon
+///
+/// # The application name attached to every message. Defaults to the file
+/// # name of the current executable.
+/// app_name: my_app
+///
+/// # The hostname attached to every message. Defaults to the hostname of the
+/// # machine.
+/// hostname: my_host
+///
+/// # The encoder used to format the message body. Defaults to `kind: pattern`
+/// # with the pattern `{m}`.
+/// encoder:
+///   kind: pattern
+///   pattern: "{t} - {m}"
+/// ```
+#[cfg(feature = "config_parsing")]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct SyslogAppenderDeserializer;
+
+#[cfg(feature = "config_parsing")]
+impl Deserialize for SyslogAppenderDeserializer {
+    type Trait = dyn Append;
+
+    type Config = SyslogAppenderConfig;
+
+    fn deserialize(
+        &self,
+        config: SyslogAppenderConfig,
+        deserializers: &Deserializers,
+    ) -> anyhow::Result<Box<dyn Append>> {
+        let mut appender = SyslogAppender::builder();
+        if let Some(protocol) = config.protocol {
+            let protocol = match protocol {
+                ConfigProtocol::Rfc5424 => Protocol::Rfc5424,
+                ConfigProtocol::Rfc3164 => Protocol::Rfc3164,
+            };
+            appender = appender.protocol(protocol);
+        }
+        if let Some(facility) = config.facility {
+            appender = appender.facility(facility);
+        }
+        if let Some(app_name) = config.app_name {
+            appender = appender.app_name(app_name);
+        }
+        if let Some(hostname) = config.hostname {
+            appender = appender.hostname(hostname);
+        }
+        if let Some(encoder) = config.encoder {
+            appender = appender.encoder(deserializers.deserialize(&encoder.kind, encoder.config)?);
+        }
+
+        let transport = match (config.transport, config.address) {
+            #[cfg(unix)]
+            (None, Some(path)) | (Some(ConfigTransport::Unix), Some(path)) => {
+                Transport::Unix(path.into())
+            }
+            #[cfg(unix)]
+            (None, None) | (Some(ConfigTransport::Unix), None) => Transport::default(),
+            #[cfg(not(unix))]
+            (None, _) | (Some(ConfigTransport::Unix), _) => {
+                anyhow::bail!("the unix transport is not supported on this platform")
+            }
+            (Some(ConfigTransport::Udp), Some(address)) => Transport::Udp(address),
+            (Some(ConfigTransport::Tcp), Some(address)) => Transport::Tcp(address),
+            (Some(_), None) => {
+                anyhow::bail!("an address is required for the udp and tcp transports")
+            }
+        };
+
+        Ok(Box::new(appender.build(transport)?))
+    }
+
+    fn check_components(
+        &self,
+        config: &SyslogAppenderConfig,
+        deserializers: &Deserializers,
+    ) -> anyhow::Result<()> {
+        match &config.encoder {
+            Some(encoder) => deserializers.check_nested::<dyn Encode>(
+                "encoder",
+                &encoder.kind,
+                encoder.config.clone(),
+            ),
+            None => Ok(()),
+        }
+    }
+
+    #[cfg(feature = "schema")]
+    fn schema(&self, generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        <SyslogAppenderConfig as schemars::JsonSchema>::json_schema(generator)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{io::Read, net::TcpListener};
+
+    use super::*;
+
+    fn record_at(level: Level, f: impl FnOnce(&Record<'_>)) {
+        f(&Record::builder()
+            .level(level)
+            .target("target")
+            .args(format_args!("hello world"))
+            .build())
+    }
+
+    fn udp_pair(protocol: Protocol) -> (SyslogAppender, UdpSocket) {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let appender = SyslogAppender::builder()
+            .protocol(protocol)
+            .facility(Facility::Local3)
+            .app_name("app")
+            .hostname("host")
+            .build(Transport::Udp(server.local_addr().unwrap().to_string()))
+            .unwrap();
+        (appender, server)
+    }
+
+    #[test]
+    fn severities() {
+        assert_eq!(severity(Level::Error), 3);
+        assert_eq!(severity(Level::Warn), 4);
+        assert_eq!(severity(Level::Info), 6);
+        assert_eq!(severity(Level::Debug), 7);
+        assert_eq!(severity(Level::Trace), 7);
+    }
+
+    #[test]
+    fn rfc5424_format() {
+        let (appender, _server) = udp_pair(Protocol::Rfc5424);
+        let time = DateTime::parse_from_rfc3339("2016-03-20T14:22:20.644420340-08:00")
+            .unwrap()
+            .with_timezone(&Local);
+
+        record_at(Level::Warn, |record| {
+            let message = appender.format(time, record).unwrap();
+            assert_eq!(
+                String::from_utf8(message).unwrap(),
+                format!(
+                    "<156>1 {}.644420{} host app {} - - hello world",
+                    time.format("%Y-%m-%dT%H:%M:%S"),
+                    time.format("%:z"),
+                    process::id()
+                )
+            );
+        });
+    }
+
+    #[test]
+    fn header_fields() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let build = |protocol, hostname: &str, app_name: &str| {
+            SyslogAppender::builder()
+                .protocol(protocol)
+                .hostname(hostname)
+                .app_name(app_name)
+                .build(Transport::Udp(server.local_addr().unwrap().to_string()))
+                .unwrap()
+        };
+
+        let appender = build(Protocol::Rfc5424, "my host\u{e9}", &"a".repeat(60));
+        assert_eq!(appender.hostname, "my_host_");
+        assert_eq!(appender.app_name, "a".repeat(48));
+
+        let appender = build(Protocol::Rfc3164, &"h".repeat(300), "my app");
+        assert_eq!(appender.hostname, "h".repeat(255));
+        assert_eq!(appender.app_name, "my_app");
+
+        let appender = build(Protocol::Rfc5424, "", "");
+        assert_eq!(appender.hostname, "-");
+        assert_eq!(appender.app_name, "log4rs");
+    }
+
+    #[test]
+    fn rfc3164_format() {
+        let (appender, _server) = udp_pair(Protocol::Rfc3164);
+        let time = DateTime::parse_from_rfc3339("2016-03-05T14:22:20-08:00")
+            .unwrap()
+            .with_timezone(&Local);
+
+        record_at(Level::Error, |record| {
+            let message = appender.format(time, record).unwrap();
+            assert_eq!(
+                String::from_utf8(message).unwrap(),
+                format!(
+                    "<155>{} host app[{}]: hello world",
+                    time.format("%b %e %H:%M:%S"),
+                    process::id()
+                )
+            );
+        });
+    }
+
+    #[test]
+    fn trailing_newline_is_removed() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let appender = SyslogAppender::builder()
+            .encoder(Box::new(PatternEncoder::new("{m}{n}")))
+            .build(Transport::Udp(server.local_addr().unwrap().to_string()))
+            .unwrap();
+
+        record_at(Level::Info, |record| {
+            let message = appender.format(Local::now(), record).unwrap();
+            assert!(message.ends_with(b" hello world"));
+        });
+    }
+
+    #[test]
+    fn udp() {
+        let (appender, server) = udp_pair(Protocol::Rfc5424);
+        record_at(Level::Info, |record| appender.append(record).unwrap());
+
+        let mut buf = [0; 1024];
+        let len = server.recv(&mut buf).unwrap();
+        let message = std::str::from_utf8(&buf[..len]).unwrap();
+        assert!(message.starts_with("<158>1 "), "{}", message);
+        assert!(
+            message.ends_with(&format!(" host app {} - - hello world", process::id())),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn tcp_octet_counting() {
+        let server = TcpListener::bind("127.0.0.1:0").unwrap();
+        let appender = SyslogAppender::builder()
+            .hostname("host")
+            .app_name("app")
+            .build(Transport::Tcp(server.local_addr().unwrap().to_string()))
+            .unwrap();
+        let (mut stream, _) = server.accept().unwrap();
+
+        record_at(Level::Info, |record| appender.append(record).unwrap());
+        record_at(Level::Debug, |record| appender.append(record).unwrap());
+        drop(appender);
+
+        let mut received = String::new();
+        stream.read_to_string(&mut received).unwrap();
+
+        let mut rest = received.as_str();
+        let mut messages = vec![];
+        while !rest.is_empty() {
+            let (len, tail) = rest.split_once(' ').unwrap();
+            let len = len.parse::<usize>().unwrap();
+            messages.push(&tail[..len]);
+            rest = &tail[len..];
+        }
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].starts_with("<14>1 "));
+        assert!(messages[1].starts_with("<15>1 "));
+        assert!(messages.iter().all(|m| m.ends_with("hello world")));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn unix() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log.sock");
+        let server = UnixDatagram::bind(&path).unwrap();
+        let appender = SyslogAppender::builder()
+            .protocol(Protocol::Rfc3164)
+            .facility(Facility::Daemon)
+            .build(Transport::Unix(path))
+            .unwrap();
+
+        record_at(Level::Info, |record| appender.append(record).unwrap());
+
+        let mut buf = [0; 1024];
+        let len = server.recv(&mut buf).unwrap();
+        let message = std::str::from_utf8(&buf[..len]).unwrap();
+        assert!(message.starts_with("<30>"), "{}", message);
+        assert!(message.ends_with("]: hello world"), "{}", message);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn unix_reconnects() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log.sock");
+        let server = UnixDatagram::bind(&path).unwrap();
+        let appender = SyslogAppender::builder()
+            .build(Transport::Unix(path.clone()))
+            .unwrap();
+
+        // the daemon goes away, then recreates its socket
+        drop(server);
+        std::fs::remove_file(&path).unwrap();
+        record_at(Level::Info, |record| {
+            assert!(appender.append(record).is_err())
+        });
+        let server = UnixDatagram::bind(&path).unwrap();
+        record_at(Level::Info, |record| appender.append(record).unwrap());
+
+        let mut buf = [0; 1024];
+        let len = server.recv(&mut buf).unwrap();
+        let message = std::str::from_utf8(&buf[..len]).unwrap();
+        assert!(message.ends_with("hello world"), "{}", message);
+    }
+
+    #[test]
+    fn udp_ipv6() {
+        let server = match UdpSocket::bind("[::1]:0") {
+            Ok(server) => server,
+            // IPv6 is not available
+            Err(_) => return,
+        };
+        let appender = SyslogAppender::builder()
+            .build(Transport::Udp(server.local_addr().unwrap().to_string()))
+            .unwrap();
+        record_at(Level::Info, |record| appender.append(record).unwrap());
+
+        let mut buf = [0; 1024];
+        let len = server.recv(&mut buf).unwrap();
+        let message = std::str::from_utf8(&buf[..len]).unwrap();
+        assert!(message.ends_with("hello world"), "{}", message);
+    }
+
+    #[test]
+    #[cfg(feature = "yaml_format")]
+    fn deserialize() {
+        use crate::config::RawConfig;
+
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let config = format!(
+            "
+appenders:
+    foo:
+        kind: syslog
+        protocol: rfc3164
+        transport: udp
+        address: {}
+        facility: local7
+        app_name: my_app
+",
+            server.local_addr().unwrap()
+        );
+
+        let config = ::serde_yaml::from_str::<RawConfig>(&config).unwrap();
+        let errors = config.appenders_lossy(&Deserializers::new()).1;
+        assert!(errors.is_empty());
+    }
+}
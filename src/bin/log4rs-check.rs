@@ -0,0 +1,68 @@
+//! Checks log4rs config files, reporting every problem found in them.
+//!
+//! ```text
+//! log4rs-check [--build] [--print] <FILE>...
+//! ```
+//!
+//! Several files are layered on top of each other in the order given, as
+//! `log4rs::init_files` does. With `--build`, every component is built to
+//! check it, which may create the files appenders log to. With `--print`, the
+//! resolved configuration, after merging included files and replacing
+//! environment variables, is written to stdout as YAML.
+//!
+//! The exit status is 0 if the configuration is valid, 1 if problems were
+//! found and 2 if the arguments are invalid.
+
+use std::{env, process::ExitCode};
+
+use log4rs::config::{
+    resolve_config_files, validate_config_files_with_mode, Deserializers, ValidationMode,
+};
+
+const USAGE: &str = "usage: log4rs-check [--build] [--print] <FILE>...";
+
+fn main() -> ExitCode {
+    let mut mode = ValidationMode::Parse;
+    let mut print = false;
+    let mut paths = vec![];
+    for arg in env::args().skip(1) {
+        match &*arg {
+            "--build" => mode = ValidationMode::Build,
+            "--print" => print = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return ExitCode::SUCCESS;
+            }
+            _ if arg.starts_with('-') => {
+                eprintln!("unknown option `{}`\n{}", arg, USAGE);
+                return ExitCode::from(2);
+            }
+            _ => paths.push(arg),
+        }
+    }
+    if paths.is_empty() {
+        eprintln!("{}", USAGE);
+        return ExitCode::from(2);
+    }
+
+    let diagnostics = validate_config_files_with_mode(&paths, &Deserializers::default(), mode);
+    for diagnostic in &diagnostics {
+        eprintln!("{}", diagnostic);
+    }
+
+    if print {
+        match resolve_config_files(&paths).and_then(|config| Ok(serde_yaml::to_string(&config)?)) {
+            Ok(config) => print!("{}", config),
+            Err(e) => {
+                eprintln!("{:#}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    if diagnostics.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
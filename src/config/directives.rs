@@ -0,0 +1,269 @@
+//! Logging directives in the syntax of env_logger's `RUST_LOG`.
+
+use log::LevelFilter;
+use std::{env, fmt, str::FromStr};
+use thiserror::Error;
+
+use crate::config::{runtime, Config, Logger, Root};
+
+/// A set of levels in the syntax of env_logger's `RUST_LOG`, such as
+/// `info,my_crate::db=trace`, to be layered on top of a `Config`.
+///
+/// The directives are separated by commas. A bare level sets the level of the
+/// root logger, `name=level` sets the level of the logger `name`, and a bare
+/// name sets the level of that logger to `trace`. When a directive is given
+/// more than once, the last one wins.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Directives {
+    root: Option<LevelFilter>,
+    loggers: Vec<(String, LevelFilter)>,
+}
+
+impl Directives {
+    /// Parses directives from a string.
+    pub fn parse(directives: &str) -> Result<Directives, DirectivesError> {
+        directives.parse()
+    }
+
+    /// Parses directives from the environment variable `name`, such as
+    /// `RUST_LOG`.
+    ///
+    /// No directives are returned if the variable is not set.
+    pub fn from_env(name: &str) -> Result<Directives, DirectivesError> {
+        match env::var_os(name) {
+            Some(directives) => directives.to_string_lossy().parse(),
+            None => Ok(Directives::default()),
+        }
+    }
+
+    /// Returns `true` if there are no directives.
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none() && self.loggers.is_empty()
+    }
+
+    /// Returns the level set for the root logger, if any.
+    pub fn root_level(&self) -> Option<LevelFilter> {
+        self.root
+    }
+
+    /// Returns the level set for each logger, in the order given.
+    pub fn logger_levels(&self) -> impl Iterator<Item = (&str, LevelFilter)> {
+        self.loggers
+            .iter()
+            .map(|(name, level)| (name.as_str(), *level))
+    }
+
+    /// Applies the directives to `config`.
+    ///
+    /// Loggers which the config does not contain are created without
+    /// appenders of their own and are additive, so their records go to the
+    /// appenders of their nearest configured ancestor, normally the root.
+    pub fn apply(&self, config: &mut Config) {
+        self.apply_root(config.root_mut());
+        self.apply_loggers(config.loggers_mut());
+    }
+
+    pub(crate) fn apply_root(&self, root: &mut Root) {
+        if let Some(level) = self.root {
+            root.set_level(level);
+        }
+    }
+
+    pub(crate) fn apply_loggers(&self, loggers: &mut Vec<Logger>) {
+        for (name, level) in &self.loggers {
+            match loggers.iter_mut().find(|logger| logger.name() == name) {
+                Some(logger) => logger.set_level(*level),
+                None => loggers.push(Logger::builder().build(name.clone(), *level)),
+            }
+        }
+    }
+}
+
+impl FromStr for Directives {
+    type Err = DirectivesError;
+
+    fn from_str(s: &str) -> Result<Directives, DirectivesError> {
+        let error = |directive: &str, reason: &str| DirectivesError {
+            directive: directive.to_owned(),
+            reason: reason.to_owned(),
+        };
+
+        if let Some((_, filter)) = s.split_once('/') {
+            return Err(error(filter, "message filters are not supported"));
+        }
+
+        let mut directives = Directives::default();
+        for directive in s.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            let (name, level) = match directive.split_once('=') {
+                Some((name, level)) => {
+                    let level = level
+                        .trim()
+                        .parse()
+                        .map_err(|_| error(directive, "unknown level"))?;
+                    (Some(name.trim()), level)
+                }
+                None => match directive.parse() {
+                    Ok(level) => (None, level),
+                    Err(_) => (Some(directive), LevelFilter::Trace),
+                },
+            };
+
+            match name {
+                None => directives.root = Some(level),
+                Some(name) => {
+                    runtime::check_logger_name(name)
+                        .map_err(|_| error(directive, "invalid logger name"))?;
+                    directives.loggers.retain(|(n, _)| n != name);
+                    directives.loggers.push((name.to_owned(), level));
+                }
+            }
+        }
+
+        Ok(directives)
+    }
+}
+
+impl fmt::Display for Directives {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let root = self.root.iter().map(|level| level.as_str().to_lowercase());
+        let loggers = self
+            .loggers
+            .iter()
+            .map(|(name, level)| format!("{}={}", name, level.as_str().to_lowercase()));
+        fmt.write_str(&root.chain(loggers).collect::<Vec<_>>().join(","))
+    }
+}
+
+#[cfg(feature = "config_parsing")]
+impl serde::Serialize for Directives {
+    fn serialize<S>(&self, s: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        s.collect_str(self)
+    }
+}
+
+#[cfg(feature = "config_parsing")]
+impl<'de> serde::Deserialize<'de> for Directives {
+    fn deserialize<D>(d: D) -> Result<Directives, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(serde::Deserialize)]
+        #[serde(untagged)]
+        enum Source {
+            Directives(String),
+            Env { env: String },
+        }
+
+        match Source::deserialize(d)? {
+            Source::Directives(directives) => directives.parse(),
+            Source::Env { env } => Directives::from_env(&env),
+        }
+        .map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(feature = "schema")]
+impl schemars::JsonSchema for Directives {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "Directives".into()
+    }
+
+    fn json_schema(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        schemars::json_schema!({
+            "anyOf": [
+                { "type": "string" },
+                {
+                    "type": "object",
+                    "properties": { "env": { "type": "string" } },
+                    "required": ["env"],
+                    "additionalProperties": false
+                }
+            ]
+        })
+    }
+}
+
+/// An error parsing logging directives.
+#[derive(Debug, Error)]
+#[error("invalid logging directive `{directive}`: {reason}")]
+pub struct DirectivesError {
+    directive: String,
+    reason: String,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let directives =
+            Directives::parse("info, my_crate::db=TRACE,other,my_crate::db=warn,").unwrap();
+        assert_eq!(directives.root_level(), Some(LevelFilter::Info));
+        assert_eq!(
+            directives.logger_levels().collect::<Vec<_>>(),
+            [
+                ("other", LevelFilter::Trace),
+                ("my_crate::db", LevelFilter::Warn)
+            ]
+        );
+
+        assert_eq!(directives.to_string(), "info,other=trace,my_crate::db=warn");
+        assert!(Directives::parse("").unwrap().is_empty());
+        assert_eq!(
+            Directives::parse("off").unwrap().root_level(),
+            Some(LevelFilter::Off)
+        );
+
+        for (s, expected) in [
+            (
+                "a=loud",
+                "invalid logging directive `a=loud`: unknown level",
+            ),
+            (
+                "a:b=info",
+                "invalid logging directive `a:b=info`: invalid logger name",
+            ),
+            (
+                "=info",
+                "invalid logging directive `=info`: invalid logger name",
+            ),
+            (
+                "info/foo",
+                "invalid logging directive `foo`: message filters are not supported",
+            ),
+        ] {
+            assert_eq!(Directives::parse(s).unwrap_err().to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn apply() {
+        let mut config = Config::builder()
+            .logger(
+                Logger::builder()
+                    .additive(false)
+                    .build("my_crate", LevelFilter::Info),
+            )
+            .build(Root::builder().build(LevelFilter::Warn))
+            .unwrap();
+
+        Directives::parse("debug,my_crate=error,my_crate::db=trace")
+            .unwrap()
+            .apply(&mut config);
+
+        assert_eq!(config.root().level(), LevelFilter::Debug);
+        let loggers = config.loggers();
+        assert_eq!(loggers.len(), 2);
+        assert_eq!(loggers[0].name(), "my_crate");
+        assert_eq!(loggers[0].level(), LevelFilter::Error);
+        assert!(!loggers[0].additive());
+        assert_eq!(loggers[1].name(), "my_crate::db");
+        assert_eq!(loggers[1].level(), LevelFilter::Trace);
+        assert!(loggers[1].appenders().is_empty());
+        assert!(loggers[1].additive());
+    }
+}
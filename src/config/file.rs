@@ -1,14 +1,24 @@
 use std::{
-    fs,
+    collections::{BTreeMap, HashSet},
+    fmt, fs,
     path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
     thread,
     time::{Duration, SystemTime},
 };
 
+use anyhow::{anyhow, Context};
+use derive_more::Debug;
+use serde_value::Value;
 use thiserror::Error;
 
-use super::{init_config, Config, Deserializers, Handle, RawConfig};
-use crate::handle_error;
+use super::{
+    init_config,
+    interpolate::{deserialize_into, interpolate, Location},
+    raw::Includes,
+    AppenderCache, Config, Deserializers, RawConfig,
+};
+use crate::{handle_error, Handle, Reload};
 
 /// Initializes the global logger as a log4rs logger configured via a file.
 ///
@@ -18,37 +28,405 @@ use crate::handle_error;
 /// Any nonfatal errors encountered when processing the configuration are
 /// reported to stderr.
 ///
+/// If the configuration sets a refresh rate, the file is checked for changes
+/// at that rate, or with the `config_watch` feature, watched for changes.
+///
 /// Requires the `file` feature (enabled by default).
 pub fn init_file<P>(path: P, deserializers: Deserializers) -> anyhow::Result<()>
 where
     P: AsRef<Path>,
 {
-    let path = path.as_ref().to_path_buf();
-    let format = Format::from_path(&path)?;
-    let source = read_config(&path)?;
-    // An Err here could come because mtime isn't available, so don't bail
-    let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
-    let config = format.parse(&source)?;
-
-    let refresh_rate = config.refresh_rate();
-    let config = deserialize(&config, &deserializers);
-
-    match init_config(config) {
-        Ok(handle) => {
-            if let Some(refresh_rate) = refresh_rate {
-                ConfigReloader::start(
-                    path,
-                    format,
-                    refresh_rate,
-                    source,
-                    modified,
-                    deserializers,
-                    handle,
-                );
+    init_files(&[path], deserializers)
+}
+
+/// Initializes the global logger as a log4rs logger configured via a file,
+/// with callbacks to be notified of reloads.
+///
+/// This behaves like `init_file`, but returns a `Handle` whose `reload_now`
+/// method reloads the file on demand.
+///
+/// The validator of `hooks`, if any, is also applied to the initial
+/// configuration, and its error is returned if it rejects it.
+pub fn init_file_with_hooks<P>(
+    path: P,
+    deserializers: Deserializers,
+    hooks: ReloadHooks,
+) -> anyhow::Result<Handle>
+where
+    P: AsRef<Path>,
+{
+    init_files_with_hooks(&[path], deserializers, hooks)
+}
+
+/// Initializes the global logger as a log4rs logger configured via several
+/// files layered on top of each other.
+///
+/// The files are merged in order, along with the files they include, so that
+/// later files override earlier ones. Every file involved is checked for
+/// changes if the merged configuration sets a refresh rate.
+pub fn init_files<P>(paths: &[P], deserializers: Deserializers) -> anyhow::Result<()>
+where
+    P: AsRef<Path>,
+{
+    init_files_with_hooks(paths, deserializers, ReloadHooks::new()).map(|_| ())
+}
+
+/// Initializes the global logger as a log4rs logger configured via several
+/// files layered on top of each other, with callbacks to be notified of
+/// reloads.
+///
+/// See `init_files` and `init_file_with_hooks`.
+pub fn init_files_with_hooks<P>(
+    paths: &[P],
+    deserializers: Deserializers,
+    hooks: ReloadHooks,
+) -> anyhow::Result<Handle>
+where
+    P: AsRef<Path>,
+{
+    let paths = paths
+        .iter()
+        .map(|path| path.as_ref().to_path_buf())
+        .collect::<Vec<_>>();
+    init_origin(Origin::Files(paths), deserializers, hooks)
+}
+
+/// Initializes the global logger as a log4rs logger configured via a string
+/// in the given format, such as one embedded with `include_str!` or read from
+/// an environment variable.
+///
+/// Files included by the configuration are resolved relative to the current
+/// directory, and are checked for changes if it sets a refresh rate.
+///
+/// ```no_run
+/// use log4rs::config::{Deserializers, Format};
+///
+/// let config = "
+/// appenders:
+///   stdout:
+///     kind: console
+/// root:
+///   level: info
+///   appenders: [stdout]
+/// ";
+/// log4rs::config::init_config_str(config, Format::Yaml, Deserializers::default()).unwrap();
+/// ```
+pub fn init_config_str(
+    source: &str,
+    format: Format,
+    deserializers: Deserializers,
+) -> anyhow::Result<Handle> {
+    let source = StrSource {
+        source: source.to_owned(),
+        format,
+    };
+    init_config_source(source, deserializers, ReloadHooks::new())
+}
+
+/// Initializes the global logger as a log4rs logger configured via a
+/// `ConfigSource`, with callbacks to be notified of reloads.
+///
+/// If the configuration sets a refresh rate, the source is read again at that
+/// rate and the configuration is reloaded if it has changed. `Handle`'s
+/// `reload_now` method reads it again on demand.
+pub fn init_config_source<S>(
+    source: S,
+    deserializers: Deserializers,
+    hooks: ReloadHooks,
+) -> anyhow::Result<Handle>
+where
+    S: ConfigSource,
+{
+    init_origin(Origin::source(Box::new(source))?, deserializers, hooks)
+}
+
+fn init_origin(
+    origin: Origin,
+    deserializers: Deserializers,
+    hooks: ReloadHooks,
+) -> anyhow::Result<Handle> {
+    let (raw, files) = origin.load()?;
+
+    #[cfg(feature = "ring_buffer_appender")]
+    let registrations = crate::append::ring_buffer::Registrations::begin();
+    let mut appenders = AppenderCache::default();
+    let config = deserialize(&raw, &deserializers, &mut appenders);
+    if let Some(validator) = &hooks.validator {
+        validator(&config).context("the configuration was rejected")?;
+    }
+
+    let mut handle = init_config(config)?;
+    #[cfg(feature = "ring_buffer_appender")]
+    registrations.commit();
+    let reloader = Arc::new(Mutex::new(ConfigReloader {
+        origin,
+        files,
+        refresh_rate: raw.refresh_rate(),
+        raw,
+        deserializers,
+        appenders,
+        hooks,
+        handle: handle.clone(),
+        running: false,
+    }));
+    ConfigReloader::start(&reloader);
+    handle.set_reloader(Arc::new(FileReloader(reloader)));
+
+    Ok(handle)
+}
+
+/// A source of configuration other than files, such as a remote config
+/// service.
+///
+/// Files included by the configuration are resolved relative to the current
+/// directory.
+///
+/// ```no_run
+/// use log4rs::config::{ConfigSource, Deserializers, ReloadHooks};
+///
+/// struct EnvSource;
+///
+/// impl ConfigSource for EnvSource {
+///     fn read(&mut self) -> anyhow::Result<String> {
+///         Ok(std::env::var("LOG4RS_CONFIG")?)
+///     }
+/// }
+///
+/// log4rs::config::init_config_source(EnvSource, Deserializers::default(), ReloadHooks::new())
+///     .unwrap();
+/// ```
+pub trait ConfigSource: Send + 'static {
+    /// Reads the current configuration.
+    fn read(&mut self) -> anyhow::Result<String>;
+
+    /// Returns the format of the configuration.
+    ///
+    /// The default detects the format from the configuration itself, as
+    /// `Format::detect` does.
+    fn format(&self) -> Option<Format> {
+        None
+    }
+}
+
+#[derive(Debug)]
+struct StrSource {
+    source: String,
+    format: Format,
+}
+
+impl ConfigSource for StrSource {
+    fn read(&mut self) -> anyhow::Result<String> {
+        Ok(self.source.clone())
+    }
+
+    fn format(&self) -> Option<Format> {
+        Some(self.format)
+    }
+}
+
+/// Where a config is loaded from.
+#[derive(Debug)]
+enum Origin {
+    // the files given to `init_files`
+    Files(Vec<PathBuf>),
+    // a source, along with its contents as last read
+    Source(#[debug(skip)] Box<dyn ConfigSource>, String),
+}
+
+impl Origin {
+    fn source(mut source: Box<dyn ConfigSource>) -> anyhow::Result<Origin> {
+        let contents = source.read().context("error reading config source")?;
+        Ok(Origin::Source(source, contents))
+    }
+
+    fn load(&self) -> anyhow::Result<(RawConfig, Vec<SourceFile>)> {
+        match self {
+            Origin::Files(paths) => load(paths),
+            Origin::Source(source, contents) => load_str(contents, source.format()),
+        }
+    }
+}
+
+type SuccessFn = dyn Fn(&ReloadSummary) + Send + Sync;
+type FailureFn = dyn Fn(&anyhow::Error) + Send + Sync;
+type ValidatorFn = dyn Fn(&Config) -> anyhow::Result<()> + Send + Sync;
+
+/// Callbacks invoked when a configuration file is reloaded.
+///
+/// The callbacks run on the thread performing the reload, and must not call
+/// `Handle::reload_now` themselves.
+///
+/// ```no_run
+/// use log4rs::config::{Deserializers, ReloadHooks};
+///
+/// let hooks = ReloadHooks::new()
+///     .on_success(|summary| eprintln!("logging config reloaded: {}", summary))
+///     .on_failure(|e| eprintln!("logging config not reloaded: {:#}", e))
+///     .validator(|config| {
+///         if config.root().appenders().is_empty() {
+///             anyhow::bail!("the root logger has no appenders");
+///         }
+///         Ok(())
+///     });
+/// let handle = log4rs::config::init_file_with_hooks("log4rs.yml", Deserializers::default(), hooks)
+///     .unwrap();
+/// ```
+#[derive(Debug, Default)]
+pub struct ReloadHooks {
+    #[debug(skip)]
+    on_success: Option<Box<SuccessFn>>,
+    #[debug(skip)]
+    on_failure: Option<Box<FailureFn>>,
+    #[debug(skip)]
+    validator: Option<Box<ValidatorFn>>,
+}
+
+impl ReloadHooks {
+    /// Creates a new `ReloadHooks` with no callbacks.
+    pub fn new() -> ReloadHooks {
+        ReloadHooks::default()
+    }
+
+    /// Sets a callback to be invoked with a summary of the changes after a
+    /// new configuration has been applied.
+    pub fn on_success<F>(mut self, f: F) -> ReloadHooks
+    where
+        F: Fn(&ReloadSummary) + Send + Sync + 'static,
+    {
+        self.on_success = Some(Box::new(f));
+        self
+    }
+
+    /// Sets a callback to be invoked when the configuration file cannot be
+    /// read, parsed, built or validated. The current configuration remains in
+    /// place.
+    ///
+    /// A reloaded configuration is not applied if any of its appenders,
+    /// filters or loggers is incorrect, unlike the initial one, from which
+    /// those are stripped.
+    ///
+    /// Such errors are reported to stderr if no callback is set.
+    pub fn on_failure<F>(mut self, f: F) -> ReloadHooks
+    where
+        F: Fn(&anyhow::Error) + Send + Sync + 'static,
+    {
+        self.on_failure = Some(Box::new(f));
+        self
+    }
+
+    /// Sets a callback to check a new configuration before it is applied.
+    ///
+    /// If the callback returns an error, the configuration is discarded and
+    /// the reload fails with that error.
+    ///
+    /// The callback is given the built configuration, so its new or changed
+    /// appenders have already been created by then: files have been opened,
+    /// and truncated unless appending, and sockets connected. Those are closed
+    /// again if the configuration is discarded, ring buffers it named are
+    /// unregistered, restoring the handles they replaced, and the unchanged
+    /// appenders it shares with the current configuration forward records
+    /// to those of the current configuration again.
+    pub fn validator<F>(mut self, f: F) -> ReloadHooks
+    where
+        F: Fn(&Config) -> anyhow::Result<()> + Send + Sync + 'static,
+    {
+        self.validator = Some(Box::new(f));
+        self
+    }
+}
+
+/// A summary of the changes made to the configuration by a reload.
+///
+/// Appenders and loggers are identified by name, in sorted order. The root
+/// and loggers are compared with the directives of the configuration applied,
+/// so loggers added by directives are reported as added.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReloadSummary {
+    pub(super) appenders_added: Vec<String>,
+    pub(super) appenders_removed: Vec<String>,
+    pub(super) appenders_changed: Vec<String>,
+    pub(super) loggers_added: Vec<String>,
+    pub(super) loggers_removed: Vec<String>,
+    pub(super) loggers_changed: Vec<String>,
+    pub(super) root_changed: bool,
+    pub(super) refresh_rate_changed: bool,
+}
+
+impl ReloadSummary {
+    /// Returns the appenders which were not part of the previous
+    /// configuration.
+    pub fn appenders_added(&self) -> &[String] {
+        &self.appenders_added
+    }
+
+    /// Returns the appenders which are no longer part of the configuration.
+    pub fn appenders_removed(&self) -> &[String] {
+        &self.appenders_removed
+    }
+
+    /// Returns the appenders whose configuration changed.
+    pub fn appenders_changed(&self) -> &[String] {
+        &self.appenders_changed
+    }
+
+    /// Returns the loggers which were not part of the previous configuration.
+    pub fn loggers_added(&self) -> &[String] {
+        &self.loggers_added
+    }
+
+    /// Returns the loggers which are no longer part of the configuration.
+    pub fn loggers_removed(&self) -> &[String] {
+        &self.loggers_removed
+    }
+
+    /// Returns the loggers whose configuration changed.
+    pub fn loggers_changed(&self) -> &[String] {
+        &self.loggers_changed
+    }
+
+    /// Returns `true` if the root logger's configuration changed.
+    pub fn root_changed(&self) -> bool {
+        self.root_changed
+    }
+
+    /// Returns `true` if the refresh rate changed.
+    pub fn refresh_rate_changed(&self) -> bool {
+        self.refresh_rate_changed
+    }
+
+    /// Returns `true` if nothing changed.
+    pub fn is_empty(&self) -> bool {
+        *self == ReloadSummary::default()
+    }
+}
+
+impl fmt::Display for ReloadSummary {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return fmt.write_str("no changes");
+        }
+
+        let mut parts = vec![];
+        for (what, names) in [
+            ("appenders added", &self.appenders_added),
+            ("appenders removed", &self.appenders_removed),
+            ("appenders changed", &self.appenders_changed),
+            ("loggers added", &self.loggers_added),
+            ("loggers removed", &self.loggers_removed),
+            ("loggers changed", &self.loggers_changed),
+        ] {
+            if !names.is_empty() {
+                parts.push(format!("{}: {}", what, names.join(", ")));
             }
-            Ok(())
         }
-        Err(e) => Err(e.into()),
+        if self.root_changed {
+            parts.push("root changed".to_owned());
+        }
+        if self.refresh_rate_changed {
+            parts.push("refresh rate changed".to_owned());
+        }
+
+        fmt.write_str(&parts.join("; "))
     }
 }
 
@@ -60,12 +438,59 @@ pub fn load_config_file<P>(path: P, deserializers: Deserializers) -> anyhow::Res
 where
     P: AsRef<Path>,
 {
-    let path = path.as_ref();
-    let format = Format::from_path(path)?;
-    let source = read_config(path)?;
-    let config = format.parse(&source)?;
+    load_config_files(&[path], deserializers)
+}
+
+/// Loads a log4rs logger configuration from several files layered on top of
+/// each other, as `init_files` does.
+pub fn load_config_files<P>(paths: &[P], deserializers: Deserializers) -> anyhow::Result<Config>
+where
+    P: AsRef<Path>,
+{
+    let paths = paths
+        .iter()
+        .map(|path| path.as_ref().to_path_buf())
+        .collect::<Vec<_>>();
+    let (config, _) = load(&paths)?;
 
-    Ok(deserialize(&config, &deserializers))
+    Ok(deserialize(
+        &config,
+        &deserializers,
+        &mut AppenderCache::default(),
+    ))
+}
+
+/// Loads a log4rs logger configuration from a string in the given format.
+///
+/// Files included by the configuration are resolved relative to the current
+/// directory.
+pub fn load_config_str(
+    source: &str,
+    format: Format,
+    deserializers: &Deserializers,
+) -> anyhow::Result<Config> {
+    let (config, _) = load_str(source, Some(format))?;
+
+    Ok(deserialize(
+        &config,
+        deserializers,
+        &mut AppenderCache::default(),
+    ))
+}
+
+/// Reads a config from several files layered on top of each other, as
+/// `init_files` does, returning it after merging the files and replacing
+/// environment variables, but before deserializing it.
+pub fn resolve_config_files<P>(paths: &[P]) -> anyhow::Result<Value>
+where
+    P: AsRef<Path>,
+{
+    let paths = paths
+        .iter()
+        .map(|path| path.as_ref().to_path_buf())
+        .collect::<Vec<_>>();
+    let layers = load_layers(&paths)?;
+    Ok(merge_layers(layers.into_iter().map(|layer| layer.value)))
 }
 
 /// The various types of formatting errors that can be generated.
@@ -92,59 +517,322 @@ pub enum FormatError {
     UnknownFormat,
 }
 
-#[derive(Debug)]
-enum Format {
-    #[cfg(feature = "yaml_format")]
+/// The format of a config.
+///
+/// Each format requires a Cargo feature to be parsed: `yaml_format`,
+/// `json_format` or `toml_format`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Format {
+    /// YAML.
     Yaml,
-    #[cfg(feature = "json_format")]
+    /// JSON.
     Json,
-    #[cfg(feature = "toml_format")]
+    /// TOML.
     Toml,
 }
 
 impl Format {
-    fn from_path(path: &Path) -> anyhow::Result<Format> {
-        match path.extension().and_then(|s| s.to_str()) {
-            #[cfg(feature = "yaml_format")]
+    /// Returns the format of a config file from its extension.
+    ///
+    /// `FormatError::UnknownFormat` is returned if the path has no extension.
+    pub fn from_path<P>(path: P) -> Result<Format, FormatError>
+    where
+        P: AsRef<Path>,
+    {
+        match path.as_ref().extension().and_then(|s| s.to_str()) {
             Some("yaml") | Some("yml") => Ok(Format::Yaml),
-            #[cfg(not(feature = "yaml_format"))]
-            Some("yaml") | Some("yml") => Err(FormatError::YamlFeatureFlagRequired.into()),
-
-            #[cfg(feature = "json_format")]
             Some("json") => Ok(Format::Json),
-            #[cfg(not(feature = "json_format"))]
-            Some("json") => Err(FormatError::JsonFeatureFlagRequired.into()),
-
-            #[cfg(feature = "toml_format")]
             Some("toml") => Ok(Format::Toml),
-            #[cfg(not(feature = "toml_format"))]
-            Some("toml") => Err(FormatError::TomlFeatureFlagRequired.into()),
+            Some(f) => Err(FormatError::UnsupportedFormat(f.to_string())),
+            None => Err(FormatError::UnknownFormat),
+        }
+    }
 
-            Some(f) => Err(FormatError::UnsupportedFormat(f.to_string()).into()),
-            None => Err(FormatError::UnknownFormat.into()),
+    /// Guesses the format of a config from its contents.
+    ///
+    /// A config whose first line other than blank lines and comments opens
+    /// an object is JSON, one which starts with a table header or a
+    /// `key = value` pair is TOML, and any other is YAML.
+    pub fn detect(source: &str) -> Format {
+        let line = source
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty() && !line.starts_with('#'));
+        match line {
+            Some(line) if line.starts_with('{') => Format::Json,
+            Some(line) if line.starts_with('[') => Format::Toml,
+            Some(line) => match line.split_once('=') {
+                Some((key, _))
+                    if !key.trim().is_empty()
+                        && key
+                            .chars()
+                            .all(|c| c.is_ascii_alphanumeric() || "_-.\"' ".contains(c)) =>
+                {
+                    Format::Toml
+                }
+                _ => Format::Yaml,
+            },
+            None => Format::Yaml,
         }
     }
 
     #[allow(unused_variables)]
-    fn parse(&self, source: &str) -> anyhow::Result<RawConfig> {
+    fn parse(&self, source: &str) -> anyhow::Result<Value> {
         match *self {
             #[cfg(feature = "yaml_format")]
             Format::Yaml => ::serde_yaml::from_str(source).map_err(Into::into),
+            #[cfg(not(feature = "yaml_format"))]
+            Format::Yaml => Err(FormatError::YamlFeatureFlagRequired.into()),
+
             #[cfg(feature = "json_format")]
             Format::Json => ::serde_json::from_str(source).map_err(Into::into),
+            #[cfg(not(feature = "json_format"))]
+            Format::Json => Err(FormatError::JsonFeatureFlagRequired.into()),
+
             #[cfg(feature = "toml_format")]
             Format::Toml => ::toml::from_str(source).map_err(Into::into),
+            #[cfg(not(feature = "toml_format"))]
+            Format::Toml => Err(FormatError::TomlFeatureFlagRequired.into()),
         }
     }
 }
 
 fn read_config(path: &Path) -> anyhow::Result<String> {
-    let s = fs::read_to_string(path)?;
-    Ok(s)
+    fs::read_to_string(path).with_context(|| FileContext::new("error reading config file", path))
+}
+
+/// The file, and the key within it if known, which an error loading a config
+/// came from.
+#[derive(Debug)]
+pub(super) struct FileContext {
+    message: String,
+    pub(super) path: PathBuf,
+    pub(super) key: Option<String>,
+}
+
+impl FileContext {
+    fn new(message: &str, path: &Path) -> FileContext {
+        FileContext {
+            message: format!("{} `{}`", message, path.display()),
+            path: path.to_path_buf(),
+            key: None,
+        }
+    }
 }
 
-fn deserialize(config: &RawConfig, deserializers: &Deserializers) -> Config {
-    let (appenders, mut errors) = config.appenders_lossy(deserializers);
+impl fmt::Display for FileContext {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.write_str(&self.message)
+    }
+}
+
+/// A file read while loading a config.
+#[derive(Debug)]
+struct SourceFile {
+    path: PathBuf,
+    source: String,
+    modified: Option<SystemTime>,
+}
+
+/// The contents of a config file, without its includes.
+#[derive(Debug)]
+pub(super) struct Layer {
+    pub(super) path: PathBuf,
+    pub(super) value: Value,
+}
+
+/// Reads the files at `paths` and those they include, merging them in order
+/// into a single config.
+fn load(paths: &[PathBuf]) -> anyhow::Result<(RawConfig, Vec<SourceFile>)> {
+    let mut loaded = HashSet::new();
+    let mut layers = vec![];
+    let mut files = vec![];
+    for path in paths {
+        load_layer(path, &mut vec![], &mut loaded, &mut layers, &mut files)?;
+    }
+
+    let config =
+        deserialize_into::<RawConfig>(merge_layers(layers.into_iter().map(|layer| layer.value)))
+            .map_err(|e| {
+                let names = files
+                    .iter()
+                    .map(|file| format!("`{}`", file.path.display()))
+                    .collect::<Vec<_>>();
+                match &*names {
+                    [name] => anyhow!("error deserializing config file {}: {}", name, e),
+                    names => anyhow!(
+                        "error deserializing config merged from files {}: {}",
+                        names.join(", "),
+                        e
+                    ),
+                }
+            })?;
+    Ok((config, files))
+}
+
+/// Parses a config from a string, detecting its format if `None`, and
+/// merges the files it includes underneath it.
+fn load_str(source: &str, format: Option<Format>) -> anyhow::Result<(RawConfig, Vec<SourceFile>)> {
+    let format = format.unwrap_or_else(|| Format::detect(source));
+    let mut value = format.parse(source).context("error parsing config")?;
+    interpolate(&mut value).context("error interpolating config")?;
+
+    let includes = take_includes(&mut value).context("invalid `include` in config")?;
+    let mut loaded = HashSet::new();
+    let mut layers = vec![];
+    let mut files = vec![];
+    for include in includes.paths() {
+        load_layer(include, &mut vec![], &mut loaded, &mut layers, &mut files)?;
+    }
+
+    let config = deserialize_into::<RawConfig>(merge_layers(
+        layers.into_iter().map(|layer| layer.value).chain([value]),
+    ))
+    .map_err(|e| anyhow!("error deserializing config: {}", e))?;
+    Ok((config, files))
+}
+
+/// Reads the files at `paths` and those they include, returning their
+/// contents in the order they are merged.
+pub(super) fn load_layers(paths: &[PathBuf]) -> anyhow::Result<Vec<Layer>> {
+    let mut loaded = HashSet::new();
+    let mut layers = vec![];
+    for path in paths {
+        load_layer(path, &mut vec![], &mut loaded, &mut layers, &mut vec![])?;
+    }
+    Ok(layers)
+}
+
+/// Merges the contents of config files, the later overriding the earlier.
+pub(super) fn merge_layers<I>(values: I) -> Value
+where
+    I: IntoIterator<Item = Value>,
+{
+    let mut merged = Value::Map(BTreeMap::new());
+    for value in values {
+        merge(&mut merged, value);
+    }
+    merged
+}
+
+/// Loads the file at `path` after the files it includes, skipping any file
+/// which has already been loaded so that one included by several others is
+/// only merged once, underneath all of them.
+fn load_layer(
+    path: &Path,
+    including: &mut Vec<PathBuf>,
+    loaded: &mut HashSet<PathBuf>,
+    layers: &mut Vec<Layer>,
+    files: &mut Vec<SourceFile>,
+) -> anyhow::Result<()> {
+    let id = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    if including.contains(&id) {
+        return Err(anyhow!(FileContext {
+            message: format!("config file `{}` includes itself", path.display()),
+            path: path.to_path_buf(),
+            key: Some("include".to_owned()),
+        }));
+    }
+    if !loaded.insert(id.clone()) {
+        return Ok(());
+    }
+
+    let format = match Format::from_path(path) {
+        Ok(format) => Some(format),
+        Err(FormatError::UnknownFormat) => None,
+        Err(e) => {
+            return Err(anyhow!(e).context(FileContext::new("error loading config file", path)))
+        }
+    };
+    // An Err here could come because mtime isn't available, so don't bail
+    let modified = fs::metadata(path).and_then(|m| m.modified()).ok();
+    let source = read_config(path)?;
+    let mut value = format
+        .unwrap_or_else(|| Format::detect(&source))
+        .parse(&source)
+        .with_context(|| FileContext::new("error parsing config file", path))?;
+    if let Err(e) = interpolate(&mut value) {
+        let key = e.downcast_ref::<Location>().map(|l| l.0.clone());
+        return Err(e.context(FileContext {
+            key,
+            ..FileContext::new("error interpolating config file", path)
+        }));
+    }
+    files.push(SourceFile {
+        path: path.to_path_buf(),
+        source,
+        modified,
+    });
+
+    let includes = take_includes(&mut value).with_context(|| FileContext {
+        key: Some("include".to_owned()),
+        ..FileContext::new("invalid `include` in config file", path)
+    })?;
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    including.push(id);
+    for include in includes.paths() {
+        load_layer(&dir.join(include), including, loaded, layers, files)?;
+    }
+    including.pop();
+
+    layers.push(Layer {
+        path: path.to_path_buf(),
+        value,
+    });
+    Ok(())
+}
+
+/// Removes the `include` key from a config, returning the paths it includes.
+fn take_includes(value: &mut Value) -> Result<Includes, serde_value::DeserializerError> {
+    match value {
+        Value::Map(map) => match map.remove(&Value::String("include".to_owned())) {
+            Some(includes) => includes.deserialize_into(),
+            None => Ok(Includes::default()),
+        },
+        // an empty file
+        Value::Unit => {
+            *value = Value::Map(BTreeMap::new());
+            Ok(Includes::default())
+        }
+        _ => Ok(Includes::default()),
+    }
+}
+
+/// Merges `overlay` into `base`. Maps are merged key by key, and any other
+/// value replaces the one it overrides, as does a map whose `kind` differs.
+/// A null value removes the entry it overrides.
+fn merge(base: &mut Value, overlay: Value) {
+    let kind = Value::String("kind".to_owned());
+    match (base, overlay) {
+        (Value::Map(base), Value::Map(overlay))
+            if base
+                .get(&kind)
+                .is_none_or(|k| overlay.get(&kind).is_none_or(|o| o == k)) =>
+        {
+            for (key, value) in overlay {
+                match value {
+                    Value::Unit | Value::Option(None) => {
+                        base.remove(&key);
+                    }
+                    value => match base.get_mut(&key) {
+                        Some(base) => merge(base, value),
+                        None => {
+                            base.insert(key, value);
+                        }
+                    },
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+fn deserialize(
+    config: &RawConfig,
+    deserializers: &Deserializers,
+    appenders: &mut AppenderCache,
+) -> Config {
+    let (appenders, mut errors) = config.appenders_reusing(deserializers, appenders);
     errors.handle();
 
     let (config, mut errors) = Config::builder()
@@ -157,76 +845,734 @@ fn deserialize(config: &RawConfig, deserializers: &Deserializers) -> Config {
     config
 }
 
+/// Deserializes `config` as `deserialize` does, but fails rather than
+/// stripping any part of it which is incorrect.
+fn deserialize_strict(
+    config: &RawConfig,
+    deserializers: &Deserializers,
+    appenders: &mut AppenderCache,
+) -> anyhow::Result<Config> {
+    let (appenders, errors) = config.appenders_reusing(deserializers, appenders);
+    if !errors.is_empty() {
+        let errors = errors
+            .errors()
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        anyhow::bail!("{}", errors.join("; "));
+    }
+
+    Config::builder()
+        .appenders(appenders)
+        .loggers(config.loggers())
+        .build(config.root())
+        .map_err(|errors| {
+            let errors = errors
+                .errors()
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>();
+            anyhow::anyhow!("{}", errors.join("; "))
+        })
+}
+
+#[derive(Debug)]
 struct ConfigReloader {
-    path: PathBuf,
-    format: Format,
-    source: String,
-    modified: Option<SystemTime>,
+    origin: Origin,
+    // every file read by the last load, including those included
+    files: Vec<SourceFile>,
+    refresh_rate: Option<Duration>,
+    // the config currently applied
+    raw: RawConfig,
+    #[debug(skip)]
     deserializers: Deserializers,
+    // the appenders of the current config, reused by a reload if unchanged
+    appenders: AppenderCache,
+    hooks: ReloadHooks,
     handle: Handle,
+    // whether the refresh thread is running
+    running: bool,
 }
 
 impl ConfigReloader {
-    fn start(
-        path: PathBuf,
-        format: Format,
-        rate: Duration,
-        source: String,
-        modified: Option<SystemTime>,
-        deserializers: Deserializers,
-        handle: Handle,
-    ) {
-        let mut reloader = ConfigReloader {
-            path,
-            format,
-            source,
-            modified,
-            deserializers,
-            handle,
-        };
+    /// Starts the refresh thread if the config sets a refresh rate and it
+    /// isn't already running.
+    fn start(reloader: &Arc<Mutex<ConfigReloader>>) {
+        let mut locked = lock(reloader);
+        if locked.running || locked.refresh_rate.is_none() {
+            return;
+        }
+        locked.running = true;
 
+        let reloader = reloader.clone();
         thread::Builder::new()
             .name("log4rs refresh".to_owned())
-            .spawn(move || reloader.run(rate))
+            .spawn(move || ConfigReloader::run(&reloader))
             .unwrap();
     }
 
-    fn run(&mut self, mut rate: Duration) {
+    fn run(reloader: &Mutex<ConfigReloader>) {
+        // only files can be watched
+        #[cfg(feature = "config_watch")]
+        if matches!(lock(reloader).origin, Origin::Files(_)) {
+            ConfigReloader::watch(reloader);
+        }
+
         loop {
+            let rate = match lock(reloader).refresh_rate {
+                Some(rate) => rate,
+                None => return,
+            };
             thread::sleep(rate);
 
-            match self.run_once(rate) {
-                Ok(Some(r)) => rate = r,
-                Ok(None) => break,
-                Err(e) => handle_error(&e),
+            if !lock(reloader).run_once(true) {
+                return;
             }
         }
     }
 
-    fn run_once(&mut self, rate: Duration) -> anyhow::Result<Option<Duration>> {
-        if let Some(last_modified) = self.modified {
-            let modified = fs::metadata(&self.path).and_then(|m| m.modified())?;
-            if last_modified == modified {
-                return Ok(Some(rate));
+    /// Reloads the config whenever its files change, until the refresh
+    /// thread should stop or watching fails.
+    #[cfg(feature = "config_watch")]
+    fn watch(reloader: &Mutex<ConfigReloader>) {
+        match super::watch::ConfigWatcher::new(&lock(reloader).file_paths()) {
+            Ok(mut watcher) => loop {
+                if let Err(e) = watcher.wait(None) {
+                    handle_error(&e.context("config file watching failed, polling instead"));
+                    break;
+                }
+
+                let (running, paths) = {
+                    let mut reloader = lock(reloader);
+                    (reloader.run_once(false), reloader.file_paths())
+                };
+                if !running {
+                    return;
+                }
+                // the files included may have changed
+                if let Err(e) = watcher.set_files(&paths) {
+                    handle_error(&e.context("config file watching failed, polling instead"));
+                    break;
+                }
+            },
+            Err(e) => handle_error(&e.context("unable to watch config file, polling instead")),
+        }
+    }
+
+    /// Reloads the config if it has changed, returning whether the refresh
+    /// thread should keep running. When `poll` is `false`, the modification
+    /// time is not trusted to tell whether it has, since a watcher has
+    /// already seen a change.
+    fn run_once(&mut self, poll: bool) -> bool {
+        match self.changed(poll) {
+            Ok(true) => {
+                let _ = self.reload();
             }
+            Ok(false) => {}
+            Err(e) => self.report_failure(&e),
+        }
 
-            self.modified = Some(modified);
+        if self.refresh_rate.is_none() {
+            self.running = false;
         }
+        self.running
+    }
 
-        let source = read_config(&self.path)?;
+    #[cfg(feature = "config_watch")]
+    fn file_paths(&self) -> Vec<PathBuf> {
+        self.files.iter().map(|file| file.path.clone()).collect()
+    }
 
-        if source == self.source {
-            return Ok(Some(rate));
+    /// Returns whether the source or any of the files has changed,
+    /// remembering their new contents so that a config which fails to load
+    /// is not reloaded again until it changes.
+    fn changed(&mut self, poll: bool) -> anyhow::Result<bool> {
+        let mut changed = false;
+        if let Origin::Source(source, contents) = &mut self.origin {
+            let new = source.read().context("error reading config source")?;
+            if new != *contents {
+                *contents = new;
+                changed = true;
+            }
         }
 
-        self.source = source;
+        for file in &mut self.files {
+            if let Some(last_modified) = file.modified {
+                let modified = fs::metadata(&file.path)
+                    .and_then(|m| m.modified())
+                    .with_context(|| FileContext::new("error reading config file", &file.path))?;
+                if poll && last_modified == modified {
+                    continue;
+                }
 
-        let config = self.format.parse(&self.source)?;
-        let rate = config.refresh_rate();
-        let config = deserialize(&config, &self.deserializers);
+                file.modified = Some(modified);
+            }
+
+            let source = read_config(&file.path)?;
+
+            if source != file.source {
+                file.source = source;
+                changed = true;
+            }
+        }
+
+        Ok(changed)
+    }
+
+    /// Loads and applies the config files, reporting the outcome to the
+    /// hooks.
+    fn reload(&mut self) -> anyhow::Result<()> {
+        let summary = match self.apply() {
+            Ok(summary) => summary,
+            Err(e) => {
+                self.report_failure(&e);
+                return Err(e);
+            }
+        };
+
+        if let Some(on_success) = &self.hooks.on_success {
+            on_success(&summary);
+        }
+        Ok(())
+    }
+
+    fn apply(&mut self) -> anyhow::Result<ReloadSummary> {
+        let (raw, files) = self.origin.load()?;
+        // the ring buffers registered by the current config stay registered
+        // if this one is rejected
+        #[cfg(feature = "ring_buffer_appender")]
+        let registrations = crate::append::ring_buffer::Registrations::begin();
+        // the cache must keep matching the applied config if this one is
+        // rejected
+        let mut appenders = self.appenders.clone();
+        let config = deserialize_strict(&raw, &self.deserializers, &mut appenders)
+            .context("error building the new configuration")
+            .and_then(|config| match &self.hooks.validator {
+                Some(validator) => validator(&config)
+                    .context("the new configuration was rejected")
+                    .map(|()| config),
+                None => Ok(config),
+            });
+        let config = match config {
+            Ok(config) => config,
+            Err(e) => {
+                // the appenders reused from the current config were resolved
+                // against those of this one
+                self.appenders.resolve();
+                return Err(e);
+            }
+        };
 
         self.handle.set_config(config);
+        #[cfg(feature = "ring_buffer_appender")]
+        registrations.commit();
+
+        let summary = self.raw.changes(&raw);
+        self.refresh_rate = raw.refresh_rate();
+        self.raw = raw;
+        self.files = files;
+        self.appenders = appenders;
+        Ok(summary)
+    }
+
+    /// Reads the source again, if any, and reloads the config whether or not
+    /// it has changed.
+    fn reload_now(&mut self) -> anyhow::Result<()> {
+        if let Origin::Source(source, contents) = &mut self.origin {
+            match source.read().context("error reading config source") {
+                Ok(new) => *contents = new,
+                Err(e) => {
+                    self.report_failure(&e);
+                    return Err(e);
+                }
+            }
+        }
+        self.reload()
+    }
+
+    fn report_failure(&self, e: &anyhow::Error) {
+        match &self.hooks.on_failure {
+            Some(on_failure) => on_failure(e),
+            None => handle_error(e),
+        }
+    }
+}
+
+fn lock(reloader: &Mutex<ConfigReloader>) -> MutexGuard<'_, ConfigReloader> {
+    reloader.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Reloads a config on behalf of a `Handle`.
+#[derive(Debug)]
+struct FileReloader(Arc<Mutex<ConfigReloader>>);
+
+impl Reload for FileReloader {
+    fn reload(&self) -> anyhow::Result<()> {
+        let result = lock(&self.0).reload_now();
+        // the new config may have set a refresh rate
+        ConfigReloader::start(&self.0);
+        result
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "yaml_format")]
+mod test {
+    use log::LevelFilter;
+
+    use super::*;
+    use crate::config::Root;
+
+    fn reloader(path: &Path, hooks: ReloadHooks) -> (crate::Logger, ConfigReloader) {
+        let logger = crate::Logger::new(
+            Config::builder()
+                .build(Root::builder().build(LevelFilter::Info))
+                .unwrap(),
+        );
+        let reloader = ConfigReloader {
+            origin: Origin::Files(vec![path.to_path_buf()]),
+            files: vec![SourceFile {
+                path: path.to_path_buf(),
+                source: String::new(),
+                modified: None,
+            }],
+            refresh_rate: None,
+            raw: RawConfig::default(),
+            deserializers: Deserializers::default(),
+            appenders: AppenderCache::default(),
+            hooks,
+            handle: logger.handle(),
+            running: false,
+        };
+        (logger, reloader)
+    }
+
+    #[test]
+    fn hooks() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log4rs.yaml");
+        let summaries = Arc::new(Mutex::new(vec![]));
+        let failures = Arc::new(Mutex::new(vec![]));
+        let hooks = {
+            let summaries = summaries.clone();
+            let failures = failures.clone();
+            ReloadHooks::new()
+                .on_success(move |summary| summaries.lock().unwrap().push(summary.to_string()))
+                .on_failure(move |e| failures.lock().unwrap().push(format!("{:#}", e)))
+                .validator(|config| {
+                    if config.root().level() == LevelFilter::Trace {
+                        anyhow::bail!("trace is too verbose");
+                    }
+                    Ok(())
+                })
+        };
+        let (logger, mut reloader) = reloader(&path, hooks);
+
+        fs::write(
+            &path,
+            "loggers:\n  foo:\n    level: debug\nroot:\n  level: warn\n",
+        )
+        .unwrap();
+        reloader.run_once(true);
+        assert_eq!(logger.max_log_level(), LevelFilter::Debug);
+        // unchanged, so not reloaded
+        reloader.run_once(true);
+
+        fs::write(&path, "root:\n  level: trace\n").unwrap();
+        reloader.run_once(true);
+        assert_eq!(logger.max_log_level(), LevelFilter::Debug);
+
+        fs::write(&path, "root: [").unwrap();
+        assert!(reloader.reload().is_err());
+        assert_eq!(logger.max_log_level(), LevelFilter::Debug);
+
+        fs::write(&path, "root:\n  level: warn\n").unwrap();
+        reloader.reload().unwrap();
+        reloader.reload().unwrap();
+        assert_eq!(logger.max_log_level(), LevelFilter::Warn);
+
+        assert_eq!(
+            *summaries.lock().unwrap(),
+            [
+                "loggers added: foo; root changed",
+                "loggers removed: foo",
+                "no changes"
+            ]
+        );
+        let failures = failures.lock().unwrap();
+        assert_eq!(failures.len(), 2);
+        assert_eq!(
+            failures[0],
+            "the new configuration was rejected: trace is too verbose"
+        );
+    }
+
+    #[test]
+    fn incorrect_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log4rs.yaml");
+        let summaries = Arc::new(Mutex::new(vec![]));
+        let failures = Arc::new(Mutex::new(vec![]));
+        let hooks = {
+            let summaries = summaries.clone();
+            let failures = failures.clone();
+            ReloadHooks::new()
+                .on_success(move |summary| summaries.lock().unwrap().push(summary.to_string()))
+                .on_failure(move |e| failures.lock().unwrap().push(format!("{:#}", e)))
+        };
+        let (logger, mut reloader) = reloader(&path, hooks);
+
+        fs::write(&path, "root:\n  level: warn\n").unwrap();
+        reloader.reload().unwrap();
+
+        fs::write(
+            &path,
+            "appenders:\n  a:\n    kind: nonexistent\nroot:\n  level: debug\n",
+        )
+        .unwrap();
+        assert!(reloader.reload().is_err());
+        fs::write(&path, "root:\n  level: debug\n  appenders: [a]\n").unwrap();
+        assert!(reloader.reload().is_err());
+        assert_eq!(logger.max_log_level(), LevelFilter::Warn);
+        assert_eq!(reloader.raw.root().level(), LevelFilter::Warn);
+
+        assert_eq!(*summaries.lock().unwrap(), ["root changed"]);
+        let failures = failures.lock().unwrap();
+        assert_eq!(failures.len(), 2);
+        assert!(failures[0]
+            .starts_with("error building the new configuration: error deserializing appender a"));
+        assert_eq!(
+            failures[1],
+            "error building the new configuration: Reference to nonexistent appender: `a`"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "ring_buffer_appender")]
+    fn rejected_ring_buffer() {
+        use crate::append::ring_buffer;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log4rs.yaml");
+        let hooks = ReloadHooks::new().on_failure(|_| {}).validator(|config| {
+            if config.root().level() == LevelFilter::Trace {
+                anyhow::bail!("trace is too verbose");
+            }
+            Ok(())
+        });
+        let (logger, mut reloader) = reloader(&path, hooks);
+
+        let config = |capacity, level| {
+            format!(
+                "appenders:\n  ring:\n    kind: ring_buffer\n    name: file::test::rejected\n    capacity: {}\nroot:\n  level: {}\n  appenders: [ring]\n",
+                capacity, level
+            )
+        };
+        fs::write(&path, config(10, "info")).unwrap();
+        reloader.reload().unwrap();
+
+        // the ring buffer has changed, so it is built again
+        fs::write(&path, config(20, "trace")).unwrap();
+        assert!(reloader.reload().is_err());
+
+        log::Log::log(
+            &logger,
+            &log::Record::builder()
+                .level(log::Level::Info)
+                .args(format_args!("kept"))
+                .build(),
+        );
+        let handle = ring_buffer::handle("file::test::rejected").unwrap();
+        assert_eq!(handle.len(), 1);
+    }
+
+    #[test]
+    #[cfg(all(feature = "ring_buffer_appender", feature = "file_appender"))]
+    fn rejected_flush_target() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log4rs.yaml");
+        let hooks = ReloadHooks::new().on_failure(|_| {}).validator(|config| {
+            if config.root().level() == LevelFilter::Trace {
+                anyhow::bail!("trace is too verbose");
+            }
+            Ok(())
+        });
+        let (logger, mut reloader) = reloader(&path, hooks);
+
+        let config = |file: &str, level| {
+            format!(
+                "appenders:\n  ring:\n    kind: ring_buffer\n    flush_to: file\n  file:\n    kind: file\n    path: {}\n    encoder:\n      pattern: \"{{m}}\"\nroot:\n  level: {}\n  appenders: [ring]\n",
+                dir.path().join(file).display(),
+                level
+            )
+        };
+        fs::write(&path, config("kept.log", "info")).unwrap();
+        reloader.reload().unwrap();
+
+        // the file appender has changed, so the reused ring buffer is
+        // resolved against the new one
+        fs::write(&path, config("rejected.log", "trace")).unwrap();
+        assert!(reloader.reload().is_err());
+
+        log::Log::log(
+            &logger,
+            &log::Record::builder()
+                .level(log::Level::Error)
+                .args(format_args!("flushed"))
+                .build(),
+        );
+        log::Log::flush(&logger);
+        let kept = fs::read_to_string(dir.path().join("kept.log")).unwrap();
+        assert_eq!(kept, "flushed");
+        let rejected = fs::read_to_string(dir.path().join("rejected.log")).unwrap_or_default();
+        assert_eq!(rejected, "");
+    }
+
+    #[test]
+    fn source() {
+        #[derive(Clone)]
+        struct Shared(Arc<Mutex<String>>);
+
+        impl ConfigSource for Shared {
+            fn read(&mut self) -> anyhow::Result<String> {
+                Ok(self.0.lock().unwrap().clone())
+            }
+        }
+
+        let shared = Shared(Arc::new(Mutex::new("root:\n  level: warn\n".to_owned())));
+        let origin = Origin::source(Box::new(shared.clone())).unwrap();
+        let (raw, files) = origin.load().unwrap();
+        assert_eq!(raw.root().level(), LevelFilter::Warn);
+        assert!(files.is_empty());
+
+        let (logger, mut reloader) = reloader(Path::new("unused.yaml"), ReloadHooks::new());
+        reloader.origin = origin;
+        reloader.files = files;
+
+        *shared.0.lock().unwrap() = "root:\n  level: trace\n".to_owned();
+        reloader.run_once(true);
+        assert_eq!(logger.max_log_level(), LevelFilter::Trace);
+
+        *shared.0.lock().unwrap() = "root:\n  level: error\n".to_owned();
+        reloader.reload_now().unwrap();
+        assert_eq!(logger.max_log_level(), LevelFilter::Error);
+    }
+
+    #[test]
+    fn load_config_str() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join("base");
+        fs::write(&base, "loggers:\n  app:\n    level: debug\n").unwrap();
+
+        let source = format!(
+            "include: {}\nroot:\n  level: ${{LOG4RS_TEST_UNSET:-warn}}\n",
+            base.display()
+        );
+        let (config, files) = load_str(&source, Some(Format::Yaml)).unwrap();
+        assert_eq!(config.root().level(), LevelFilter::Warn);
+        assert_eq!(config.loggers()[0].name(), "app");
+        assert_eq!(files.len(), 1);
+
+        let config =
+            super::load_config_str(&source, Format::Yaml, &Deserializers::default()).unwrap();
+        assert_eq!(config.root().level(), LevelFilter::Warn);
+
+        let err = load_str("root: [", Some(Format::Yaml)).unwrap_err();
+        assert_eq!(err.to_string(), "error parsing config");
+    }
+
+    #[test]
+    fn detect_format() {
+        for (source, expected) in [
+            ("", Format::Yaml),
+            ("# comment\nroot:\n  level: info\n", Format::Yaml),
+            ("---\nroot: {}\n", Format::Yaml),
+            ("root:\n  level: a=b\n", Format::Yaml),
+            ("  {\"root\": {}}", Format::Json),
+            ("# comment\n\n[root]\nlevel = \"info\"\n", Format::Toml),
+            ("refresh_rate = \"30 seconds\"\n", Format::Toml),
+            ("\"refresh_rate\" = \"30 seconds\"\n", Format::Toml),
+        ] {
+            assert_eq!(Format::detect(source), expected, "{:?}", source);
+        }
+
+        assert_eq!(Format::from_path("log4rs.yml").unwrap(), Format::Yaml);
+        assert_eq!(Format::from_path("log4rs.toml").unwrap(), Format::Toml);
+        assert!(matches!(
+            Format::from_path("log4rs"),
+            Err(FormatError::UnknownFormat)
+        ));
+        assert!(matches!(
+            Format::from_path("log4rs.ini"),
+            Err(FormatError::UnsupportedFormat(_))
+        ));
+    }
+
+    #[test]
+    fn reload_now_without_source() {
+        let logger = crate::Logger::new(
+            Config::builder()
+                .build(Root::builder().build(LevelFilter::Info))
+                .unwrap(),
+        );
+        assert!(logger.handle().reload_now().is_err());
+    }
+
+    #[test]
+    fn layers() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("base")).unwrap();
+        fs::write(
+            dir.path().join("base/log4rs.yaml"),
+            r#"
+appenders:
+  stdout:
+    kind: console
+    encoder:
+      pattern: "{m}"
+  file:
+    kind: file
+    path: base.log
+  debug:
+    kind: console
+root:
+  level: info
+  appenders: [stdout, debug]
+"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("log4rs.yaml"),
+            r#"
+include: base/log4rs.yaml
+appenders:
+  stdout:
+    target: stderr
+  file:
+    kind: console
+  debug: ~
+root:
+  appenders: [stdout, file]
+"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("log4rs.prod.yaml"),
+            "loggers:\n  app:\n    level: warn\n",
+        )
+        .unwrap();
+
+        let (config, files) = load(&[
+            dir.path().join("log4rs.yaml"),
+            dir.path().join("log4rs.prod.yaml"),
+        ])
+        .unwrap();
+        assert_eq!(files.len(), 3);
+        assert!(config.include().is_empty());
+        assert_eq!(config.root().level(), LevelFilter::Info);
+        assert_eq!(config.root().appenders(), ["stdout", "file"]);
+        assert_eq!(config.loggers()[0].name(), "app");
+
+        let appenders = config.appenders_raw();
+        assert_eq!(appenders.len(), 2);
+        assert_eq!(
+            appenders["stdout"],
+            serde_yaml::from_str("kind: console\ntarget: stderr\nencoder:\n  pattern: '{m}'")
+                .unwrap()
+        );
+        // a different kind replaces the appender rather than being merged
+        assert_eq!(
+            appenders["file"],
+            serde_yaml::from_str("kind: console").unwrap()
+        );
+    }
+
+    #[test]
+    fn layer_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.yaml");
+        let b = dir.path().join("b.yaml");
+
+        fs::write(&a, "include: b.yaml\n").unwrap();
+        fs::write(&b, "include: [a.yaml]\n").unwrap();
+        let err = load(std::slice::from_ref(&a)).unwrap_err();
+        assert!(err.to_string().contains("includes itself"), "{:#}", err);
+
+        fs::write(&b, "root: [").unwrap();
+        let err = load(std::slice::from_ref(&a)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("error parsing config file `{}`", b.display())
+        );
+
+        fs::write(&b, "roots: {}").unwrap();
+        let err = load(&[a.clone(), b.clone()]).unwrap_err();
+        assert!(err.to_string().starts_with(&format!(
+            "error deserializing config merged from files `{}`, `{}`:",
+            a.display(),
+            b.display()
+        )));
+    }
+
+    #[test]
+    fn diamond_includes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log4rs.yaml");
+        fs::write(&path, "include: [b.yaml, c.yaml]\n").unwrap();
+        fs::write(
+            dir.path().join("base.yaml"),
+            "root:\n  level: info\nloggers:\n  app:\n    level: info\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("b.yaml"),
+            "include: base.yaml\nloggers:\n  app:\n    level: debug\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("c.yaml"),
+            "include: ./base.yaml\nroot:\n  level: warn\n",
+        )
+        .unwrap();
+
+        let (config, files) = load(std::slice::from_ref(&path)).unwrap();
+        assert_eq!(config.root().level(), LevelFilter::Warn);
+        assert_eq!(config.loggers()[0].level(), LevelFilter::Debug);
+        let names = files
+            .iter()
+            .map(|file| file.path.file_name().unwrap().to_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["log4rs.yaml", "b.yaml", "base.yaml", "c.yaml"]);
+    }
+
+    #[test]
+    fn interpolation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log4rs.yaml");
+        fs::write(
+            &path,
+            "include: ${LOG4RS_TEST_UNSET:-base}.yaml\nroot:\n  level: ${LOG4RS_TEST_UNSET:-warn}\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("base.yaml"),
+            "loggers:\n  app:\n    level: debug\n",
+        )
+        .unwrap();
+
+        let (config, _) = load(std::slice::from_ref(&path)).unwrap();
+        assert_eq!(config.root().level(), LevelFilter::Warn);
+        assert_eq!(config.loggers()[0].name(), "app");
 
-        Ok(rate)
+        fs::write(&path, "root:\n  level: ${LOG4RS_TEST_UNSET}\n").unwrap();
+        let err = load(std::slice::from_ref(&path)).unwrap_err();
+        assert_eq!(
+            format!("{:#}", err),
+            format!(
+                "error interpolating config file `{}`: invalid value at `root.level`: \
+                 environment variable `LOG4RS_TEST_UNSET` is not set",
+                path.display()
+            )
+        );
     }
 }
@@ -0,0 +1,382 @@
+//! `${NAME}` and `${NAME:-default}` interpolation of config values.
+
+use serde::de::{
+    value::{MapDeserializer, SeqDeserializer},
+    DeserializeOwned, Deserializer, IntoDeserializer, Visitor,
+};
+use serde_value::{DeserializerError, Value, ValueDeserializer};
+use std::{collections::BTreeMap, env, fmt};
+
+/// Replaces placeholders in every string value of `value`, except map keys,
+/// with the environment variables they name.
+///
+/// `${NAME}` requires the variable to be set, while `${NAME:-default}` uses
+/// the default if it is unset or empty. `$${` produces a literal `${`.
+/// Interpolated values remain strings; `deserialize_into` parses those which
+/// are read as numbers or booleans.
+pub(crate) fn interpolate(value: &mut Value) -> anyhow::Result<()> {
+    interpolate_with(value, &|name| env::var(name).ok(), &mut vec![])
+}
+
+/// The key of a value which could not be interpolated, as a dotted path.
+#[derive(Debug)]
+pub(crate) struct Location(pub(crate) String);
+
+impl fmt::Display for Location {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "invalid value at `{}`", self.0)
+    }
+}
+
+fn interpolate_with(
+    value: &mut Value,
+    lookup: &dyn Fn(&str) -> Option<String>,
+    location: &mut Vec<String>,
+) -> anyhow::Result<()> {
+    match value {
+        Value::String(s) => {
+            if let Some(new) = interpolate_str(s, lookup).map_err(|e| match &**location {
+                [] => e,
+                location => e.context(Location(location.join("."))),
+            })? {
+                *value = new;
+            }
+        }
+        Value::Map(map) => {
+            let entries = std::mem::take(map);
+            let mut new = BTreeMap::new();
+            for (key, mut value) in entries {
+                location.push(key_name(&key));
+                let result = interpolate_with(&mut value, lookup, location);
+                location.pop();
+                result?;
+                new.insert(key, value);
+            }
+            *map = new;
+        }
+        Value::Seq(seq) => {
+            for (i, value) in seq.iter_mut().enumerate() {
+                location.push(i.to_string());
+                let result = interpolate_with(value, lookup, location);
+                location.pop();
+                result?;
+            }
+        }
+        Value::Option(Some(value)) | Value::Newtype(value) => {
+            interpolate_with(value, lookup, location)?
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+fn key_name(key: &Value) -> String {
+    match key {
+        Value::String(s) => s.clone(),
+        Value::U64(n) => n.to_string(),
+        Value::I64(n) => n.to_string(),
+        key => format!("{:?}", key),
+    }
+}
+
+/// Returns the interpolated value of `s`, or `None` if it contains no
+/// placeholders.
+fn interpolate_str(
+    s: &str,
+    lookup: &dyn Fn(&str) -> Option<String>,
+) -> anyhow::Result<Option<Value>> {
+    if !s.contains("${") {
+        return Ok(None);
+    }
+
+    let mut out = String::new();
+    let mut rest = s;
+    while let Some(idx) = rest.find("${") {
+        if rest[..idx].ends_with('$') {
+            // an escaped `$${`
+            out.push_str(&rest[..idx]);
+            out.push('{');
+            rest = &rest[idx + 2..];
+            continue;
+        }
+        out.push_str(&rest[..idx]);
+
+        let body = &rest[idx + 2..];
+        let end = match body.find('}') {
+            Some(end) => end,
+            None => anyhow::bail!("unterminated placeholder in `{}`", s),
+        };
+        let (name, default) = match body[..end].split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (&body[..end], None),
+        };
+        if name.is_empty() {
+            anyhow::bail!("empty variable name in `{}`", s);
+        }
+
+        match (lookup(name), default) {
+            (Some(value), Some(default)) if value.is_empty() => out.push_str(default),
+            (Some(value), _) => out.push_str(&value),
+            (None, Some(default)) => out.push_str(default),
+            (None, None) => anyhow::bail!("environment variable `{}` is not set", name),
+        }
+        rest = &body[end + 1..];
+    }
+    out.push_str(rest);
+
+    Ok(Some(Value::String(out)))
+}
+
+/// Deserializes a config value, accepting strings where numbers or booleans
+/// are expected, as interpolated values are.
+pub(crate) fn deserialize_into<T>(value: Value) -> Result<T, DeserializerError>
+where
+    T: DeserializeOwned,
+{
+    T::deserialize(Lenient(value))
+}
+
+/// A deserializer for a `Value` which parses strings read as numbers or
+/// booleans.
+struct Lenient(Value);
+
+macro_rules! parse_strings {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value, DeserializerError>
+            where
+                V: Visitor<'de>,
+            {
+                if let Value::String(s) = &self.0 {
+                    if let Ok(v) = s.parse() {
+                        return visitor.$visit(v);
+                    }
+                }
+                self.deserialize_any(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for Lenient {
+    type Error = DeserializerError;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, DeserializerError>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            Value::Option(Some(value)) => visitor.visit_some(Lenient(*value)),
+            Value::Newtype(value) => visitor.visit_newtype_struct(Lenient(*value)),
+            Value::Seq(seq) => {
+                visitor.visit_seq(SeqDeserializer::new(seq.into_iter().map(Lenient)))
+            }
+            Value::Map(map) => visitor.visit_map(MapDeserializer::new(
+                map.into_iter().map(|(k, v)| (Lenient(k), Lenient(v))),
+            )),
+            value => ValueDeserializer::new(value).deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, DeserializerError>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            Value::Option(None) => visitor.visit_none(),
+            Value::Option(Some(value)) => visitor.visit_some(Lenient(*value)),
+            Value::Unit => visitor.visit_unit(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, DeserializerError>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            Value::Newtype(value) => visitor.visit_newtype_struct(Lenient(*value)),
+            _ => visitor.visit_newtype_struct(self),
+        }
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DeserializerError>
+    where
+        V: Visitor<'de>,
+    {
+        ValueDeserializer::new(self.0).deserialize_enum(name, variants, visitor)
+    }
+
+    parse_strings! {
+        deserialize_bool => visit_bool,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+    }
+
+    serde::forward_to_deserialize_any! {
+        char str string bytes byte_buf unit unit_struct seq tuple tuple_struct
+        map struct identifier ignored_any
+    }
+}
+
+impl IntoDeserializer<'_, DeserializerError> for Lenient {
+    type Deserializer = Lenient;
+
+    fn into_deserializer(self) -> Lenient {
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "DIR" => Some("/var/log".to_owned()),
+            "LEVEL" => Some("debug".to_owned()),
+            "COUNT" => Some("5".to_owned()),
+            "EMPTY" => Some(String::new()),
+            _ => None,
+        }
+    }
+
+    fn interpolated(s: &str) -> anyhow::Result<Value> {
+        let mut value = Value::String(s.to_owned());
+        interpolate_with(&mut value, &lookup, &mut vec![])?;
+        Ok(value)
+    }
+
+    #[test]
+    fn strings() {
+        let tests = [
+            ("{d} {m}{n}", Value::String("{d} {m}{n}".to_owned())),
+            (
+                "${DIR}/app.log",
+                Value::String("/var/log/app.log".to_owned()),
+            ),
+            ("${LEVEL}", Value::String("debug".to_owned())),
+            ("${MISSING:-info}", Value::String("info".to_owned())),
+            ("${EMPTY:-info}", Value::String("info".to_owned())),
+            ("${EMPTY}", Value::String(String::new())),
+            ("${MISSING:-}", Value::String(String::new())),
+            (
+                "${DIR}:${LEVEL}",
+                Value::String("/var/log:debug".to_owned()),
+            ),
+            ("$${DIR}", Value::String("${DIR}".to_owned())),
+            ("$ENV{DIR}", Value::String("$ENV{DIR}".to_owned())),
+            ("${COUNT}", Value::String("5".to_owned())),
+            ("${MISSING:-true}", Value::String("true".to_owned())),
+            ("${COUNT} mb", Value::String("5 mb".to_owned())),
+        ];
+
+        for (s, expected) in tests {
+            assert_eq!(interpolated(s).unwrap(), expected, "{}", s);
+        }
+    }
+
+    #[test]
+    fn deserialize() {
+        #[derive(serde::Deserialize, PartialEq, Debug)]
+        struct Config {
+            name: String,
+            count: u64,
+            offset: Option<i32>,
+            ratio: f64,
+            enabled: bool,
+            names: Vec<String>,
+        }
+
+        let mut value = Value::Map(
+            [
+                ("name", "${COUNT}"),
+                ("count", "${COUNT}"),
+                ("offset", "${MISSING:--3}"),
+                ("ratio", "0.5"),
+                ("enabled", "${MISSING:-true}"),
+            ]
+            .into_iter()
+            .map(|(k, v)| (Value::String(k.to_owned()), Value::String(v.to_owned())))
+            .chain([(
+                Value::String("names".to_owned()),
+                Value::Seq(vec![Value::String("${COUNT}".to_owned())]),
+            )])
+            .collect(),
+        );
+        interpolate_with(&mut value, &lookup, &mut vec![]).unwrap();
+        assert_eq!(
+            deserialize_into::<Config>(value).unwrap(),
+            Config {
+                name: "5".to_owned(),
+                count: 5,
+                offset: Some(-3),
+                ratio: 0.5,
+                enabled: true,
+                names: vec!["5".to_owned()],
+            }
+        );
+
+        let value = Value::Map(
+            [(
+                Value::String("count".to_owned()),
+                Value::String("five".to_owned()),
+            )]
+            .into_iter()
+            .collect(),
+        );
+        assert!(deserialize_into::<BTreeMap<String, u64>>(value).is_err());
+    }
+
+    #[test]
+    fn errors() {
+        for (s, expected) in [
+            ("${MISSING}", "environment variable `MISSING` is not set"),
+            ("${DIR", "unterminated placeholder in `${DIR`"),
+            ("${:-x}", "empty variable name in `${:-x}`"),
+        ] {
+            assert_eq!(interpolated(s).unwrap_err().to_string(), expected);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "yaml_format")]
+    fn nested() {
+        let mut value = serde_yaml::from_str::<Value>(
+            "appenders:\n  file:\n    path: ${DIR}/app.log\n    filters:\n    - level: ${LEVEL}\n",
+        )
+        .unwrap();
+        interpolate_with(&mut value, &lookup, &mut vec![]).unwrap();
+        let expected = serde_yaml::from_str::<Value>(
+            "appenders:\n  file:\n    path: /var/log/app.log\n    filters:\n    - level: debug\n",
+        )
+        .unwrap();
+        assert_eq!(value, expected);
+
+        let mut value =
+            serde_yaml::from_str::<Value>("appenders:\n  file:\n    path: ${MISSING}\n").unwrap();
+        let err = interpolate_with(&mut value, &lookup, &mut vec![]).unwrap_err();
+        assert_eq!(
+            format!("{:#}", err),
+            "invalid value at `appenders.file.path`: environment variable `MISSING` is not set"
+        );
+    }
+}
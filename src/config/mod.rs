@@ -4,21 +4,61 @@
 use log::SetLoggerError;
 use thiserror::Error;
 
-use crate::Handle;
-
 pub mod runtime;
 
+mod directives;
 #[cfg(feature = "config_parsing")]
 mod file;
 #[cfg(feature = "config_parsing")]
+mod interpolate;
+#[cfg(feature = "config_parsing")]
 mod raw;
+#[cfg(feature = "schema")]
+pub(crate) mod schema;
+#[cfg(feature = "config_parsing")]
+mod validate;
+#[cfg(feature = "config_watch")]
+mod watch;
 
-pub use runtime::{Appender, Config, Logger, Root};
+pub use self::directives::{Directives, DirectivesError};
+pub use runtime::{Appender, Config, ErrorPolicy, Logger, Root};
 
 #[cfg(feature = "config_parsing")]
-pub use self::file::{init_file, load_config_file, FormatError};
+pub use self::file::{
+    init_config_source, init_config_str, init_file, init_file_with_hooks, init_files,
+    init_files_with_hooks, load_config_file, load_config_files, load_config_str,
+    resolve_config_files, ConfigSource, Format, FormatError, ReloadHooks, ReloadSummary,
+};
+#[cfg(feature = "config_parsing")]
+#[allow(unused_imports)] // unused when no components are enabled
+pub(crate) use self::raw::component_config;
+#[cfg(all(
+    feature = "config_parsing",
+    any(feature = "ring_buffer_appender", feature = "threshold_filter")
+))]
+pub(crate) use self::raw::ser_level;
+#[cfg(feature = "config_parsing")]
+pub(crate) use self::raw::AppenderCache;
+#[cfg(all(
+    feature = "config_parsing",
+    any(
+        feature = "failover_appender",
+        feature = "sifting_appender",
+        feature = "socket_appender"
+    )
+))]
+pub(crate) use self::raw::{de_duration, ser_duration};
+#[cfg(feature = "config_parsing")]
+pub(crate) use self::raw::{
+    de_required_duration, describe_appender, ser_component, ser_required_duration,
+};
 #[cfg(feature = "config_parsing")]
 pub use self::raw::{Deserializable, Deserialize, Deserializers, RawConfig};
+#[cfg(feature = "config_parsing")]
+pub use self::validate::{
+    validate_config_file, validate_config_files, validate_config_files_with_mode, Diagnostic,
+    ValidationMode,
+};
 
 /// Initializes the global logger as a log4rs logger with the provided config.
 ///
@@ -41,9 +81,7 @@ pub fn init_config_with_err_handler(
 ) -> Result<crate::Handle, SetLoggerError> {
     let logger = crate::Logger::new_with_err_handler(config, err_handler);
     log::set_max_level(logger.max_log_level());
-    let handle = Handle {
-        shared: logger.0.clone(),
-    };
+    let handle = logger.handle();
     log::set_boxed_logger(Box::new(logger)).map(|()| handle)
 }
 
@@ -9,7 +9,15 @@
 //! All file formats currently share the same structure. The example below is
 //! of the YAML format.
 //!
+//! Any string in a config file may refer to environment variables as
+//! `${NAME}`, which is an error if `NAME` is not set, or `${NAME:-default}`.
+//!
 //! ```yaml
+//! # Other config files which this one is layered on top of, as a single path
+//! # or a list. Relative paths are resolved from this file's directory. The
+//! # included files are merged in order, then this file is merged on top.
+//! include: base.yaml
+//!
 //! # If set, log4rs will scan the file at the specified rate for changes and
 //! # automatically reconfigure the logger. The input string is parsed by the
 //! # humantime crate.
@@ -37,6 +45,14 @@
 //!         # filter's builder, and will vary based on the kind of filter.
 //!         level: error
 //!
+//!     # How errors returned by the appender are reported. One of `report`,
+//!     # which passes every error to the error handler, `ignore`, `log_once`,
+//!     # or `rate_limit` with the minimum time between reports.
+//!     #
+//!     # Default: report
+//!     on_error:
+//!       rate_limit: 1 minute
+//!
 //!     # The remainder of the configuration is passed along to the appender's
 //!     # builder, and will vary based on the kind of appender.
 //!     # Appenders will commonly be associated with an encoder.
@@ -86,25 +102,56 @@
 //!     # parent will also be attached to this logger.
 //!     # Default: true
 //!     additive: false
+//!
+//! # Levels in the syntax of env_logger's RUST_LOG, applied on top of those
+//! # configured above. Loggers which are not configured are created without
+//! # appenders of their own, so they log to the appenders of their nearest
+//! # configured ancestor, normally the root. Either the directives themselves
+//! # or the name of an environment variable to read them from.
+//! #
+//! # Default: none
+//! directives:
+//!   env: RUST_LOG
 //! ```
 #![allow(deprecated)]
 
-use std::{collections::HashMap, fmt, marker::PhantomData, sync::Arc, time::Duration};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    marker::PhantomData,
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
+};
 
-use anyhow::anyhow;
+use anyhow::{anyhow, Context};
 use derive_more::Debug;
 use log::LevelFilter;
-use serde::de::{self, Deserialize as SerdeDeserialize, DeserializeOwned};
+use serde::{
+    de::{self, Deserialize as SerdeDeserialize, DeserializeOwned},
+    ser::{self, Serialize as _},
+};
 use serde_value::Value;
 use thiserror::Error;
 use typemap_ors::{Key, ShareCloneMap};
 
-use crate::{append::AppenderConfig, config};
+use crate::{
+    append::{Append, AppenderConfig, Appenders, ErrorPolicyConfig},
+    config::{self, Directives, ReloadSummary},
+    filter::Filter,
+};
+
+#[cfg(feature = "schema")]
+use crate::{encode::Encode, filter::FilterConfig};
 
 #[allow(unused_imports)]
 use crate::append;
 
-#[cfg(any(feature = "json_encoder", feature = "pattern_encoder"))]
+#[cfg(any(
+    feature = "json_encoder",
+    feature = "logfmt_encoder",
+    feature = "pattern_encoder"
+))]
 use crate::encode;
 
 #[cfg(feature = "threshold_filter")]
@@ -132,6 +179,45 @@ pub trait Deserialize: Send + Sync + 'static {
         config: Self::Config,
         deserializers: &Deserializers,
     ) -> anyhow::Result<Box<Self::Trait>>;
+
+    /// Checks the components nested in `config`, such as an appender's
+    /// encoder, without building them, by passing each to
+    /// `Deserializers::check_nested`.
+    ///
+    /// This is used to validate config files. The default checks nothing.
+    fn check_components(
+        &self,
+        config: &Self::Config,
+        deserializers: &Deserializers,
+    ) -> anyhow::Result<()> {
+        let _ = (config, deserializers);
+        Ok(())
+    }
+
+    /// Returns the names of the appenders which an appender built from
+    /// `config` forwards records to, as its `Append::targets` would.
+    ///
+    /// This lets appenders which build others lazily, such as the sifting
+    /// appender, report the targets of the appenders they will build. Nested
+    /// appenders can be passed to `Deserializers::targets`. The default
+    /// returns no names.
+    fn targets(&self, config: &Self::Config, deserializers: &Deserializers) -> Vec<String> {
+        let _ = (config, deserializers);
+        vec![]
+    }
+
+    /// Returns a JSON Schema for this deserializer's configuration, without
+    /// the `kind` key.
+    ///
+    /// The schema should be inlined rather than refer to `generator`'s
+    /// definitions at its top level. The default allows any map.
+    ///
+    /// Requires the `schema` feature.
+    #[cfg(feature = "schema")]
+    fn schema(&self, generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        let _ = generator;
+        schemars::json_schema!({ "type": "object" })
+    }
 }
 
 trait ErasedDeserialize: Send + Sync + 'static {
@@ -142,6 +228,17 @@ trait ErasedDeserialize: Send + Sync + 'static {
         config: Value,
         deserializers: &Deserializers,
     ) -> anyhow::Result<Box<Self::Trait>>;
+
+    /// Checks that `config` can be deserialized into the configuration type,
+    /// and the components nested in it in turn, without building them.
+    fn check(&self, config: Value, deserializers: &Deserializers) -> anyhow::Result<()>;
+
+    /// Returns the targets of the appender built from `config`, or none if
+    /// `config` is invalid.
+    fn targets(&self, config: Value, deserializers: &Deserializers) -> Vec<String>;
+
+    #[cfg(feature = "schema")]
+    fn schema(&self, generator: &mut schemars::SchemaGenerator) -> schemars::Schema;
 }
 
 struct DeserializeEraser<T>(T);
@@ -157,9 +254,26 @@ where
         config: Value,
         deserializers: &Deserializers,
     ) -> anyhow::Result<Box<Self::Trait>> {
-        let config = config.deserialize_into()?;
+        let config = super::interpolate::deserialize_into(config)?;
         self.0.deserialize(config, deserializers)
     }
+
+    fn check(&self, config: Value, deserializers: &Deserializers) -> anyhow::Result<()> {
+        let config = super::interpolate::deserialize_into::<T::Config>(config)?;
+        self.0.check_components(&config, deserializers)
+    }
+
+    fn targets(&self, config: Value, deserializers: &Deserializers) -> Vec<String> {
+        match super::interpolate::deserialize_into::<T::Config>(config) {
+            Ok(config) => self.0.targets(&config, deserializers),
+            Err(_) => vec![],
+        }
+    }
+
+    #[cfg(feature = "schema")]
+    fn schema(&self, generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        self.0.schema(generator)
+    }
 }
 
 struct KeyAdaptor<T: ?Sized>(PhantomData<T>);
@@ -177,9 +291,15 @@ impl Default for Deserializers {
         #[allow(unused_mut)]
         let mut d = Deserializers::empty();
 
+        #[cfg(feature = "async_appender")]
+        d.insert("async", append::r#async::AsyncAppenderDeserializer);
+
         #[cfg(feature = "console_appender")]
         d.insert("console", append::console::ConsoleAppenderDeserializer);
 
+        #[cfg(feature = "failover_appender")]
+        d.insert("failover", append::failover::FailoverAppenderDeserializer);
+
         #[cfg(feature = "file_appender")]
         d.insert("file", append::file::FileAppenderDeserializer);
 
@@ -189,6 +309,24 @@ impl Default for Deserializers {
             append::rolling_file::RollingFileAppenderDeserializer,
         );
 
+        #[cfg(feature = "ring_buffer_appender")]
+        d.insert(
+            "ring_buffer",
+            append::ring_buffer::RingBufferAppenderDeserializer,
+        );
+
+        #[cfg(feature = "sifting_appender")]
+        d.insert("sifting", append::sifting::SiftingAppenderDeserializer);
+
+        #[cfg(feature = "socket_appender")]
+        d.insert("socket", append::socket::SocketAppenderDeserializer);
+
+        #[cfg(feature = "gelf_appender")]
+        d.insert("gelf", append::gelf::GelfAppenderDeserializer);
+
+        #[cfg(feature = "syslog_appender")]
+        d.insert("syslog", append::syslog::SyslogAppenderDeserializer);
+
         #[cfg(feature = "compound_policy")]
         d.insert(
             "compound",
@@ -228,6 +366,15 @@ impl Default for Deserializers {
         #[cfg(feature = "json_encoder")]
         d.insert("json", encode::json::JsonEncoderDeserializer);
 
+        #[cfg(feature = "ecs_encoder")]
+        d.insert("ecs", encode::ecs::EcsEncoderDeserializer);
+
+        #[cfg(feature = "gelf_encoder")]
+        d.insert("gelf", encode::gelf::GelfEncoderDeserializer);
+
+        #[cfg(feature = "logfmt_encoder")]
+        d.insert("logfmt", encode::logfmt::LogfmtEncoderDeserializer);
+
         #[cfg(feature = "pattern_encoder")]
         d.insert("pattern", encode::pattern::PatternEncoderDeserializer);
 
@@ -244,17 +391,37 @@ impl Deserializers {
     /// All are enabled by default.
     ///
     /// * Appenders
+    ///     * "async" -> `AsyncAppenderDeserializer`
+    ///         * Requires the `async_appender` feature.
     ///     * "console" -> `ConsoleAppenderDeserializer`
     ///         * Requires the `console_appender` feature.
+    ///     * "failover" -> `FailoverAppenderDeserializer`
+    ///         * Requires the `failover_appender` feature.
     ///     * "file" -> `FileAppenderDeserializer`
     ///         * Requires the `file_appender` feature.
     ///     * "rolling_file" -> `RollingFileAppenderDeserializer`
     ///         * Requires the `rolling_file_appender` feature.
+    ///     * "ring_buffer" -> `RingBufferAppenderDeserializer`
+    ///         * Requires the `ring_buffer_appender` feature.
+    ///     * "sifting" -> `SiftingAppenderDeserializer`
+    ///         * Requires the `sifting_appender` feature.
+    ///     * "socket" -> `SocketAppenderDeserializer`
+    ///         * Requires the `socket_appender` feature.
+    ///     * "gelf" -> `GelfAppenderDeserializer`
+    ///         * Requires the `gelf_appender` feature.
+    ///     * "syslog" -> `SyslogAppenderDeserializer`
+    ///         * Requires the `syslog_appender` feature.
     /// * Encoders
     ///     * "pattern" -> `PatternEncoderDeserializer`
     ///         * Requires the `pattern_encoder` feature.
     ///     * "json" -> `JsonEncoderDeserializer`
     ///         * Requires the `json_encoder` feature.
+    ///     * "ecs" -> `EcsEncoderDeserializer`
+    ///         * Requires the `ecs_encoder` feature.
+    ///     * "gelf" -> `GelfEncoderDeserializer`
+    ///         * Requires the `gelf_encoder` feature.
+    ///     * "logfmt" -> `LogfmtEncoderDeserializer`
+    ///         * Requires the `logfmt_encoder` feature.
     /// * Filters
     ///     * "threshold" -> `ThresholdFilterDeserializer`
     ///         * Requires the `threshold_filter` feature.
@@ -295,11 +462,64 @@ impl Deserializers {
 
     /// Deserializes a value of a specific type and kind.
     pub fn deserialize<T>(&self, kind: &str, config: Value) -> anyhow::Result<Box<T>>
+    where
+        T: Deserializable + ?Sized,
+    {
+        self.get::<T>(kind)?.deserialize(config, self)
+    }
+
+    /// Checks that `config` is a valid configuration for the kind, and those
+    /// of the components nested in it, without deserializing a value from it.
+    ///
+    /// Only the configuration types of the deserializers are checked, so
+    /// errors which would only be found while building a component are not.
+    pub(crate) fn check<T>(&self, kind: &str, config: Value) -> anyhow::Result<()>
+    where
+        T: Deserializable + ?Sized,
+    {
+        self.get::<T>(kind)?.check(config, self)
+    }
+
+    /// Checks the configuration of a component nested in another one, such
+    /// as an appender's encoder, as `Deserialize::check_components` does.
+    ///
+    /// `key` is the key of the component in the configuration of the one it
+    /// is nested in, such as `encoder`, and is reported with any error.
+    pub fn check_nested<T>(&self, key: &str, kind: &str, config: Value) -> anyhow::Result<()>
+    where
+        T: Deserializable + ?Sized,
+    {
+        let (key, result) = match self.get::<T>(kind) {
+            Ok(deserializer) => (key.to_owned(), deserializer.check(config, self)),
+            Err(e) => (format!("{}.kind", key), Err(e)),
+        };
+        result.map_err(|error| match error.downcast::<NestedConfigError>() {
+            Ok(nested) => NestedConfigError {
+                key: format!("{}.{}", key, nested.key),
+                error: nested.error,
+            }
+            .into(),
+            Err(error) => NestedConfigError { key, error }.into(),
+        })
+    }
+
+    /// Returns the names of the appenders which an appender of the kind built
+    /// from `config` forwards records to, as `Deserialize::targets` does.
+    ///
+    /// No names are returned if the kind is unknown or `config` is invalid.
+    pub fn targets(&self, kind: &str, config: Value) -> Vec<String> {
+        match self.get::<dyn Append>(kind) {
+            Ok(deserializer) => deserializer.targets(config, self),
+            Err(_) => vec![],
+        }
+    }
+
+    fn get<T>(&self, kind: &str) -> anyhow::Result<&Arc<dyn ErasedDeserialize<Trait = T>>>
     where
         T: Deserializable + ?Sized,
     {
         match self.0.get::<KeyAdaptor<T>>().and_then(|m| m.get(kind)) {
-            Some(b) => b.deserialize(config, self),
+            Some(b) => Ok(b),
             None => Err(anyhow!(
                 "no {} deserializer for kind `{}` registered",
                 T::name(),
@@ -307,6 +527,112 @@ impl Deserializers {
             )),
         }
     }
+
+    /// Returns a JSON Schema for config files which use these deserializers.
+    ///
+    /// Components are described by the schemas of the deserializers
+    /// registered for them, one per `kind`.
+    ///
+    /// Requires the `schema` feature.
+    #[cfg(feature = "schema")]
+    pub fn json_schema(&self) -> schemars::Schema {
+        let mut generator = schemars::generate::SchemaSettings::draft2020_12()
+            .for_deserialize()
+            .into_generator();
+
+        #[allow(unused_mut)]
+        let mut definitions = vec![
+            self.kinds_schema::<dyn Append>(&mut generator, "Appender", None),
+            self.kinds_schema::<dyn Encode>(&mut generator, "Encoder", Some("pattern")),
+            self.kinds_schema::<dyn Filter>(&mut generator, "Filter", None),
+        ];
+        #[cfg(feature = "rolling_file_appender")]
+        definitions.push(
+            self.kinds_schema::<dyn append::rolling_file::policy::Policy>(
+                &mut generator,
+                "Policy",
+                Some("compound"),
+            ),
+        );
+        #[cfg(feature = "compound_policy")]
+        {
+            use append::rolling_file::policy::compound::{roll::Roll, trigger::Trigger};
+            definitions.push(self.kinds_schema::<dyn Trigger>(&mut generator, "Trigger", None));
+            definitions.push(self.kinds_schema::<dyn Roll>(&mut generator, "Roller", None));
+        }
+
+        // the placeholder schemas of components are kept when a definition of
+        // the same name exists
+        for (name, schema) in definitions {
+            generator
+                .definitions_mut()
+                .insert(name.to_owned(), schema.into());
+        }
+        generator.into_root_schema_for::<RawConfig>()
+    }
+
+    /// Returns the definition `name` of the `T` components, a schema matching
+    /// any registered kind of them.
+    #[cfg(feature = "schema")]
+    fn kinds_schema<T>(
+        &self,
+        generator: &mut schemars::SchemaGenerator,
+        name: &'static str,
+        default_kind: Option<&str>,
+    ) -> (&'static str, schemars::Schema)
+    where
+        T: Deserializable + ?Sized,
+    {
+        let mut kinds = self
+            .0
+            .get::<KeyAdaptor<T>>()
+            .map(|m| m.iter().collect::<Vec<_>>())
+            .unwrap_or_default();
+        kinds.sort_by(|a, b| a.0.cmp(b.0));
+
+        let kinds = kinds
+            .into_iter()
+            .map(|(kind, deserializer)| {
+                let mut schema = deserializer.schema(generator);
+                let object = schema.ensure_object();
+                if object.get("type").is_none() {
+                    object.insert("type".to_owned(), "object".into());
+                }
+                let properties = object
+                    .entry("properties")
+                    .or_insert_with(|| serde_json::Value::Object(Default::default()));
+                if let Some(properties) = properties.as_object_mut() {
+                    properties.insert("kind".to_owned(), serde_json::json!({ "const": kind }));
+                    if name == "Appender" {
+                        properties.insert(
+                            "filters".to_owned(),
+                            generator.subschema_for::<Vec<FilterConfig>>().into(),
+                        );
+                        properties.insert(
+                            "on_error".to_owned(),
+                            generator.subschema_for::<ErrorPolicyConfig>().into(),
+                        );
+                    }
+                }
+                if default_kind != Some(kind) {
+                    let required = object
+                        .entry("required")
+                        .or_insert_with(|| serde_json::Value::Array(vec![]));
+                    if let Some(required) = required.as_array_mut() {
+                        required.insert(0, "kind".into());
+                    }
+                }
+                schema.into()
+            })
+            .collect::<Vec<serde_json::Value>>();
+
+        let schema = if kinds.is_empty() {
+            schemars::Schema::from(false)
+        } else {
+            schemars::json_schema!({ "oneOf": kinds })
+        };
+        (name, schema)
+    }
 }
 
 #[derive(Debug, Error)]
@@ -318,20 +644,50 @@ pub enum DeserializingConfigError {
 }
 
 /// A raw deserializable log4rs configuration.
-#[derive(Clone, Debug, Default, serde::Deserialize)]
+///
+/// It can also be serialized, to write out a configuration loaded from files
+/// or described by `RawConfig::from_config`.
+#[derive(Clone, Debug, Default, serde::Deserialize, serde::Serialize)]
 #[serde(deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct RawConfig {
-    #[serde(deserialize_with = "de_duration", default)]
+    #[serde(default, skip_serializing_if = "Includes::is_empty")]
+    include: Includes,
+
+    #[serde(
+        deserialize_with = "de_duration",
+        serialize_with = "ser_duration",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[cfg_attr(
+        feature = "schema",
+        schemars(schema_with = "crate::config::schema::duration")
+    )]
     refresh_rate: Option<Duration>,
 
     #[serde(default)]
     root: Root,
 
-    #[serde(default)]
+    #[serde(default, serialize_with = "ser_sorted")]
     appenders: HashMap<String, AppenderConfig>,
 
-    #[serde(default)]
+    #[serde(default, serialize_with = "ser_sorted")]
     loggers: HashMap<String, Logger>,
+
+    #[serde(default, skip_serializing_if = "Directives::is_empty")]
+    directives: Directives,
+}
+
+/// An error in the configuration of a component nested in another one, found
+/// by `Deserializers::check_nested`.
+#[derive(Debug, Error)]
+#[error("`{key}`: {error:#}")]
+pub(crate) struct NestedConfigError {
+    // the dotted path of the key the error was found at, relative to the
+    // outermost component
+    pub(crate) key: String,
+    pub(crate) error: anyhow::Error,
 }
 
 #[derive(Debug, Error)]
@@ -342,6 +698,9 @@ impl AppenderErrors {
     pub fn is_empty(&self) -> bool {
         self.0.is_empty()
     }
+    pub(crate) fn errors(&self) -> &[DeserializingConfigError] {
+        &self.0
+    }
     pub fn handle(&mut self) {
         for error in self.0.drain(..) {
             crate::handle_error(&error.into());
@@ -349,17 +708,99 @@ impl AppenderErrors {
     }
 }
 
+/// The appenders built from a previous `RawConfig`, along with the kind and
+/// configuration each was built from.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct AppenderCache(#[debug(skip)] HashMap<String, (String, Value, Arc<dyn Append>)>);
+
+impl AppenderCache {
+    /// Resolves the cached appenders against each other again.
+    ///
+    /// A config built from the cache resolves the appenders it reuses against
+    /// its own, so this undoes that if the config is not applied after all.
+    pub(crate) fn resolve(&self) {
+        let appenders = Appenders::new(
+            self.0
+                .iter()
+                .map(|(name, (_, _, appender))| (&**name, appender)),
+        );
+        for (_, _, appender) in self.0.values() {
+            // appenders which failed to resolve were left out of the config
+            let _ = appender.resolve(&appenders);
+        }
+    }
+}
+
 impl RawConfig {
+    /// Describes `config` as a raw config, which can be serialized and later
+    /// deserialized into an equivalent `Config`.
+    ///
+    /// Every appender and the components it is built from must be able to
+    /// describe their configuration, as the built-in components which have a
+    /// deserializer in the default `Deserializers` do. Appenders with a
+    /// custom `ErrorPolicy` cannot be described.
+    pub fn from_config(config: &config::Config) -> anyhow::Result<RawConfig> {
+        let mut appenders = HashMap::new();
+        for appender in config.appenders() {
+            let raw = describe_appender(
+                appender.appender(),
+                appender.filters(),
+                appender.error_policy(),
+            )
+            .with_context(|| format!("unable to describe appender `{}`", appender.name()))?;
+            appenders.insert(appender.name().to_owned(), raw);
+        }
+        Ok(RawConfig::from_parts(
+            appenders,
+            config.root(),
+            config.loggers(),
+        ))
+    }
+
+    pub(crate) fn from_parts(
+        appenders: HashMap<String, AppenderConfig>,
+        root: &config::Root,
+        loggers: &[config::Logger],
+    ) -> RawConfig {
+        RawConfig {
+            root: Root {
+                level: root.level(),
+                appenders: root.appenders().to_vec(),
+            },
+            appenders,
+            loggers: loggers
+                .iter()
+                .map(|logger| {
+                    let raw = Logger {
+                        level: logger.level(),
+                        appenders: logger.appenders().to_vec(),
+                        additive: logger.additive(),
+                    };
+                    (logger.name().to_owned(), raw)
+                })
+                .collect(),
+            ..RawConfig::default()
+        }
+    }
+
     /// Returns the root.
+    ///
+    /// The config's directives are applied to its level.
     pub fn root(&self) -> config::Root {
-        config::Root::builder()
+        let mut root = config::Root::builder()
             .appenders(self.root.appenders.clone())
-            .build(self.root.level)
+            .build(self.root.level);
+        self.directives.apply_root(&mut root);
+        root
     }
 
     /// Returns the loggers.
+    ///
+    /// The config's directives are applied to their levels, adding any
+    /// loggers they name which are not configured.
     pub fn loggers(&self) -> Vec<config::Logger> {
-        self.loggers
+        let mut loggers = self
+            .loggers
             .iter()
             .map(|(name, logger)| {
                 config::Logger::builder()
@@ -367,7 +808,9 @@ impl RawConfig {
                     .additive(logger.additive)
                     .build(name.clone(), logger.level)
             })
-            .collect()
+            .collect();
+        self.directives.apply_loggers(&mut loggers);
+        loggers
     }
 
     /// Returns the appenders.
@@ -376,74 +819,398 @@ impl RawConfig {
     pub fn appenders_lossy(
         &self,
         deserializers: &Deserializers,
+    ) -> (Vec<config::Appender>, AppenderErrors) {
+        self.appenders_reusing(deserializers, &mut AppenderCache::default())
+    }
+
+    /// Like `appenders_lossy`, but appenders whose kind and configuration are
+    /// unchanged from those in `cache` are reused rather than deserialized
+    /// again. The cache is then updated with this config's appenders.
+    ///
+    /// Filters and error policies are always rebuilt, since they are not part
+    /// of the appender itself.
+    pub(crate) fn appenders_reusing(
+        &self,
+        deserializers: &Deserializers,
+        cache: &mut AppenderCache,
     ) -> (Vec<config::Appender>, AppenderErrors) {
         let mut appenders = vec![];
         let mut errors = vec![];
+        let mut built = HashMap::new();
 
         for (name, appender) in &self.appenders {
             let mut builder = config::Appender::builder();
+            if let Some(on_error) = &appender.on_error {
+                let policy = match on_error {
+                    ErrorPolicyConfig::Report => config::ErrorPolicy::Report,
+                    ErrorPolicyConfig::Ignore => config::ErrorPolicy::Ignore,
+                    ErrorPolicyConfig::LogOnce => config::ErrorPolicy::LogOnce,
+                    ErrorPolicyConfig::RateLimit(interval) => {
+                        config::ErrorPolicy::RateLimit(*interval)
+                    }
+                };
+                builder = builder.error_policy(policy);
+            }
             for filter in &appender.filters {
                 match deserializers.deserialize(&filter.kind, filter.config.clone()) {
                     Ok(filter) => builder = builder.filter(filter),
                     Err(e) => errors.push(DeserializingConfigError::Filter(name.clone(), e)),
                 }
             }
-            match deserializers.deserialize(&appender.kind, appender.config.clone()) {
-                Ok(appender) => appenders.push(builder.build(name.clone(), appender)),
-                Err(e) => errors.push(DeserializingConfigError::Appender(name.clone(), e)),
-            }
+            let instance = match cache.0.remove(name) {
+                Some((kind, config, instance))
+                    if kind == appender.kind && config == appender.config =>
+                {
+                    instance
+                }
+                _ => match deserializers
+                    .deserialize::<dyn Append>(&appender.kind, appender.config.clone())
+                {
+                    Ok(instance) => Arc::from(instance),
+                    Err(e) => {
+                        errors.push(DeserializingConfigError::Appender(name.clone(), e));
+                        continue;
+                    }
+                },
+            };
+            built.insert(
+                name.clone(),
+                (
+                    appender.kind.clone(),
+                    appender.config.clone(),
+                    instance.clone(),
+                ),
+            );
+            appenders.push(builder.build_shared(name.clone(), instance));
         }
+        cache.0 = built;
 
         (appenders, AppenderErrors(errors))
     }
 
+    /// Checks the configurations of the appenders and their filters against
+    /// the deserializers of their kinds, without building them.
+    pub(crate) fn check_appenders(&self, deserializers: &Deserializers) -> AppenderErrors {
+        let mut errors = vec![];
+        for (name, appender) in &self.appenders {
+            for filter in &appender.filters {
+                if let Err(e) =
+                    deserializers.check::<dyn Filter>(&filter.kind, filter.config.clone())
+                {
+                    errors.push(DeserializingConfigError::Filter(name.clone(), e));
+                }
+            }
+            if let Err(e) =
+                deserializers.check::<dyn Append>(&appender.kind, appender.config.clone())
+            {
+                errors.push(DeserializingConfigError::Appender(name.clone(), e));
+            }
+        }
+        AppenderErrors(errors)
+    }
+
     /// Returns the requested refresh rate.
     pub fn refresh_rate(&self) -> Option<Duration> {
         self.refresh_rate
     }
+
+    /// Returns the configurations of the appenders, by name, before they are
+    /// deserialized.
+    pub fn appenders_raw(&self) -> &HashMap<String, AppenderConfig> {
+        &self.appenders
+    }
+
+    /// Returns the paths of the config files this one is layered on top of.
+    ///
+    /// Includes are resolved by the functions loading config files, such as
+    /// `init_file`, and are otherwise ignored.
+    pub fn include(&self) -> &[PathBuf] {
+        self.include.paths()
+    }
+
+    /// Returns the directives applied to the levels of the root and loggers.
+    pub fn directives(&self) -> &Directives {
+        &self.directives
+    }
+
+    /// Sets the directives applied to the levels of the root and loggers,
+    /// replacing any given by the config itself.
+    pub fn set_directives(&mut self, directives: Directives) {
+        self.directives = directives;
+    }
+
+    /// Summarizes the differences between this config and `new`.
+    pub(crate) fn changes(&self, new: &RawConfig) -> ReloadSummary {
+        fn diff<T: PartialEq>(
+            old: &HashMap<String, T>,
+            new: &HashMap<String, T>,
+        ) -> (Vec<String>, Vec<String>, Vec<String>) {
+            let mut added = vec![];
+            let mut changed = vec![];
+            for (name, value) in new {
+                match old.get(name) {
+                    None => added.push(name.clone()),
+                    Some(old) if old != value => changed.push(name.clone()),
+                    Some(_) => {}
+                }
+            }
+            let mut removed = old
+                .keys()
+                .filter(|name| !new.contains_key(*name))
+                .cloned()
+                .collect::<Vec<_>>();
+
+            added.sort();
+            removed.sort();
+            changed.sort();
+            (added, removed, changed)
+        }
+
+        let (appenders_added, appenders_removed, appenders_changed) =
+            diff(&self.appenders, &new.appenders);
+        // loggers are compared with the directives applied, so that those
+        // added or changed by the directives are reported as such
+        let loggers = |config: &RawConfig| {
+            config
+                .loggers()
+                .into_iter()
+                .map(|logger| (logger.name().to_owned(), logger))
+                .collect::<HashMap<_, _>>()
+        };
+        let (loggers_added, loggers_removed, loggers_changed) = diff(&loggers(self), &loggers(new));
+
+        ReloadSummary {
+            appenders_added,
+            appenders_removed,
+            appenders_changed,
+            loggers_added,
+            loggers_removed,
+            loggers_changed,
+            root_changed: self.root.appenders != new.root.appenders
+                || self.root().level() != new.root().level(),
+            refresh_rate_changed: self.refresh_rate != new.refresh_rate,
+        }
+    }
 }
 
-fn de_duration<'de, D>(d: D) -> Result<Option<Duration>, D::Error>
+/// Describes an appender of a `Config`, along with its filters and error
+/// policy.
+pub(crate) fn describe_appender(
+    appender: &dyn Append,
+    filters: &[Box<dyn Filter>],
+    error_policy: &config::ErrorPolicy,
+) -> anyhow::Result<AppenderConfig> {
+    let mut raw = appender
+        .config_value()
+        .ok_or_else(|| anyhow!("the appender cannot describe its configuration"))?
+        .deserialize_into::<AppenderConfig>()?;
+    for filter in filters {
+        let filter = filter
+            .config_value()
+            .ok_or_else(|| anyhow!("a filter cannot describe its configuration"))?;
+        raw.filters.push(filter.deserialize_into()?);
+    }
+    raw.on_error = match error_policy {
+        config::ErrorPolicy::Report => None,
+        config::ErrorPolicy::Ignore => Some(ErrorPolicyConfig::Ignore),
+        config::ErrorPolicy::LogOnce => Some(ErrorPolicyConfig::LogOnce),
+        config::ErrorPolicy::RateLimit(interval) => Some(ErrorPolicyConfig::RateLimit(*interval)),
+        config::ErrorPolicy::Custom(_) => {
+            anyhow::bail!("a custom error policy cannot be described")
+        }
+    };
+    Ok(raw)
+}
+
+/// Returns the configuration of a component of the given kind as it would
+/// appear in a config file, for the `config_value` methods of components.
+#[allow(dead_code)] // unused when no components are enabled
+pub(crate) fn component_config<T>(kind: &str, config: &T) -> Option<Value>
 where
-    D: de::Deserializer<'de>,
+    T: serde::Serialize,
 {
-    struct S(Duration);
+    let mut map = match normalize(serde_value::to_value(config).ok()?) {
+        Value::Map(map) => map,
+        Value::Unit => BTreeMap::new(),
+        _ => return None,
+    };
+    map.insert(
+        Value::String("kind".to_owned()),
+        Value::String(kind.to_owned()),
+    );
+    Some(Value::Map(map))
+}
 
-    impl<'de2> de::Deserialize<'de2> for S {
-        fn deserialize<D>(d: D) -> Result<S, D::Error>
-        where
-            D: de::Deserializer<'de2>,
-        {
-            struct V;
+/// Removes the wrappers serde adds to options and newtypes, and the entries
+/// of maps which are unset, which not every format can represent.
+#[allow(dead_code)]
+fn normalize(value: Value) -> Value {
+    match value {
+        Value::Option(Some(value)) | Value::Newtype(value) => normalize(*value),
+        Value::Map(map) => Value::Map(
+            map.into_iter()
+                .map(|(key, value)| (key, normalize(value)))
+                .filter(|(_, value)| !matches!(value, Value::Option(None) | Value::Unit))
+                .collect(),
+        ),
+        Value::Seq(seq) => Value::Seq(seq.into_iter().map(normalize).collect()),
+        value => value,
+    }
+}
 
-            impl<'de3> de::Visitor<'de3> for V {
-                type Value = S;
+/// Serializes a component as a map of its kind and configuration.
+pub(crate) fn ser_component<S>(kind: &str, config: &Value, s: S) -> Result<S::Ok, S::Error>
+where
+    S: ser::Serializer,
+{
+    let mut map = match config {
+        Value::Map(map) => map.clone(),
+        _ => BTreeMap::new(),
+    };
+    map.insert(
+        Value::String("kind".to_owned()),
+        Value::String(kind.to_owned()),
+    );
+    map.serialize(s)
+}
 
-                fn expecting(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
-                    fmt.write_str("a duration")
-                }
+fn ser_sorted<S, T>(map: &HashMap<String, T>, s: S) -> Result<S::Ok, S::Error>
+where
+    S: ser::Serializer,
+    T: ser::Serialize,
+{
+    map.iter().collect::<BTreeMap<_, _>>().serialize(s)
+}
 
-                fn visit_str<E>(self, v: &str) -> Result<S, E>
-                where
-                    E: de::Error,
-                {
-                    humantime::parse_duration(v).map(S).map_err(E::custom)
-                }
+/// Serializes a level in lowercase, as config files usually spell it.
+pub(crate) fn ser_level<S>(level: &LevelFilter, s: S) -> Result<S::Ok, S::Error>
+where
+    S: ser::Serializer,
+{
+    s.serialize_str(&level.as_str().to_lowercase())
+}
+
+/// The paths given by an `include` key, either a single path or a list.
+#[derive(Clone, Debug, Default, serde::Serialize)]
+pub(crate) struct Includes(Vec<PathBuf>);
+
+impl Includes {
+    pub(crate) fn paths(&self) -> &[PathBuf] {
+        &self.0
+    }
+
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl<'de> de::Deserialize<'de> for Includes {
+    fn deserialize<D>(d: D) -> Result<Includes, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        #[derive(serde::Deserialize)]
+        #[serde(untagged)]
+        enum Paths {
+            One(PathBuf),
+            Many(Vec<PathBuf>),
+        }
+
+        match Paths::deserialize(d)? {
+            Paths::One(path) => Ok(Includes(vec![path])),
+            Paths::Many(paths) => Ok(Includes(paths)),
+        }
+    }
+}
+
+#[cfg(feature = "schema")]
+impl schemars::JsonSchema for Includes {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "Includes".into()
+    }
+
+    fn json_schema(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        schemars::json_schema!({
+            "anyOf": [
+                { "type": "string" },
+                { "type": "array", "items": { "type": "string" } }
+            ]
+        })
+    }
+}
+
+struct HumanDuration(Duration);
+
+impl<'de> de::Deserialize<'de> for HumanDuration {
+    fn deserialize<D>(d: D) -> Result<HumanDuration, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        struct V;
+
+        impl<'de2> de::Visitor<'de2> for V {
+            type Value = HumanDuration;
+
+            fn expecting(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+                fmt.write_str("a duration")
             }
 
-            d.deserialize_any(V)
+            fn visit_str<E>(self, v: &str) -> Result<HumanDuration, E>
+            where
+                E: de::Error,
+            {
+                humantime::parse_duration(v)
+                    .map(HumanDuration)
+                    .map_err(E::custom)
+            }
         }
+
+        d.deserialize_any(V)
+    }
+}
+
+pub(crate) fn de_duration<'de, D>(d: D) -> Result<Option<Duration>, D::Error>
+where
+    D: de::Deserializer<'de>,
+{
+    Option::<HumanDuration>::deserialize(d).map(|r| r.map(|s| s.0))
+}
+
+pub(crate) fn de_required_duration<'de, D>(d: D) -> Result<Duration, D::Error>
+where
+    D: de::Deserializer<'de>,
+{
+    HumanDuration::deserialize(d).map(|s| s.0)
+}
+
+pub(crate) fn ser_duration<S>(d: &Option<Duration>, s: S) -> Result<S::Ok, S::Error>
+where
+    S: ser::Serializer,
+{
+    match d {
+        Some(d) => ser_required_duration(d, s),
+        None => s.serialize_none(),
     }
+}
 
-    Option::<S>::deserialize(d).map(|r| r.map(|s| s.0))
+pub(crate) fn ser_required_duration<S>(d: &Duration, s: S) -> Result<S::Ok, S::Error>
+where
+    S: ser::Serializer,
+{
+    s.serialize_str(&humantime::format_duration(*d).to_string())
 }
 
-#[derive(Clone, Debug, serde::Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
 #[serde(deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 struct Root {
-    #[serde(default = "root_level_default")]
+    #[serde(default = "root_level_default", serialize_with = "ser_level")]
+    #[cfg_attr(
+        feature = "schema",
+        schemars(schema_with = "crate::config::schema::level_filter")
+    )]
     level: LevelFilter,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     appenders: Vec<String>,
 }
 
@@ -461,11 +1228,17 @@ fn root_level_default() -> LevelFilter {
     LevelFilter::Debug
 }
 
-#[derive(serde::Deserialize, Debug, Clone)]
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, PartialEq, Eq)]
 #[serde(deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 struct Logger {
+    #[serde(serialize_with = "ser_level")]
+    #[cfg_attr(
+        feature = "schema",
+        schemars(schema_with = "crate::config::schema::level_filter")
+    )]
     level: LevelFilter,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     appenders: Vec<String>,
     #[serde(default = "logger_additive_default")]
     additive: bool,
@@ -518,12 +1291,358 @@ loggers:
         assert!(errors.is_empty());
     }
 
+    #[test]
+    #[cfg(all(feature = "yaml_format", feature = "console_appender"))]
+    fn error_policy_deserialize() {
+        let cfg = r#"
+appenders:
+    console:
+        kind: console
+        on_error:
+            rate_limit: 1 minute
+    quiet:
+        kind: console
+        on_error: ignore
+"#;
+        let config = ::serde_yaml::from_str::<RawConfig>(cfg).unwrap();
+        assert_eq!(
+            config.appenders["console"].on_error,
+            Some(ErrorPolicyConfig::RateLimit(Duration::from_secs(60)))
+        );
+        let (appenders, errors) = config.appenders_lossy(&Deserializers::new());
+        assert!(errors.is_empty());
+        assert!(matches!(
+            appenders
+                .iter()
+                .find(|a| a.name() == "quiet")
+                .unwrap()
+                .error_policy(),
+            config::ErrorPolicy::Ignore
+        ));
+
+        let cfg = "kind: console\non_error: sometimes\n";
+        assert!(::serde_yaml::from_str::<AppenderConfig>(cfg).is_err());
+    }
+
+    #[test]
+    #[cfg(all(
+        feature = "yaml_format",
+        feature = "console_appender",
+        feature = "threshold_filter"
+    ))]
+    fn reuse_unchanged_appenders() {
+        // the appenders are returned so that they aren't dropped, which could
+        // let a rebuilt appender reuse their address
+        fn build(
+            cfg: &str,
+            cache: &mut AppenderCache,
+        ) -> HashMap<String, (*const dyn Append, config::Appender)> {
+            let config = ::serde_yaml::from_str::<RawConfig>(cfg).unwrap();
+            let (appenders, errors) = config.appenders_reusing(&Deserializers::new(), cache);
+            assert!(errors.is_empty());
+            appenders
+                .into_iter()
+                .map(|a| {
+                    let ptr = a.appender() as *const dyn Append;
+                    (a.name().to_owned(), (ptr, a))
+                })
+                .collect()
+        }
+
+        let mut cache = AppenderCache::default();
+        let first = build(
+            r#"
+appenders:
+    kept:
+        kind: console
+    changed:
+        kind: console
+    removed:
+        kind: console
+"#,
+            &mut cache,
+        );
+        let second = build(
+            r#"
+appenders:
+    kept:
+        kind: console
+        filters:
+        - kind: threshold
+          level: warn
+    changed:
+        kind: console
+        target: stderr
+    added:
+        kind: console
+"#,
+            &mut cache,
+        );
+
+        assert!(std::ptr::addr_eq(first["kept"].0, second["kept"].0));
+        assert!(!std::ptr::addr_eq(first["changed"].0, second["changed"].0));
+        assert_eq!(cache.0.len(), 3);
+        assert!(!cache.0.contains_key("removed"));
+    }
+
     #[test]
     #[cfg(feature = "yaml_format")]
     fn empty() {
         ::serde_yaml::from_str::<RawConfig>("{}").unwrap();
     }
 
+    #[test]
+    #[cfg(feature = "yaml_format")]
+    fn directives() {
+        let cfg = r#"
+root:
+  level: warn
+loggers:
+  foo:
+    level: info
+    additive: false
+directives: error,foo=debug,foo::bar=trace
+"#;
+        let config = ::serde_yaml::from_str::<RawConfig>(cfg).unwrap();
+        assert_eq!(config.root().level(), LevelFilter::Error);
+        let mut loggers = config.loggers();
+        loggers.sort_by(|a, b| a.name().cmp(b.name()));
+        assert_eq!(loggers.len(), 2);
+        assert_eq!(loggers[0].level(), LevelFilter::Debug);
+        assert!(!loggers[0].additive());
+        assert_eq!(loggers[1].name(), "foo::bar");
+        assert_eq!(loggers[1].level(), LevelFilter::Trace);
+
+        let config = ::serde_yaml::from_str::<RawConfig>(
+            "directives:\n  env: LOG4RS_TEST_UNSET_DIRECTIVES\n",
+        )
+        .unwrap();
+        assert!(config.directives().is_empty());
+
+        assert!(::serde_yaml::from_str::<RawConfig>("directives: foo=loud").is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "yaml_format")]
+    fn directive_changes() {
+        let old = ::serde_yaml::from_str::<RawConfig>(
+            "root:\n  level: warn\nloggers:\n  foo:\n    level: info\n",
+        )
+        .unwrap();
+        let new = ::serde_yaml::from_str::<RawConfig>(
+            "root:\n  level: warn\nloggers:\n  foo:\n    level: info\n\
+             directives: foo=debug,bar=trace\n",
+        )
+        .unwrap();
+
+        let summary = old.changes(&new);
+        assert_eq!(summary.loggers_added(), ["bar"]);
+        assert_eq!(summary.loggers_changed(), ["foo"]);
+        assert!(!summary.root_changed());
+
+        let summary = new.changes(&old);
+        assert_eq!(summary.loggers_removed(), ["bar"]);
+        assert_eq!(summary.loggers_changed(), ["foo"]);
+
+        let same = ::serde_yaml::from_str::<RawConfig>(
+            "root:\n  level: error\nloggers:\n  foo:\n    level: info\n",
+        )
+        .unwrap();
+        let mut old = same.clone();
+        old.set_directives(Directives::parse("warn").unwrap());
+        assert!(old.changes(&same).root_changed());
+        old.set_directives(Directives::parse("error").unwrap());
+        assert!(old.changes(&same).is_empty());
+    }
+
+    #[test]
+    #[cfg(all(
+        feature = "yaml_format",
+        feature = "console_appender",
+        feature = "file_appender",
+        feature = "rolling_file_appender",
+        feature = "compound_policy",
+        feature = "size_trigger",
+        feature = "fixed_window_roller",
+        feature = "json_encoder",
+        feature = "threshold_filter"
+    ))]
+    fn serialize_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = format!(
+            r#"
+appenders:
+  stdout:
+    kind: console
+    target: stderr
+    encoder:
+      kind: pattern
+      pattern: "{{l}} - {{m}}{{n}}"
+    filters:
+      - kind: threshold
+        level: info
+  file:
+    kind: file
+    path: {0}/app.log
+    encoder:
+      kind: json
+    on_error:
+      rate_limit: 30 seconds
+  rolling:
+    kind: rolling_file
+    path: {0}/rolling.log
+    policy:
+      trigger:
+        kind: size
+        limit: 5 mb
+      roller:
+        kind: fixed_window
+        pattern: "{0}/rolling.{{}}.log"
+        count: 3
+root:
+  level: warn
+  appenders: [stdout]
+loggers:
+  app:
+    level: debug
+    appenders: [file, rolling]
+    additive: false
+"#,
+            dir.path().display()
+        );
+
+        let describe = |raw: RawConfig| {
+            let logger = config::create_raw_config(raw).unwrap();
+            logger.handle().raw_config().unwrap()
+        };
+
+        let raw = describe(::serde_yaml::from_str::<RawConfig>(&cfg).unwrap());
+        let yaml = ::serde_yaml::to_string(&raw).unwrap();
+        assert!(yaml.contains("rate_limit: 30s"), "{}", yaml);
+        assert!(yaml.contains("limit: 5242880"), "{}", yaml);
+        assert!(yaml.contains("level: warn"), "{}", yaml);
+
+        let reloaded = describe(::serde_yaml::from_str::<RawConfig>(&yaml).unwrap());
+        assert_eq!(::serde_yaml::to_string(&reloaded).unwrap(), yaml);
+
+        #[cfg(feature = "json_format")]
+        {
+            let json = ::serde_json::to_string(&raw).unwrap();
+            let reloaded = describe(::serde_json::from_str::<RawConfig>(&json).unwrap());
+            assert_eq!(::serde_yaml::to_string(&reloaded).unwrap(), yaml);
+        }
+
+        #[cfg(feature = "toml_format")]
+        {
+            let toml = ::toml::to_string(&raw).unwrap();
+            let reloaded = describe(::toml::from_str::<RawConfig>(&toml).unwrap());
+            assert_eq!(::serde_yaml::to_string(&reloaded).unwrap(), yaml);
+        }
+
+        // levels overridden through a handle are part of the description
+        let logger = config::create_raw_config(raw).unwrap();
+        logger
+            .handle()
+            .set_logger_level("app::db", LevelFilter::Trace)
+            .unwrap();
+        let raw = logger.handle().raw_config().unwrap();
+        let db = raw
+            .loggers()
+            .into_iter()
+            .find(|l| l.name() == "app::db")
+            .unwrap();
+        assert_eq!(db.level(), LevelFilter::Trace);
+    }
+
+    #[test]
+    fn describe_custom_appender() {
+        #[derive(derive_more::Debug)]
+        struct Custom;
+
+        impl Append for Custom {
+            fn append(&self, _: &log::Record<'_>) -> anyhow::Result<()> {
+                Ok(())
+            }
+
+            fn flush(&self) {}
+        }
+
+        let config = config::Config::builder()
+            .appender(config::Appender::builder().build("custom", Box::new(Custom)))
+            .build(config::Root::builder().build(LevelFilter::Info))
+            .unwrap();
+        let err = RawConfig::from_config(&config).unwrap_err();
+        assert_eq!(
+            format!("{:#}", err),
+            "unable to describe appender `custom`: the appender cannot describe its configuration"
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "schema", feature = "all_components", feature = "log_kv"))]
+    fn schema_is_up_to_date() {
+        // set LOG4RS_UPDATE_SCHEMA to regenerate the published schema
+        let path = "docs/log4rs.schema.json";
+        let schema = Deserializers::default().json_schema();
+        if std::env::var_os("LOG4RS_UPDATE_SCHEMA").is_some() {
+            let json = serde_json::to_string_pretty(&schema).unwrap();
+            fs::write(path, json + "\n").unwrap();
+        }
+
+        let published =
+            serde_json::from_str::<serde_json::Value>(&fs::read_to_string(path).unwrap()).unwrap();
+        assert!(
+            published == *schema.as_value(),
+            "{} is out of date, rerun the test with LOG4RS_UPDATE_SCHEMA=1",
+            path
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "schema", feature = "pattern_encoder"))]
+    fn schema_kinds() {
+        struct CustomDeserializer;
+
+        impl Deserialize for CustomDeserializer {
+            type Trait = dyn Append;
+            type Config = ();
+
+            fn deserialize(&self, _: (), _: &Deserializers) -> anyhow::Result<Box<dyn Append>> {
+                unimplemented!()
+            }
+
+            fn schema(&self, _: &mut schemars::SchemaGenerator) -> schemars::Schema {
+                schemars::json_schema!({
+                    "type": "object",
+                    "properties": { "url": { "type": "string" } },
+                    "required": ["url"]
+                })
+            }
+        }
+
+        let mut deserializers = Deserializers::empty();
+        deserializers.insert("custom", CustomDeserializer);
+        deserializers.insert(
+            "pattern",
+            crate::encode::pattern::PatternEncoderDeserializer,
+        );
+        let schema = deserializers.json_schema();
+        let defs = &schema.as_value()["$defs"];
+
+        let appenders = defs["Appender"]["oneOf"].as_array().unwrap();
+        assert_eq!(appenders.len(), 1);
+        assert_eq!(appenders[0]["properties"]["kind"]["const"], "custom");
+        assert_eq!(appenders[0]["properties"]["url"]["type"], "string");
+        assert!(appenders[0]["properties"]["filters"].is_object());
+        assert_eq!(appenders[0]["required"], serde_json::json!(["kind", "url"]));
+
+        let encoders = defs["Encoder"]["oneOf"].as_array().unwrap();
+        assert_eq!(encoders[0]["properties"]["kind"]["const"], "pattern");
+        assert!(encoders[0].get("required").is_none());
+
+        assert_eq!(defs["Filter"], false);
+    }
+
     #[cfg(windows)]
     #[allow(dead_code)]
     const LINE_ENDING: &'static str = "\r\n";
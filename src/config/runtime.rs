@@ -1,10 +1,19 @@
 //! log4rs configuration
 
+use anyhow::anyhow;
+use derive_more::Debug;
 use log::LevelFilter;
-use std::collections::HashSet;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
 use thiserror::Error;
 
-use crate::{append::Append, filter::Filter};
+use crate::{
+    append::{Append, Appenders},
+    filter::Filter,
+};
 
 /// A log4rs configuration.
 #[derive(Debug)]
@@ -43,6 +52,10 @@ impl Config {
         &self.loggers
     }
 
+    pub(crate) fn loggers_mut(&mut self) -> &mut Vec<Logger> {
+        &mut self.loggers
+    }
+
     pub(crate) fn unpack(self) -> (Vec<Appender>, Root, Vec<Logger>) {
         let Config {
             appenders,
@@ -110,6 +123,48 @@ impl ConfigBuilder {
             }
         }
 
+        let mut unresolved = vec![];
+        {
+            let targets = ok_appenders
+                .iter()
+                .map(|a| (&*a.name, a.appender.targets()))
+                .collect::<HashMap<_, _>>();
+            for appender in &ok_appenders {
+                let mut through = vec![];
+                if forwards_to(&appender.name, &appender.name, &targets, &mut through) {
+                    let err = if through.is_empty() {
+                        anyhow!("appender forwards records to itself")
+                    } else {
+                        anyhow!(
+                            "appender forwards records back to itself through {}",
+                            through
+                                .iter()
+                                .map(|name| format!("`{}`", name))
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        )
+                    };
+                    unresolved.push(appender.name.clone());
+                    errors.push(ConfigError::UnresolvedAppender(appender.name.clone(), err));
+                }
+            }
+        }
+        // appenders forwarding records to those in a cycle then fail to
+        // resolve
+        ok_appenders.retain(|a| !unresolved.contains(&a.name));
+
+        {
+            let appenders = Appenders::new(ok_appenders.iter().map(|a| (&*a.name, &a.appender)));
+            for appender in &ok_appenders {
+                if let Err(err) = appender.appender.resolve(&appenders) {
+                    unresolved.push(appender.name.clone());
+                    errors.push(ConfigError::UnresolvedAppender(appender.name.clone(), err));
+                }
+            }
+        }
+        ok_appenders.retain(|a| !unresolved.contains(&a.name));
+        appender_names.retain(|name| !unresolved.contains(name));
+
         let mut ok_root_appenders = vec![];
         for appender in root.appenders {
             if appender_names.contains(&appender) {
@@ -166,6 +221,32 @@ impl ConfigBuilder {
     }
 }
 
+/// Returns whether the records `from` forwards reach the appender `to`,
+/// adding the appenders in between to `through`.
+fn forwards_to<'a>(
+    from: &str,
+    to: &str,
+    targets: &HashMap<&str, Vec<&'a str>>,
+    through: &mut Vec<&'a str>,
+) -> bool {
+    for &target in targets.get(from).into_iter().flatten() {
+        if target == to {
+            return true;
+        }
+        // a cycle which doesn't include `to` is reported for its own
+        // appenders
+        if through.contains(&target) {
+            continue;
+        }
+        through.push(target);
+        if forwards_to(target, to, targets, through) {
+            return true;
+        }
+        through.pop();
+    }
+    false
+}
+
 /// Configuration for the root logger.
 #[derive(Debug)]
 pub struct Root {
@@ -230,18 +311,40 @@ impl RootBuilder {
     }
 }
 
+/// How errors returned by an appender are reported.
+#[derive(Clone, Debug, Default)]
+pub enum ErrorPolicy {
+    /// Every error is passed to the logger's error handler.
+    #[default]
+    Report,
+    /// Errors are not reported.
+    Ignore,
+    /// Only the first error is passed to the logger's error handler.
+    LogOnce,
+    /// At most one error per interval is passed to the logger's error handler,
+    /// noting how many were suppressed since the previous one.
+    RateLimit(Duration),
+    /// Every error is passed to the function instead of the logger's error
+    /// handler.
+    Custom(#[debug("..")] Arc<dyn Fn(&anyhow::Error) + Send + Sync>),
+}
+
 /// Configuration for an appender.
 #[derive(Debug)]
 pub struct Appender {
     name: String,
-    appender: Box<dyn Append>,
+    appender: Arc<dyn Append>,
     filters: Vec<Box<dyn Filter>>,
+    error_policy: ErrorPolicy,
 }
 
 impl Appender {
     /// Creates a new `AppenderBuilder` with the specified name and `Append` trait object.
     pub fn builder() -> AppenderBuilder {
-        AppenderBuilder { filters: vec![] }
+        AppenderBuilder {
+            filters: vec![],
+            error_policy: ErrorPolicy::default(),
+        }
     }
 
     /// Returns the name of the appender.
@@ -259,13 +362,20 @@ impl Appender {
         &self.filters
     }
 
-    pub(crate) fn unpack(self) -> (String, Box<dyn Append>, Vec<Box<dyn Filter>>) {
+    /// Returns how errors returned by the appender are reported.
+    pub fn error_policy(&self) -> &ErrorPolicy {
+        &self.error_policy
+    }
+
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn unpack(self) -> (String, Arc<dyn Append>, Vec<Box<dyn Filter>>, ErrorPolicy) {
         let Appender {
             name,
             appender,
             filters,
+            error_policy,
         } = self;
-        (name, appender, filters)
+        (name, appender, filters, error_policy)
     }
 }
 
@@ -273,6 +383,7 @@ impl Appender {
 #[derive(Debug)]
 pub struct AppenderBuilder {
     filters: Vec<Box<dyn Filter>>,
+    error_policy: ErrorPolicy,
 }
 
 impl AppenderBuilder {
@@ -291,8 +402,25 @@ impl AppenderBuilder {
         self
     }
 
+    /// Sets how errors returned by the appender are reported.
+    ///
+    /// Defaults to `ErrorPolicy::Report`.
+    pub fn error_policy(mut self, error_policy: ErrorPolicy) -> AppenderBuilder {
+        self.error_policy = error_policy;
+        self
+    }
+
     /// Consumes the `AppenderBuilder`, returning the `Appender`.
     pub fn build<T>(self, name: T, appender: Box<dyn Append>) -> Appender
+    where
+        T: Into<String>,
+    {
+        self.build_shared(name, Arc::from(appender))
+    }
+
+    /// Like `build`, but for an appender which may also be part of another
+    /// configuration.
+    pub(crate) fn build_shared<T>(self, name: T, appender: Arc<dyn Append>) -> Appender
     where
         T: Into<String>,
     {
@@ -300,6 +428,7 @@ impl AppenderBuilder {
             name: name.into(),
             appender,
             filters: self.filters,
+            error_policy: self.error_policy,
         }
     }
 }
@@ -343,6 +472,10 @@ impl Logger {
     pub fn additive(&self) -> bool {
         self.additive
     }
+
+    pub(crate) fn set_level(&mut self, level: LevelFilter) {
+        self.level = level;
+    }
 }
 
 /// A builder for `Logger`s.
@@ -392,7 +525,7 @@ impl LoggerBuilder {
     }
 }
 
-fn check_logger_name(name: &str) -> Result<(), ConfigError> {
+pub(crate) fn check_logger_name(name: &str) -> Result<(), ConfigError> {
     if name.is_empty() {
         return Err(ConfigError::InvalidLoggerName(name.to_owned()));
     }
@@ -460,6 +593,10 @@ pub enum ConfigError {
     #[error("Invalid logger name `{0}`")]
     InvalidLoggerName(String),
 
+    /// An appender failed to look up the appenders it refers to.
+    #[error("Unable to resolve appender `{0}`: {1}")]
+    UnresolvedAppender(String, anyhow::Error),
+
     #[doc(hidden)]
     #[error("Reserved for future use")]
     __Extensible,
@@ -0,0 +1,66 @@
+//! JSON Schema fragments shared by the configurations of components.
+//!
+//! Requires the `schema` feature.
+
+use schemars::{json_schema, Schema, SchemaGenerator};
+
+/// The schema of a component selected by its `kind`, such as an encoder.
+///
+/// `Deserializers::json_schema` replaces it with the schemas of the kinds
+/// registered for the component.
+pub(crate) fn component(_: &mut SchemaGenerator) -> Schema {
+    json_schema!({
+        "type": "object",
+        "properties": {
+            "kind": { "type": "string" }
+        }
+    })
+}
+
+/// The schema of a duration, as parsed by `humantime`.
+pub(crate) fn duration(_: &mut SchemaGenerator) -> Schema {
+    json_schema!({
+        "type": "string",
+        "description": "A duration such as `30 seconds` or `1h 30m`."
+    })
+}
+
+/// The schema of a `LevelFilter`.
+pub(crate) fn level_filter(_: &mut SchemaGenerator) -> Schema {
+    levels(&["off", "error", "warn", "info", "debug", "trace"])
+}
+
+/// The schema of a `Level`.
+#[allow(dead_code)] // unused when the ring buffer appender is not enabled
+pub(crate) fn level(_: &mut SchemaGenerator) -> Schema {
+    levels(&["error", "warn", "info", "debug", "trace"])
+}
+
+// Levels are parsed case insensitively, but are usually written in lower or
+// upper case.
+fn levels(levels: &[&str]) -> Schema {
+    let levels = levels
+        .iter()
+        .map(|level| level.to_string())
+        .chain(levels.iter().map(|level| level.to_uppercase()))
+        .collect::<Vec<_>>();
+    json_schema!({
+        "type": "string",
+        "enum": levels
+    })
+}
+
+/// The schema of a size in bytes, either a number or a string with a unit
+/// such as `10 mb`.
+#[allow(dead_code)] // unused when the size trigger is not enabled
+pub(crate) fn size(_: &mut SchemaGenerator) -> Schema {
+    json_schema!({
+        "anyOf": [
+            { "type": "integer", "minimum": 0 },
+            {
+                "type": "string",
+                "pattern": "^[0-9]+(\\s*([bB]|[kKmMgGtT][iI]?[bB])\\s*)?$"
+            }
+        ]
+    })
+}
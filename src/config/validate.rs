@@ -0,0 +1,557 @@
+//! Checking config files without initializing a logger.
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use serde_value::Value;
+
+use super::{
+    file::{self, FileContext, Layer},
+    interpolate::deserialize_into,
+    raw::{DeserializingConfigError, NestedConfigError},
+    runtime::ConfigError,
+    Config, Deserializers, RawConfig,
+};
+
+/// A problem found in a config file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    file: Option<PathBuf>,
+    key: Option<String>,
+    message: String,
+}
+
+impl Diagnostic {
+    /// Returns the file the problem was found in, if known.
+    pub fn file(&self) -> Option<&Path> {
+        self.file.as_deref()
+    }
+
+    /// Returns the key the problem was found at, if known, as a dotted path
+    /// such as `appenders.stdout.encoder.kind`.
+    pub fn key(&self) -> Option<&str> {
+        self.key.as_deref()
+    }
+
+    /// Returns a description of the problem.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(fmt, "{}: ", file.display())?;
+        }
+        if let Some(key) = &self.key {
+            write!(fmt, "`{}`: ", key)?;
+        }
+        fmt.write_str(&self.message)
+    }
+}
+
+/// How thoroughly config files are checked.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ValidationMode {
+    /// Checks the configuration of each appender and filter, and of the
+    /// components nested in them such as encoders, against the configuration
+    /// type of its kind, without building it.
+    ///
+    /// The checks made while building a component are not covered.
+    #[default]
+    Parse,
+    /// Builds every appender and filter, checking the configuration as
+    /// thoroughly as `init_file` does.
+    ///
+    /// Building components has side effects: a file appender, for example,
+    /// creates the file it logs to, and a socket appender connects.
+    Build,
+}
+
+/// Checks a config file, returning every problem found in it.
+///
+/// An empty list is returned if the file is valid. Components are not built,
+/// as described by `ValidationMode::Parse`.
+pub fn validate_config_file<P>(path: P, deserializers: &Deserializers) -> Vec<Diagnostic>
+where
+    P: AsRef<Path>,
+{
+    validate_config_files(&[path], deserializers)
+}
+
+/// Checks several config files layered on top of each other, as `init_files`
+/// does, returning every problem found in them.
+///
+/// Components are not built, as described by `ValidationMode::Parse`.
+pub fn validate_config_files<P>(paths: &[P], deserializers: &Deserializers) -> Vec<Diagnostic>
+where
+    P: AsRef<Path>,
+{
+    validate_config_files_with_mode(paths, deserializers, ValidationMode::Parse)
+}
+
+/// Checks several config files layered on top of each other, as
+/// `validate_config_files` does, in the given mode.
+pub fn validate_config_files_with_mode<P>(
+    paths: &[P],
+    deserializers: &Deserializers,
+    mode: ValidationMode,
+) -> Vec<Diagnostic>
+where
+    P: AsRef<Path>,
+{
+    let paths = paths
+        .iter()
+        .map(|path| path.as_ref().to_path_buf())
+        .collect::<Vec<_>>();
+    let layers = match file::load_layers(&paths) {
+        Ok(layers) => layers,
+        Err(e) => return vec![load_diagnostic(&e)],
+    };
+
+    let mut validator = Validator {
+        layers: &layers,
+        diagnostics: vec![],
+    };
+    let merged = file::merge_layers(layers.iter().map(|layer| layer.value.clone()));
+    if let Some(config) = validator.raw_config(merged) {
+        validator.components(&config, deserializers, mode);
+    }
+    validator.diagnostics
+}
+
+fn load_diagnostic(e: &anyhow::Error) -> Diagnostic {
+    match e.downcast_ref::<FileContext>() {
+        Some(context) => Diagnostic {
+            file: Some(context.path.clone()),
+            key: context.key.clone(),
+            message: e
+                .chain()
+                .skip(1)
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(": "),
+        },
+        None => Diagnostic {
+            file: None,
+            key: None,
+            message: format!("{:#}", e),
+        },
+    }
+}
+
+struct Validator<'a> {
+    layers: &'a [Layer],
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Validator<'_> {
+    fn report(&mut self, key: Option<String>, message: String) {
+        let file = key
+            .as_deref()
+            .and_then(|key| self.origin(key))
+            .or_else(|| self.layers.last().map(|layer| layer.path.clone()));
+        self.diagnostics.push(Diagnostic { file, key, message });
+    }
+
+    /// Returns the last file to set the value at `key`, or the longest
+    /// prefix of it which any file sets.
+    fn origin(&self, key: &str) -> Option<PathBuf> {
+        let key = key.split('.').collect::<Vec<_>>();
+        (1..=key.len()).rev().find_map(|len| {
+            self.layers
+                .iter()
+                .rev()
+                .find(|layer| lookup(&layer.value, &key[..len]).is_some())
+                .map(|layer| layer.path.clone())
+        })
+    }
+
+    /// Deserializes each entry of the config separately, so that every
+    /// malformed one is reported, and returns a config of those which are
+    /// not.
+    fn raw_config(&mut self, merged: Value) -> Option<RawConfig> {
+        let map = match merged {
+            Value::Map(map) => map,
+            _ => {
+                self.report(None, "the config must be a map".to_owned());
+                return None;
+            }
+        };
+
+        let mut ok = BTreeMap::new();
+        for (section, value) in map {
+            let name = key_name(&section);
+            match value {
+                Value::Map(entries) if name == "appenders" || name == "loggers" => {
+                    let mut ok_entries = BTreeMap::new();
+                    for (entry, value) in entries {
+                        let key = format!("{}.{}", name, key_name(&entry));
+                        let mut single = BTreeMap::new();
+                        single.insert(entry, value);
+                        let single = Value::Map(single);
+                        if self.check(&section, &single, key) {
+                            if let Value::Map(single) = single {
+                                ok_entries.extend(single);
+                            }
+                        }
+                    }
+                    ok.insert(section, Value::Map(ok_entries));
+                }
+                value => {
+                    if self.check(&section, &value, name) {
+                        ok.insert(section, value);
+                    }
+                }
+            }
+        }
+
+        match deserialize_into::<RawConfig>(Value::Map(ok)) {
+            Ok(config) => Some(config),
+            Err(e) => {
+                self.report(None, e.to_string());
+                None
+            }
+        }
+    }
+
+    fn check(&mut self, section: &Value, value: &Value, key: String) -> bool {
+        let mut single = BTreeMap::new();
+        single.insert(section.clone(), value.clone());
+        match deserialize_into::<RawConfig>(Value::Map(single)) {
+            Ok(_) => true,
+            Err(e) => {
+                self.report(Some(key), e.to_string());
+                false
+            }
+        }
+    }
+
+    fn components(
+        &mut self,
+        config: &RawConfig,
+        deserializers: &Deserializers,
+        mode: ValidationMode,
+    ) {
+        let (appenders, errors) = match mode {
+            ValidationMode::Parse => (vec![], config.check_appenders(deserializers)),
+            ValidationMode::Build => config.appenders_lossy(deserializers),
+        };
+        let mut errors = errors
+            .errors()
+            .iter()
+            .map(|error| {
+                let (key, e) = match error {
+                    DeserializingConfigError::Appender(name, e) => {
+                        (format!("appenders.{}", name), e)
+                    }
+                    DeserializingConfigError::Filter(name, e) => {
+                        (format!("appenders.{}.filters", name), e)
+                    }
+                };
+                match e.downcast_ref::<NestedConfigError>() {
+                    Some(nested) => (
+                        format!("{}.{}", key, nested.key),
+                        format!("{:#}", nested.error),
+                    ),
+                    None => (key, format!("{:#}", e)),
+                }
+            })
+            .collect::<Vec<_>>();
+        errors.sort();
+        for (key, message) in errors {
+            self.report(Some(key), message);
+        }
+
+        let root = config.root();
+        let mut loggers = config.loggers();
+        loggers.sort_by(|a, b| a.name().cmp(b.name()));
+        let defined = config.appenders_raw();
+        for appender in root.appenders() {
+            if !defined.contains_key(appender) {
+                self.report(
+                    Some("root.appenders".to_owned()),
+                    format!("reference to nonexistent appender `{}`", appender),
+                );
+            }
+        }
+        for logger in &loggers {
+            for appender in logger.appenders() {
+                if !defined.contains_key(appender) {
+                    self.report(
+                        Some(format!("loggers.{}.appenders", logger.name())),
+                        format!("reference to nonexistent appender `{}`", appender),
+                    );
+                }
+            }
+        }
+
+        let (_, errors) = Config::builder()
+            .appenders(appenders)
+            .loggers(loggers)
+            .build_lossy(root);
+        for error in errors.errors() {
+            let key = match error {
+                // reported above, or an appender which wasn't built
+                ConfigError::NonexistentAppender(_) => continue,
+                ConfigError::UnresolvedAppender(name, _) => Some(format!("appenders.{}", name)),
+                ConfigError::InvalidLoggerName(name) => Some(format!("loggers.{}", name)),
+                _ => None,
+            };
+            self.report(key, error.to_string());
+        }
+    }
+}
+
+fn key_name(key: &Value) -> String {
+    match key {
+        Value::String(s) => s.clone(),
+        key => format!("{:?}", key),
+    }
+}
+
+fn lookup<'a>(value: &'a Value, key: &[&str]) -> Option<&'a Value> {
+    let (first, rest) = match key.split_first() {
+        Some(split) => split,
+        None => return Some(value),
+    };
+    match value {
+        Value::Map(map) => map
+            .get(&Value::String((*first).to_owned()))
+            .and_then(|value| lookup(value, rest)),
+        Value::Seq(seq) => first
+            .parse::<usize>()
+            .ok()
+            .and_then(|i| seq.get(i))
+            .and_then(|value| lookup(value, rest)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+#[cfg(all(
+    feature = "yaml_format",
+    feature = "console_appender",
+    feature = "threshold_filter"
+))]
+mod test {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn valid() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log4rs.yaml");
+        fs::write(
+            &path,
+            "appenders:\n  stdout:\n    kind: console\nroot:\n  appenders: [stdout]\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            validate_config_file(&path, &Deserializers::default()),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn diagnostics() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join("base.yaml");
+        let path = dir.path().join("log4rs.yaml");
+        fs::write(
+            &base,
+            r#"
+appenders:
+  stdout:
+    kind: console
+    filters:
+      - kind: threshold
+        level: loud
+  bad:
+    kind: consol
+loggers:
+  app:
+    level: info
+    appenders: [missing]
+"#,
+        )
+        .unwrap();
+        fs::write(
+            &path,
+            r#"
+include: base.yaml
+refresh: 30 seconds
+root:
+  level: warn
+  appenders: [stdout, bad, nowhere]
+loggers:
+  "a:b":
+    level: info
+  db:
+    level: chatty
+"#,
+        )
+        .unwrap();
+
+        let diagnostics = validate_config_file(&path, &Deserializers::default())
+            .into_iter()
+            .map(|d| {
+                (
+                    d.file()
+                        .unwrap()
+                        .file_name()
+                        .unwrap()
+                        .to_str()
+                        .unwrap()
+                        .to_owned(),
+                    d.key().unwrap().to_owned(),
+                )
+            })
+            .collect::<Vec<_>>();
+        let expected = [
+            ("log4rs.yaml", "loggers.db"),
+            ("log4rs.yaml", "refresh"),
+            ("base.yaml", "appenders.bad"),
+            ("base.yaml", "appenders.stdout.filters"),
+            ("log4rs.yaml", "root.appenders"),
+            ("base.yaml", "loggers.app.appenders"),
+            ("log4rs.yaml", "loggers.a:b"),
+        ];
+        assert_eq!(
+            diagnostics,
+            expected
+                .iter()
+                .map(|(file, key)| (file.to_string(), key.to_string()))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "file_appender")]
+    fn modes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log4rs.yaml");
+        let log = dir.path().join("app.log");
+        let blocker = dir.path().join("blocker");
+        fs::write(&blocker, "").unwrap();
+        fs::write(
+            &path,
+            format!(
+                "appenders:\n  file:\n    kind: file\n    path: {}\n  blocked:\n    kind: file\n    path: {}/app.log\n  typo:\n    kind: file\n    path: {}\n    encoder:\n      kind: nonexistent\n  bad:\n    kind: file\n",
+                log.display(),
+                blocker.display(),
+                log.display()
+            ),
+        )
+        .unwrap();
+
+        let keys = |diagnostics: Vec<Diagnostic>| {
+            diagnostics
+                .iter()
+                .map(|d| d.key().unwrap().to_owned())
+                .collect::<Vec<_>>()
+        };
+        let diagnostics = validate_config_file(&path, &Deserializers::default());
+        assert_eq!(
+            keys(diagnostics),
+            ["appenders.bad", "appenders.typo.encoder.kind"]
+        );
+        assert!(!log.exists());
+
+        let diagnostics = validate_config_files_with_mode(
+            &[&path],
+            &Deserializers::default(),
+            ValidationMode::Build,
+        );
+        assert_eq!(
+            keys(diagnostics),
+            ["appenders.bad", "appenders.blocked", "appenders.typo"]
+        );
+    }
+
+    #[test]
+    #[cfg(all(
+        feature = "async_appender",
+        feature = "failover_appender",
+        feature = "rolling_file_appender",
+        feature = "compound_policy"
+    ))]
+    fn nested() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log4rs.yaml");
+        fs::write(
+            &path,
+            r#"
+appenders:
+  async:
+    kind: async
+    appender:
+      kind: console
+      encoder:
+        kind: jsn
+  failover:
+    kind: failover
+    appenders:
+      - async
+      - kind: console
+        filters:
+          - kind: threshold
+            level: loud
+  rolling:
+    kind: rolling_file
+    path: app.log
+    policy:
+      trigger:
+        kind: sise
+      roller:
+        kind: delete
+"#,
+        )
+        .unwrap();
+
+        let diagnostics = validate_config_file(&path, &Deserializers::default())
+            .into_iter()
+            .map(|d| (d.key().unwrap().to_owned(), d.message().to_owned()))
+            .collect::<Vec<_>>();
+        assert_eq!(diagnostics.len(), 3);
+        assert_eq!(
+            diagnostics[0],
+            (
+                "appenders.async.appender.encoder.kind".to_owned(),
+                "no encoder deserializer for kind `jsn` registered".to_owned()
+            )
+        );
+        assert_eq!(diagnostics[1].0, "appenders.failover.appenders.1.filters.0");
+        assert_eq!(
+            diagnostics[2],
+            (
+                "appenders.rolling.policy.trigger.kind".to_owned(),
+                "no trigger deserializer for kind `sise` registered".to_owned()
+            )
+        );
+    }
+
+    #[test]
+    fn load_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log4rs.yaml");
+        fs::write(&path, "root:\n  level: ${LOG4RS_TEST_UNSET}\n").unwrap();
+
+        let diagnostics = validate_config_file(&path, &Deserializers::default());
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].file(), Some(&*path));
+        assert_eq!(diagnostics[0].key(), Some("root.level"));
+
+        let missing = dir.path().join("missing.yaml");
+        let diagnostics = validate_config_file(&missing, &Deserializers::default());
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].file(), Some(&*missing));
+        assert_eq!(diagnostics[0].key(), None);
+    }
+}
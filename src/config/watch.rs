@@ -0,0 +1,220 @@
+//! Event driven watching of config files.
+//!
+//! Requires the `config_watch` feature.
+
+use std::{
+    collections::HashSet,
+    fs,
+    path::{self, Path, PathBuf},
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    time::{Duration, Instant},
+};
+
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+
+// Editors and ConfigMap updates touch several files in quick succession, so
+// the events are left to settle before the config is reloaded.
+const SETTLE_TIME: Duration = Duration::from_millis(50);
+
+// The same limit as Linux's path resolution.
+const MAX_SYMLINKS: usize = 40;
+
+/// Watches the directories containing config files and the symlinks leading
+/// to them, so that replacing a file by rename, or swapping a symlink along
+/// the way as Kubernetes does when a ConfigMap is updated, is seen.
+pub(crate) struct ConfigWatcher {
+    files: Vec<PathBuf>,
+    watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<Event>>,
+    paths: HashSet<PathBuf>,
+    dirs: HashSet<PathBuf>,
+}
+
+impl ConfigWatcher {
+    pub(crate) fn new(files: &[PathBuf]) -> anyhow::Result<ConfigWatcher> {
+        let (sender, events) = mpsc::channel();
+        let mut watcher = ConfigWatcher {
+            files: absolute(files)?,
+            watcher: notify::recommended_watcher(sender)?,
+            events,
+            paths: HashSet::new(),
+            dirs: HashSet::new(),
+        };
+        watcher.update()?;
+        Ok(watcher)
+    }
+
+    /// Changes the files being watched.
+    pub(crate) fn set_files(&mut self, files: &[PathBuf]) -> anyhow::Result<()> {
+        let files = absolute(files)?;
+        if files != self.files {
+            self.files = files;
+            self.update()?;
+        }
+        Ok(())
+    }
+
+    /// Blocks until a config file may have changed, returning `false` if
+    /// `timeout` elapses first.
+    pub(crate) fn wait(&mut self, timeout: Option<Duration>) -> anyhow::Result<bool> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+
+        loop {
+            let event = match deadline {
+                Some(deadline) => {
+                    let timeout = deadline.saturating_duration_since(Instant::now());
+                    match self.events.recv_timeout(timeout) {
+                        Ok(event) => event,
+                        Err(RecvTimeoutError::Timeout) => return Ok(false),
+                        Err(RecvTimeoutError::Disconnected) => {
+                            anyhow::bail!("the config file watcher stopped")
+                        }
+                    }
+                }
+                None => self
+                    .events
+                    .recv()
+                    .map_err(|_| anyhow::anyhow!("the config file watcher stopped"))?,
+            };
+
+            if self.is_relevant(&event?) {
+                break;
+            }
+        }
+
+        loop {
+            match self.events.recv_timeout(SETTLE_TIME) {
+                Ok(event) => {
+                    event?;
+                }
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => {
+                    anyhow::bail!("the config file watcher stopped")
+                }
+            }
+        }
+
+        // a symlink may have been pointed somewhere else
+        self.update()?;
+        Ok(true)
+    }
+
+    fn is_relevant(&self, event: &Event) -> bool {
+        event.paths.iter().any(|path| self.paths.contains(path))
+    }
+
+    fn update(&mut self) -> anyhow::Result<()> {
+        let paths = self
+            .files
+            .iter()
+            .flat_map(|file| resolution_paths(file))
+            .collect::<HashSet<_>>();
+        let dirs = paths
+            .iter()
+            .filter_map(|path| path.parent())
+            .map(Path::to_path_buf)
+            .collect::<HashSet<_>>();
+
+        for dir in self.dirs.difference(&dirs) {
+            // the directory may already be gone
+            let _ = self.watcher.unwatch(dir);
+        }
+        for dir in dirs.difference(&self.dirs) {
+            self.watcher.watch(dir, RecursiveMode::NonRecursive)?;
+        }
+
+        self.paths = paths;
+        self.dirs = dirs;
+        Ok(())
+    }
+}
+
+fn absolute(files: &[PathBuf]) -> anyhow::Result<Vec<PathBuf>> {
+    files
+        .iter()
+        .map(|file| path::absolute(file).map_err(Into::into))
+        .collect()
+}
+
+/// Returns `path`, every symlink followed while resolving it, and the path it
+/// resolves to.
+fn resolution_paths(path: &Path) -> HashSet<PathBuf> {
+    let mut paths = HashSet::new();
+    let mut current = path.to_path_buf();
+    paths.insert(current.clone());
+
+    'resolve: for _ in 0..MAX_SYMLINKS {
+        let mut prefix = PathBuf::new();
+        let mut components = current.components();
+        while let Some(component) = components.next() {
+            prefix.push(component);
+            if let Ok(target) = fs::read_link(&prefix) {
+                let mut next = match prefix.parent() {
+                    Some(parent) => parent.join(target),
+                    None => target,
+                };
+                next.extend(components);
+                paths.insert(prefix);
+                paths.insert(next.clone());
+                current = next;
+                continue 'resolve;
+            }
+        }
+        break;
+    }
+
+    paths
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const TIMEOUT: Option<Duration> = Some(Duration::from_secs(5));
+
+    #[test]
+    fn rename_save() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log4rs.yaml");
+        fs::write(&path, "a").unwrap();
+        let mut watcher = ConfigWatcher::new(std::slice::from_ref(&path)).unwrap();
+
+        fs::write(dir.path().join("other"), "a").unwrap();
+        assert!(!watcher.wait(Some(SETTLE_TIME * 4)).unwrap());
+
+        let tmp = dir.path().join(".log4rs.yaml.swp");
+        fs::write(&tmp, "b").unwrap();
+        fs::rename(&tmp, &path).unwrap();
+        assert!(watcher.wait(TIMEOUT).unwrap());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn symlink_swap() {
+        use std::os::unix::fs::symlink;
+
+        // the layout Kubernetes uses for ConfigMap volumes
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("..v1")).unwrap();
+        fs::write(dir.path().join("..v1/log4rs.yaml"), "a").unwrap();
+        symlink("..v1", dir.path().join("..data")).unwrap();
+        symlink("..data/log4rs.yaml", dir.path().join("log4rs.yaml")).unwrap();
+
+        let path = dir.path().join("log4rs.yaml");
+        let mut watcher = ConfigWatcher::new(std::slice::from_ref(&path)).unwrap();
+        assert!(watcher.paths.contains(&dir.path().join("..data")));
+        assert!(watcher.paths.contains(&dir.path().join("..v1/log4rs.yaml")));
+
+        fs::create_dir(dir.path().join("..v2")).unwrap();
+        fs::write(dir.path().join("..v2/log4rs.yaml"), "b").unwrap();
+        symlink("..v2", dir.path().join("..data_tmp")).unwrap();
+        fs::rename(dir.path().join("..data_tmp"), dir.path().join("..data")).unwrap();
+        fs::remove_dir_all(dir.path().join("..v1")).unwrap();
+
+        assert!(watcher.wait(TIMEOUT).unwrap());
+        assert!(watcher.paths.contains(&dir.path().join("..v2/log4rs.yaml")));
+
+        fs::write(dir.path().join("..v2/log4rs.yaml"), "c").unwrap();
+        assert!(watcher.wait(TIMEOUT).unwrap());
+    }
+}
@@ -0,0 +1,482 @@
+//! An encoder which writes an [Elastic Common Schema][ecs] (ECS) document.
+//!
+//! Each log event will be written as a JSON object on its own line, ready to
+//! be indexed into Elasticsearch without an ingest pipeline.
+//!
+//! Requires the `ecs_encoder` feature.
+//!
+//! # Contents
+//!
+//! An example document (note that real output will not be pretty-printed):
+//!
+//! ```json
+//! {
+//!     "@timestamp": "2016-03-20T22:22:20.644Z",
+//!     "log.level": "info",
+//!     "message": "the log message",
+//!     "ecs.version": "8.11.0",
+//!     "log": {
+//!         "logger": "foo::bar",
+//!         "origin": {
+//!             "file": {
+//!                 "line": 100,
+//!                 "name": "foo/bar/mod.rs"
+//!             }
+//!         }
+//!     },
+//!     "process": {
+//!         "pid": 4321,
+//!         "thread": {
+//!             "id": 123,
+//!             "name": "main"
+//!         }
+//!     },
+//!     "labels": {
+//!         "request_id": "123e4567-e89b-12d3-a456-426655440000"
+//!     }
+//! }
+//! ```
+//!
+//! The MDC, and the record's [log::kv][log_kv] key-values if the `log_kv`
+//! feature is enabled, are written under `labels` by default. As ECS labels
+//! are strings, other values are written as their JSON text. They can
+//! instead be written as custom fields at the top level of the document,
+//! where key-values keep their own types. See `EcsEncoderBuilder`.
+//!
+//! [ecs]: https://www.elastic.co/guide/en/ecs/current/index.html
+//! [log_kv]: https://docs.rs/log/latest/log/kv/index.html
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use log::Record;
+use serde::ser::{self, Serialize, SerializeMap};
+use std::{process, thread};
+
+#[cfg(feature = "config_parsing")]
+use crate::config::{Deserialize, Deserializers};
+use crate::encode::{
+    json::{self, Displayed},
+    Encode, Write, NEWLINE,
+};
+
+/// The version of ECS which documents conform to.
+pub const ECS_VERSION: &str = "8.11.0";
+
+// The fields written by the encoder itself, which custom fields may not
+// take the place of.
+const RESERVED: &[&str] = &["@timestamp", "message", "ecs", "log", "process", "labels"];
+
+// The objects written by the encoder, whose dotted paths custom fields may
+// not write into either, as Elasticsearch would merge them.
+const RESERVED_PREFIXES: &[&str] = &["log.", "process.", "ecs.", "labels."];
+
+fn is_reserved(key: &str) -> bool {
+    RESERVED.contains(&key) || RESERVED_PREFIXES.iter().any(|p| key.starts_with(p))
+}
+
+/// The ECS encoder's configuration
+#[cfg(feature = "config_parsing")]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct EcsEncoderConfig {
+    mdc: Option<Destination>,
+    #[cfg(feature = "log_kv")]
+    attributes: Option<Destination>,
+    custom_prefix: Option<String>,
+}
+
+/// Where the `EcsEncoder` writes the entries of the MDC or key-values.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+#[cfg_attr(
+    feature = "config_parsing",
+    derive(serde::Deserialize, serde::Serialize)
+)]
+#[cfg_attr(feature = "config_parsing", serde(rename_all = "snake_case"))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum Destination {
+    /// Under `labels`, with values written as strings.
+    #[default]
+    Labels,
+    /// As custom fields at the top level of the document, with their keys
+    /// prefixed by the encoder's custom prefix.
+    Custom,
+    /// Nowhere.
+    Omit,
+}
+
+/// An `Encode`r which writes an ECS document.
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct EcsEncoder {
+    mdc: Destination,
+    attributes: Destination,
+    custom_prefix: String,
+}
+
+impl EcsEncoder {
+    /// Returns a new `EcsEncoder` with a default configuration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a new `EcsEncoderBuilder` with a default configuration.
+    pub fn builder() -> EcsEncoderBuilder {
+        EcsEncoderBuilder(EcsEncoder::default())
+    }
+
+    fn encode_inner(
+        &self,
+        w: &mut dyn Write,
+        time: DateTime<Utc>,
+        record: &Record<'_>,
+    ) -> anyhow::Result<()> {
+        let mut labels = serde_json::Map::new();
+        let mut custom = serde_json::Map::new();
+        let mut place = |destination, key: String, value: serde_json::Value| match destination {
+            Destination::Labels => {
+                let value = match value {
+                    serde_json::Value::String(value) => value,
+                    value => value.to_string(),
+                };
+                labels.insert(key, value.into());
+            }
+            Destination::Custom => {
+                let key = format!("{}{}", self.custom_prefix, key);
+                if !is_reserved(&key) {
+                    custom.insert(key, value);
+                }
+            }
+            Destination::Omit => {}
+        };
+
+        // key-values take the place of MDC entries with the same key
+        for (key, value) in json::mdc() {
+            place(self.mdc, key, value);
+        }
+        #[cfg(feature = "log_kv")]
+        for (key, value) in json::kv::collect(record.key_values()).map_err(anyhow::Error::msg)? {
+            place(self.attributes, key, value);
+        }
+
+        let document = Document {
+            time,
+            record,
+            labels,
+            custom,
+        };
+        document.serialize(&mut serde_json::Serializer::new(&mut *w))?;
+        w.write_all(NEWLINE.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// A builder for `EcsEncoder`s.
+#[derive(Clone, Debug)]
+pub struct EcsEncoderBuilder(EcsEncoder);
+
+impl EcsEncoderBuilder {
+    /// Sets where the entries of the logging thread's MDC are written.
+    ///
+    /// Defaults to `Destination::Labels`.
+    pub fn mdc(mut self, destination: Destination) -> EcsEncoderBuilder {
+        self.0.mdc = destination;
+        self
+    }
+
+    /// Sets where the record's key-values are written. A key-value takes the
+    /// place of an MDC entry written to the same place with the same key.
+    ///
+    /// Defaults to `Destination::Labels`. Only has an effect if the `log_kv`
+    /// feature is enabled.
+    pub fn attributes(mut self, destination: Destination) -> EcsEncoderBuilder {
+        self.0.attributes = destination;
+        self
+    }
+
+    /// Sets the prefix of the keys of custom fields, such as `app.`.
+    ///
+    /// Custom fields whose keys are those of the fields written by the
+    /// encoder, such as `message`, or paths within them, such as
+    /// `log.origin.file.name` or `labels.user`, are not written.
+    ///
+    /// Defaults to none.
+    pub fn custom_prefix(mut self, prefix: &str) -> EcsEncoderBuilder {
+        self.0.custom_prefix = prefix.to_owned();
+        self
+    }
+
+    /// Consumes the `EcsEncoderBuilder`, producing an `EcsEncoder`.
+    pub fn build(self) -> EcsEncoder {
+        self.0
+    }
+}
+
+impl Encode for EcsEncoder {
+    fn encode(&self, w: &mut dyn Write, record: &Record<'_>) -> anyhow::Result<()> {
+        self.encode_inner(w, Utc::now(), record)
+    }
+
+    #[cfg(feature = "config_parsing")]
+    fn config_value(&self) -> Option<serde_value::Value> {
+        let config = EcsEncoderConfig {
+            mdc: Some(self.mdc),
+            #[cfg(feature = "log_kv")]
+            attributes: Some(self.attributes),
+            custom_prefix: Some(self.custom_prefix.clone()),
+        };
+        crate::config::component_config("ecs", &config)
+    }
+}
+
+struct Document<'a> {
+    time: DateTime<Utc>,
+    record: &'a Record<'a>,
+    labels: serde_json::Map<String, serde_json::Value>,
+    custom: serde_json::Map<String, serde_json::Value>,
+}
+
+impl ser::Serialize for Document<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        let record = self.record;
+        let thread = thread::current();
+
+        let mut log = serde_json::json!({ "logger": record.target() });
+        if let Some(file) = record.file() {
+            let mut origin = serde_json::json!({ "name": file });
+            if let Some(line) = record.line() {
+                origin["line"] = line.into();
+            }
+            log["origin"] = serde_json::json!({ "file": origin });
+        }
+
+        let mut thread_fields = serde_json::Map::new();
+        #[cfg(not(target_family = "wasm"))]
+        thread_fields.insert("id".to_owned(), thread_id::get().into());
+        if let Some(name) = thread.name() {
+            thread_fields.insert("name".to_owned(), name.into());
+        }
+        let process = serde_json::json!({ "pid": process::id(), "thread": thread_fields });
+
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry(
+            "@timestamp",
+            &self.time.to_rfc3339_opts(SecondsFormat::Millis, true),
+        )?;
+        map.serialize_entry("log.level", &record.level().as_str().to_ascii_lowercase())?;
+        map.serialize_entry("message", &Displayed(record.args()))?;
+        map.serialize_entry("ecs.version", ECS_VERSION)?;
+        map.serialize_entry("log", &log)?;
+        map.serialize_entry("process", &process)?;
+        if !self.labels.is_empty() {
+            map.serialize_entry("labels", &self.labels)?;
+        }
+        for (key, value) in &self.custom {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+
+/// A deserializer for the `EcsEncoder`.
+///
+/// # Configuration
+///
+/// ```yaml
+/// kind: ecs
+///
+/// # Where to write the entries of the MDC. One of `labels`, `custom` to
+/// # write them as custom fields at the top level of the document, or `omit`.
+/// # Defaults to `labels`.
+/// mdc: labels
+///
+/// # Where to write the record's key-values, which take the place of MDC
+/// # entries written to the same place with the same key. Requires the
+/// # `log_kv` feature. Defaults to `labels`.
+/// attributes: custom
+///
+/// # The prefix of the keys of custom fields. Defaults to none.
+/// custom_prefix: "app."
+/// ```
+#[cfg(feature = "config_parsing")]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct EcsEncoderDeserializer;
+
+#[cfg(feature = "config_parsing")]
+impl Deserialize for EcsEncoderDeserializer {
+    type Trait = dyn Encode;
+
+    type Config = EcsEncoderConfig;
+
+    fn deserialize(
+        &self,
+        config: EcsEncoderConfig,
+        _: &Deserializers,
+    ) -> anyhow::Result<Box<dyn Encode>> {
+        let mut builder = EcsEncoder::builder();
+        if let Some(mdc) = config.mdc {
+            builder = builder.mdc(mdc);
+        }
+        #[cfg(feature = "log_kv")]
+        if let Some(attributes) = config.attributes {
+            builder = builder.attributes(attributes);
+        }
+        if let Some(prefix) = config.custom_prefix {
+            builder = builder.custom_prefix(&prefix);
+        }
+        Ok(Box::new(builder.build()))
+    }
+
+    #[cfg(feature = "schema")]
+    fn schema(&self, generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        <EcsEncoderConfig as schemars::JsonSchema>::json_schema(generator)
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "simple_writer")]
+mod test {
+    use log::{Level, Record};
+
+    use super::*;
+    use crate::encode::writer::simple::SimpleWriter;
+
+    fn encode(encoder: &EcsEncoder, record: &Record<'_>) -> serde_json::Value {
+        let time = DateTime::parse_from_rfc3339("2016-03-20T14:22:20.644420340-08:00")
+            .unwrap()
+            .with_timezone(&Utc);
+        let mut buf = vec![];
+        encoder
+            .encode_inner(&mut SimpleWriter(&mut buf), time, record)
+            .unwrap();
+        let line = String::from_utf8(buf).unwrap();
+        assert!(line.starts_with("{\"@timestamp\":"));
+        assert_eq!(line.trim().lines().count(), 1);
+        serde_json::from_str(&line).unwrap()
+    }
+
+    #[test]
+    fn default() {
+        log_mdc::insert("request_id", "42");
+        let record = Record::builder()
+            .level(Level::Warn)
+            .target("target")
+            .file(Some("file"))
+            .line(Some(100))
+            .args(format_args!("message"))
+            .build();
+
+        let mut expected = serde_json::json!({
+            "@timestamp": "2016-03-20T22:22:20.644Z",
+            "log.level": "warn",
+            "message": "message",
+            "ecs.version": ECS_VERSION,
+            "log": {
+                "logger": "target",
+                "origin": { "file": { "name": "file", "line": 100 } }
+            },
+            "process": {
+                "pid": process::id(),
+                "thread": { "name": "encode::ecs::test::default" }
+            },
+            "labels": { "request_id": "42" }
+        });
+        #[cfg(not(target_family = "wasm"))]
+        {
+            expected["process"]["thread"]["id"] = thread_id::get().into();
+        }
+        assert_eq!(encode(&EcsEncoder::new(), &record), expected);
+    }
+
+    #[test]
+    fn custom() {
+        log_mdc::insert("user", "alice");
+        log_mdc::insert("message", "mdc");
+        let record = Record::builder().args(format_args!("message")).build();
+
+        let encoder = EcsEncoder::builder().mdc(Destination::Custom).build();
+        let document = encode(&encoder, &record);
+        assert_eq!(document["user"], "alice");
+        assert_eq!(document["message"], "message");
+        assert!(document.get("labels").is_none());
+
+        let encoder = EcsEncoder::builder()
+            .mdc(Destination::Custom)
+            .custom_prefix("app.")
+            .build();
+        let document = encode(&encoder, &record);
+        assert_eq!(document["app.user"], "alice");
+        assert_eq!(document["app.message"], "mdc");
+
+        log_mdc::insert("ecs.version", "mdc");
+        log_mdc::insert("log.origin.file.name", "mdc");
+        log_mdc::insert("labels.user", "mdc");
+        log_mdc::insert("logger", "mdc");
+        let encoder = EcsEncoder::builder().mdc(Destination::Custom).build();
+        let document = encode(&encoder, &record);
+        assert_eq!(document["ecs.version"], ECS_VERSION);
+        assert!(document.get("log.origin.file.name").is_none());
+        assert!(document.get("labels.user").is_none());
+        assert_eq!(document["logger"], "mdc");
+        log_mdc::remove("ecs.version");
+        log_mdc::remove("log.origin.file.name");
+        log_mdc::remove("labels.user");
+        log_mdc::remove("logger");
+
+        let encoder = EcsEncoder::builder().mdc(Destination::Omit).build();
+        let document = encode(&encoder, &record);
+        assert!(document.get("labels").is_none());
+        assert!(document.get("user").is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "log_kv")]
+    fn attributes() {
+        log_mdc::insert("user", "alice");
+        let kvs = [
+            ("count", log::kv::Value::from(3)),
+            ("ok", log::kv::Value::from(true)),
+            ("user", log::kv::Value::from("bob")),
+        ];
+        let record = Record::builder()
+            .args(format_args!("message"))
+            .key_values(&kvs)
+            .build();
+
+        let document = encode(&EcsEncoder::new(), &record);
+        assert_eq!(
+            document["labels"],
+            serde_json::json!({ "count": "3", "ok": "true", "user": "bob" })
+        );
+
+        let encoder = EcsEncoder::builder()
+            .attributes(Destination::Custom)
+            .build();
+        let document = encode(&encoder, &record);
+        assert_eq!(document["labels"], serde_json::json!({ "user": "alice" }));
+        assert_eq!(document["count"], 3);
+        assert_eq!(document["ok"], true);
+        assert_eq!(document["user"], "bob");
+    }
+
+    #[test]
+    #[cfg(all(feature = "config_parsing", feature = "yaml_format"))]
+    fn deserialize() {
+        let config =
+            ::serde_yaml::from_str::<EcsEncoderConfig>("mdc: custom\ncustom_prefix: \"app.\"\n")
+                .unwrap();
+        let encoder = EcsEncoderDeserializer
+            .deserialize(config, &Deserializers::default())
+            .unwrap();
+        #[cfg(feature = "log_kv")]
+        let attributes = "attributes: labels\n";
+        #[cfg(not(feature = "log_kv"))]
+        let attributes = "";
+        let expected = ::serde_yaml::from_str::<serde_value::Value>(&format!(
+            "kind: ecs\nmdc: custom\n{attributes}custom_prefix: \"app.\"\n"
+        ))
+        .unwrap();
+        assert_eq!(encoder.config_value().unwrap(), expected);
+    }
+}
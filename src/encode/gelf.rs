@@ -0,0 +1,520 @@
+//! An encoder which writes a [GELF][gelf] 1.1 message.
+//!
+//! Each log event will be written as a JSON object on its own line. The
+//! `gelf` appender sends these messages to Graylog.
+//!
+//! Requires the `gelf_encoder` feature.
+//!
+//! # Contents
+//!
+//! An example message (note that real output will not be pretty-printed):
+//!
+//! ```json
+//! {
+//!     "version": "1.1",
+//!     "host": "my_host",
+//!     "short_message": "the first line of the log message",
+//!     "full_message": "the first line of the log message\nand the rest of it",
+//!     "timestamp": 1458512540.644,
+//!     "level": 6,
+//!     "_facility": "api",
+//!     "_file": "foo/bar/mod.rs",
+//!     "_line": 100,
+//!     "_logger": "foo::bar",
+//!     "_module_path": "foo::bar",
+//!     "_request_id": "123e4567-e89b-12d3-a456-426655440000",
+//!     "_thread": "main"
+//! }
+//! ```
+//!
+//! The `short_message` is the first non-blank line of the log message, or `-`
+//! if there is none, and the `full_message` is only written if the log
+//! message has more lines. The
+//! `level` is the syslog severity of the record's level.
+//!
+//! Static additional fields, the MDC and the record's [log::kv][log_kv]
+//! key-values if the `log_kv` feature is enabled are written as additional
+//! fields, in that order of precedence. Their keys are prefixed with an
+//! underscore, characters not allowed by GELF are replaced with
+//! underscores, and the reserved `_id` key is not written. As GELF only
+//! allows strings and numbers, other values are written as their JSON text.
+//!
+//! [gelf]: https://go2docs.graylog.org/current/getting_in_log_data/gelf.html
+//! [log_kv]: https://docs.rs/log/latest/log/kv/index.html
+
+use chrono::{DateTime, Utc};
+use log::{Level, Record};
+use serde::ser::{self, Serialize, SerializeMap};
+use std::{collections::BTreeMap, thread};
+
+#[cfg(feature = "config_parsing")]
+use crate::config::{Deserialize, Deserializers};
+use crate::{
+    encode::{json, Encode, Write, NEWLINE},
+    priv_host,
+};
+
+/// The GELF encoder's configuration
+#[cfg(feature = "config_parsing")]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct GelfEncoderConfig {
+    host: Option<String>,
+    facility: Option<String>,
+    max_short_message_length: Option<usize>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    additional_fields: BTreeMap<String, serde_json::Value>,
+}
+
+/// An `Encode`r which writes a GELF message.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct GelfEncoder {
+    host: String,
+    facility: Option<String>,
+    max_short_message_length: Option<usize>,
+    additional_fields: BTreeMap<String, serde_json::Value>,
+}
+
+impl Default for GelfEncoder {
+    fn default() -> Self {
+        GelfEncoder::builder().build()
+    }
+}
+
+impl GelfEncoder {
+    /// Returns a new `GelfEncoder` with a default configuration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a new `GelfEncoderBuilder` with a default configuration.
+    pub fn builder() -> GelfEncoderBuilder {
+        GelfEncoderBuilder {
+            host: None,
+            facility: None,
+            max_short_message_length: None,
+            additional_fields: BTreeMap::new(),
+        }
+    }
+
+    fn encode_inner(
+        &self,
+        w: &mut dyn Write,
+        time: DateTime<Utc>,
+        record: &Record<'_>,
+    ) -> anyhow::Result<()> {
+        let mut fields = BTreeMap::new();
+        let mut add = |key: &str, value: serde_json::Value| {
+            if let (Some(key), Some(value)) = (field_name(key), field_value(value)) {
+                fields.insert(key, value);
+            }
+        };
+
+        for (key, value) in &self.additional_fields {
+            add(key, value.clone());
+        }
+        for (key, value) in json::mdc() {
+            add(&key, value);
+        }
+        #[cfg(feature = "log_kv")]
+        for (key, value) in json::kv::collect(record.key_values()).map_err(anyhow::Error::msg)? {
+            add(&key, value);
+        }
+
+        if let Some(facility) = &self.facility {
+            add("facility", facility.as_str().into());
+        }
+        add("logger", record.target().into());
+        if let Some(module_path) = record.module_path() {
+            add("module_path", module_path.into());
+        }
+        if let Some(file) = record.file() {
+            add("file", file.into());
+        }
+        if let Some(line) = record.line() {
+            add("line", line.into());
+        }
+        if let Some(name) = thread::current().name() {
+            add("thread", name.into());
+        }
+
+        let message = Message {
+            encoder: self,
+            time,
+            text: record.args().to_string(),
+            level: record.level(),
+            fields,
+        };
+        message.serialize(&mut serde_json::Serializer::new(&mut *w))?;
+        w.write_all(NEWLINE.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// A builder for `GelfEncoder`s.
+#[derive(Clone, Debug)]
+pub struct GelfEncoderBuilder {
+    host: Option<String>,
+    facility: Option<String>,
+    max_short_message_length: Option<usize>,
+    additional_fields: BTreeMap<String, serde_json::Value>,
+}
+
+impl GelfEncoderBuilder {
+    /// Sets the `host` of every message.
+    ///
+    /// Defaults to the hostname of the machine.
+    pub fn host(mut self, host: &str) -> GelfEncoderBuilder {
+        self.host = Some(host.to_owned());
+        self
+    }
+
+    /// Sets the `_facility` additional field of every message.
+    ///
+    /// Defaults to none.
+    pub fn facility(mut self, facility: &str) -> GelfEncoderBuilder {
+        self.facility = Some(facility.to_owned());
+        self
+    }
+
+    /// Sets the maximum number of characters of the `short_message`. Longer
+    /// first lines are truncated, and the whole log message is written as the
+    /// `full_message`.
+    ///
+    /// Defaults to no limit.
+    pub fn max_short_message_length(mut self, length: usize) -> GelfEncoderBuilder {
+        self.max_short_message_length = Some(length);
+        self
+    }
+
+    /// Adds an additional field with the same value in every message, such
+    /// as the name of the service.
+    pub fn additional_field<V>(mut self, name: &str, value: V) -> GelfEncoderBuilder
+    where
+        V: Into<serde_json::Value>,
+    {
+        self.additional_fields.insert(name.to_owned(), value.into());
+        self
+    }
+
+    /// Consumes the `GelfEncoderBuilder`, producing a `GelfEncoder`.
+    pub fn build(self) -> GelfEncoder {
+        GelfEncoder {
+            host: self
+                .host
+                .or_else(priv_host::hostname)
+                .unwrap_or_else(|| "localhost".to_owned()),
+            facility: self.facility,
+            max_short_message_length: self.max_short_message_length,
+            additional_fields: self.additional_fields,
+        }
+    }
+}
+
+impl Encode for GelfEncoder {
+    fn encode(&self, w: &mut dyn Write, record: &Record<'_>) -> anyhow::Result<()> {
+        self.encode_inner(w, Utc::now(), record)
+    }
+
+    #[cfg(feature = "config_parsing")]
+    fn config_value(&self) -> Option<serde_value::Value> {
+        let config = GelfEncoderConfig {
+            host: Some(self.host.clone()),
+            facility: self.facility.clone(),
+            max_short_message_length: self.max_short_message_length,
+            additional_fields: self.additional_fields.clone(),
+        };
+        crate::config::component_config("gelf", &config)
+    }
+}
+
+/// Returns the syslog severity of a level, which GELF uses as its level.
+fn severity(level: Level) -> u8 {
+    match level {
+        Level::Error => 3,
+        Level::Warn => 4,
+        Level::Info => 6,
+        Level::Debug | Level::Trace => 7,
+    }
+}
+
+/// Returns the name of the additional field for `key`, unless it is
+/// reserved.
+fn field_name(key: &str) -> Option<String> {
+    let mut name = String::with_capacity(key.len() + 1);
+    if !key.starts_with('_') {
+        name.push('_');
+    }
+    name.extend(key.chars().map(|c| match c {
+        'a'..='z' | 'A'..='Z' | '0'..='9' | '_' | '.' | '-' => c,
+        _ => '_',
+    }));
+    match &*name {
+        "_" | "_id" => None,
+        _ => Some(name),
+    }
+}
+
+/// Returns the value of an additional field, which is omitted if `null`.
+fn field_value(value: serde_json::Value) -> Option<serde_json::Value> {
+    match value {
+        serde_json::Value::Null => None,
+        serde_json::Value::String(_) | serde_json::Value::Number(_) => Some(value),
+        value => Some(value.to_string().into()),
+    }
+}
+
+struct Message<'a> {
+    encoder: &'a GelfEncoder,
+    time: DateTime<Utc>,
+    text: String,
+    level: Level,
+    fields: BTreeMap<String, serde_json::Value>,
+}
+
+impl Message<'_> {
+    /// Returns the `short_message`, and whether it is not the whole text.
+    ///
+    /// GELF requires a non-empty `short_message`, so blank lines are skipped
+    /// and a blank text is replaced with `-`.
+    fn short_message(&self) -> (&str, bool) {
+        let text = self.text.trim_end_matches(['\r', '\n']);
+        let mut short = match text.lines().find(|line| !line.trim().is_empty()) {
+            Some(line) => line,
+            None => return ("-", false),
+        };
+        if let Some(max) = self.encoder.max_short_message_length {
+            if let Some((end, _)) = short.char_indices().nth(max) {
+                short = &short[..end];
+            }
+        }
+        (short, short.len() < text.len())
+    }
+}
+
+impl ser::Serialize for Message<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        let (short_message, split) = self.short_message();
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("version", "1.1")?;
+        map.serialize_entry("host", &self.encoder.host)?;
+        map.serialize_entry("short_message", short_message)?;
+        if split {
+            map.serialize_entry("full_message", &self.text)?;
+        }
+        map.serialize_entry("timestamp", &(self.time.timestamp_millis() as f64 / 1000.0))?;
+        map.serialize_entry("level", &severity(self.level))?;
+        for (key, value) in &self.fields {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+
+/// A deserializer for the `GelfEncoder`.
+///
+/// # Configuration
+///
+/// ```yaml
+/// kind: gelf
+///
+/// # The host of every message. Defaults to the hostname of the machine.
+/// host: my_host
+///
+/// # The `_facility` additional field of every message. Defaults to none.
+/// facility: api
+///
+/// # The maximum number of characters of the short message. Longer first
+/// # lines are truncated, and the whole log message is written as the full
+/// # message. Defaults to no limit.
+/// max_short_message_length: 250
+///
+/// # Additional fields with the same value in every message. Their keys are
+/// # prefixed with an underscore. Defaults to none.
+/// additional_fields:
+///   env: prod
+/// ```
+#[cfg(feature = "config_parsing")]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct GelfEncoderDeserializer;
+
+#[cfg(feature = "config_parsing")]
+impl Deserialize for GelfEncoderDeserializer {
+    type Trait = dyn Encode;
+
+    type Config = GelfEncoderConfig;
+
+    fn deserialize(
+        &self,
+        config: GelfEncoderConfig,
+        _: &Deserializers,
+    ) -> anyhow::Result<Box<dyn Encode>> {
+        let mut builder = GelfEncoder::builder();
+        if let Some(host) = &config.host {
+            builder = builder.host(host);
+        }
+        if let Some(facility) = &config.facility {
+            builder = builder.facility(facility);
+        }
+        if let Some(length) = config.max_short_message_length {
+            builder = builder.max_short_message_length(length);
+        }
+        for (name, value) in config.additional_fields {
+            builder = builder.additional_field(&name, value);
+        }
+        Ok(Box::new(builder.build()))
+    }
+
+    #[cfg(feature = "schema")]
+    fn schema(&self, generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        <GelfEncoderConfig as schemars::JsonSchema>::json_schema(generator)
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "simple_writer")]
+mod test {
+    use super::*;
+    use crate::encode::writer::simple::SimpleWriter;
+
+    fn encode(encoder: &GelfEncoder, record: &Record<'_>) -> serde_json::Value {
+        let time = DateTime::parse_from_rfc3339("2016-03-20T14:22:20.644420340-08:00")
+            .unwrap()
+            .with_timezone(&Utc);
+        let mut buf = vec![];
+        encoder
+            .encode_inner(&mut SimpleWriter(&mut buf), time, record)
+            .unwrap();
+        let line = String::from_utf8(buf).unwrap();
+        assert_eq!(line.trim().lines().count(), 1);
+        serde_json::from_str(&line).unwrap()
+    }
+
+    #[test]
+    fn message() {
+        log_mdc::insert("request id", "42");
+        log_mdc::insert("id", "reserved");
+        let encoder = GelfEncoder::builder()
+            .host("host")
+            .facility("api")
+            .additional_field("env", "prod")
+            .additional_field("port", 8080)
+            .additional_field("debug", true)
+            .build();
+        let record = Record::builder()
+            .level(Level::Warn)
+            .target("target")
+            .module_path(Some("module_path"))
+            .file(Some("file"))
+            .line(Some(100))
+            .args(format_args!("message"))
+            .build();
+
+        assert_eq!(
+            encode(&encoder, &record),
+            serde_json::json!({
+                "version": "1.1",
+                "host": "host",
+                "short_message": "message",
+                "timestamp": 1458512540.644,
+                "level": 4,
+                "_debug": "true",
+                "_env": "prod",
+                "_facility": "api",
+                "_file": "file",
+                "_line": 100,
+                "_logger": "target",
+                "_module_path": "module_path",
+                "_port": 8080,
+                "_request_id": "42",
+                "_thread": "encode::gelf::test::message"
+            })
+        );
+    }
+
+    #[test]
+    fn short_message() {
+        let record = Record::builder()
+            .args(format_args!("first line\nsecond line\n"))
+            .build();
+        let message = encode(&GelfEncoder::new(), &record);
+        assert_eq!(message["short_message"], "first line");
+        assert_eq!(message["full_message"], "first line\nsecond line\n");
+
+        let record = Record::builder().args(format_args!("one line\n")).build();
+        let message = encode(&GelfEncoder::new(), &record);
+        assert_eq!(message["short_message"], "one line");
+        assert!(message.get("full_message").is_none());
+
+        let encoder = GelfEncoder::builder().max_short_message_length(3).build();
+        let message = encode(&encoder, &record);
+        assert_eq!(message["short_message"], "one");
+        assert_eq!(message["full_message"], "one line\n");
+
+        let record = Record::builder()
+            .args(format_args!("\n  \nafter blank lines"))
+            .build();
+        let message = encode(&GelfEncoder::new(), &record);
+        assert_eq!(message["short_message"], "after blank lines");
+        assert_eq!(message["full_message"], "\n  \nafter blank lines");
+
+        for text in ["", "\n", " \r\n"] {
+            let message = encode(
+                &GelfEncoder::new(),
+                &Record::builder().args(format_args!("{}", text)).build(),
+            );
+            assert_eq!(message["short_message"], "-");
+            assert!(message.get("full_message").is_none());
+        }
+    }
+
+    #[test]
+    fn levels() {
+        assert_eq!(severity(Level::Error), 3);
+        assert_eq!(severity(Level::Warn), 4);
+        assert_eq!(severity(Level::Info), 6);
+        assert_eq!(severity(Level::Debug), 7);
+        assert_eq!(severity(Level::Trace), 7);
+    }
+
+    #[test]
+    #[cfg(feature = "log_kv")]
+    fn attributes() {
+        log_mdc::insert("user", "alice");
+        let kvs = [
+            ("count", log::kv::Value::from(3)),
+            ("user", log::kv::Value::from("bob")),
+            ("tags", log::kv::Value::from_serde(&["a", "b"])),
+        ];
+        let record = Record::builder()
+            .args(format_args!("message"))
+            .key_values(&kvs)
+            .build();
+
+        let message = encode(&GelfEncoder::new(), &record);
+        assert_eq!(message["_count"], 3);
+        assert_eq!(message["_user"], "bob");
+        assert_eq!(message["_tags"], "[\"a\",\"b\"]");
+    }
+
+    #[test]
+    #[cfg(all(feature = "config_parsing", feature = "yaml_format"))]
+    fn deserialize() {
+        let config = ::serde_yaml::from_str::<GelfEncoderConfig>(
+            "host: host\nfacility: api\nadditional_fields:\n  env: prod\n",
+        )
+        .unwrap();
+        let encoder = GelfEncoderDeserializer
+            .deserialize(config, &Deserializers::default())
+            .unwrap();
+        let expected = ::serde_yaml::from_str::<serde_value::Value>(
+            "kind: gelf\nhost: host\nfacility: api\nadditional_fields:\n  env: prod\n",
+        )
+        .unwrap();
+        assert_eq!(encoder.config_value().unwrap(), expected);
+    }
+}
@@ -28,15 +28,23 @@
 //! contain a map of the record's [log::kv][log_kv] structured logging
 //! attributes.
 //!
+//! The fields written, their names and the format of the time can be
+//! configured, and static fields, such as the name of the service, can be
+//! added to every object. The MDC and attributes can be flattened into the
+//! object itself rather than nested under a field. See `JsonEncoderBuilder`.
+//!
 //! [log_kv]: https://docs.rs/log/latest/log/kv/index.html
 
 use chrono::{
-    format::{DelayedFormat, Fixed, Item},
-    DateTime, Local,
+    format::{Fixed, Item},
+    DateTime, Local, SecondsFormat, Utc,
 };
-use log::{Level, Record};
+use log::Record;
 use serde::ser::{self, Serialize, SerializeMap};
-use std::{fmt, option, thread};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt, thread,
+};
 
 #[cfg(feature = "config_parsing")]
 use crate::config::{Deserialize, Deserializers};
@@ -44,87 +52,535 @@ use crate::encode::{Encode, Write, NEWLINE};
 
 /// The JSON encoder's configuration
 #[cfg(feature = "config_parsing")]
-#[derive(Clone, Eq, PartialEq, Hash, Debug, Default, serde::Deserialize)]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default, serde::Deserialize, serde::Serialize)]
 #[serde(deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct JsonEncoderConfig {
-    #[serde(skip_deserializing)]
-    _p: (),
+    fields: Option<Vec<Field>>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    rename: BTreeMap<Field, String>,
+    time_format: Option<TimeFormat>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    static_fields: BTreeMap<String, serde_json::Value>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    flatten: BTreeMap<Field, String>,
+    on_collision: Option<CollisionPolicy>,
 }
 
-/// An `Encode`r which writes a JSON object.
+/// A field of the objects written by the `JsonEncoder`.
+///
+/// Fields are written in the order of this enum.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+#[non_exhaustive]
+#[cfg_attr(
+    feature = "config_parsing",
+    derive(serde::Deserialize, serde::Serialize)
+)]
+#[cfg_attr(feature = "config_parsing", serde(rename_all = "snake_case"))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum Field {
+    /// The time of the event, in the configured `TimeFormat`.
+    Time,
+    /// The level of the event, such as `INFO`.
+    Level,
+    /// The log message.
+    Message,
+    /// The module path of the event, if known.
+    ModulePath,
+    /// The source file of the event, if known.
+    File,
+    /// The source line of the event, if known.
+    Line,
+    /// The target of the event.
+    Target,
+    /// The name of the logging thread, or `null` if it has none.
+    Thread,
+    /// The ID of the logging thread. Not written on WebAssembly.
+    ThreadId,
+    /// The logging thread's MDC.
+    Mdc,
+    /// The record's key-values.
+    ///
+    /// Requires the `log_kv` feature.
+    #[cfg(feature = "log_kv")]
+    Attributes,
+}
+
+impl Field {
+    const ALL: &'static [Field] = &[
+        Field::Time,
+        Field::Level,
+        Field::Message,
+        Field::ModulePath,
+        Field::File,
+        Field::Line,
+        Field::Target,
+        Field::Thread,
+        Field::ThreadId,
+        Field::Mdc,
+        #[cfg(feature = "log_kv")]
+        Field::Attributes,
+    ];
+
+    /// Returns the default name of the field.
+    pub fn name(self) -> &'static str {
+        match self {
+            Field::Time => "time",
+            Field::Level => "level",
+            Field::Message => "message",
+            Field::ModulePath => "module_path",
+            Field::File => "file",
+            Field::Line => "line",
+            Field::Target => "target",
+            Field::Thread => "thread",
+            Field::ThreadId => "thread_id",
+            Field::Mdc => "mdc",
+            #[cfg(feature = "log_kv")]
+            Field::Attributes => "attributes",
+        }
+    }
+}
+
+/// The format of the `time` field.
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
-pub struct JsonEncoder(());
+#[cfg_attr(
+    feature = "config_parsing",
+    derive(serde::Deserialize, serde::Serialize)
+)]
+#[cfg_attr(feature = "config_parsing", serde(rename_all = "snake_case"))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum TimeFormat {
+    /// An RFC 3339 string in the local time zone, such as
+    /// `2016-03-20T14:22:20.644420340-08:00`.
+    #[default]
+    Rfc3339,
+    /// An RFC 3339 string in UTC, such as `2016-03-20T22:22:20.644420340Z`.
+    Rfc3339Utc,
+    /// The number of milliseconds since the Unix epoch.
+    EpochMillis,
+    /// The number of seconds since the Unix epoch.
+    EpochSeconds,
+}
+
+/// What to do with a flattened key which is also the name of another field,
+/// or of a static field.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+#[cfg_attr(
+    feature = "config_parsing",
+    derive(serde::Deserialize, serde::Serialize)
+)]
+#[cfg_attr(feature = "config_parsing", serde(rename_all = "snake_case"))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum CollisionPolicy {
+    /// The key is not written.
+    #[default]
+    Skip,
+    /// The key is written in place of the other field.
+    Replace,
+    /// The key is written after the name of the field it was flattened from
+    /// and a dot, such as `mdc.level`.
+    Qualify,
+}
+
+/// An `Encode`r which writes a JSON object.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct JsonEncoder {
+    fields: Vec<Field>,
+    names: BTreeMap<Field, String>,
+    time_format: TimeFormat,
+    static_fields: BTreeMap<String, serde_json::Value>,
+    flatten: BTreeMap<Field, String>,
+    on_collision: CollisionPolicy,
+}
+
+impl Default for JsonEncoder {
+    fn default() -> Self {
+        JsonEncoder::builder().build()
+    }
+}
 
 impl JsonEncoder {
     /// Returns a new `JsonEncoder` with a default configuration.
     pub fn new() -> Self {
         Self::default()
     }
-}
 
-impl JsonEncoder {
+    /// Returns a new `JsonEncoderBuilder` with a default configuration.
+    pub fn builder() -> JsonEncoderBuilder {
+        JsonEncoderBuilder {
+            fields: Field::ALL.to_vec(),
+            names: BTreeMap::new(),
+            time_format: TimeFormat::default(),
+            static_fields: BTreeMap::new(),
+            flatten: BTreeMap::new(),
+            on_collision: CollisionPolicy::default(),
+        }
+    }
+
     fn encode_inner(
         &self,
         w: &mut dyn Write,
         time: DateTime<Local>,
         record: &Record<'_>,
     ) -> anyhow::Result<()> {
-        let thread = thread::current();
         let message = Message {
-            time: time.format_with_items(Some(Item::Fixed(Fixed::RFC3339)).into_iter()),
-            level: record.level(),
-            message: record.args(),
-            module_path: record.module_path(),
-            file: record.file(),
-            line: record.line(),
-            target: record.target(),
-            thread: thread.name(),
-            #[cfg(not(target_family = "wasm"))]
-            thread_id: thread_id::get(),
-            mdc: Mdc,
-            #[cfg(feature = "log_kv")]
-            attributes: kv::Attributes(record.key_values()),
+            encoder: self,
+            time,
+            record,
         };
         message.serialize(&mut serde_json::Serializer::new(&mut *w))?;
         w.write_all(NEWLINE.as_bytes())?;
         Ok(())
     }
+
+    fn name(&self, field: Field) -> &str {
+        self.names
+            .get(&field)
+            .map(String::as_str)
+            .unwrap_or_else(|| field.name())
+    }
+
+    fn flattened(&self, record: &Record<'_>) -> anyhow::Result<Flattened> {
+        let mut entries = vec![];
+        for (&field, prefix) in &self.flatten {
+            if !self.fields.contains(&field) {
+                continue;
+            }
+            let start = entries.len();
+            match field {
+                Field::Mdc => entries.extend(mdc().into_iter().map(|(k, v)| (field, k, v))),
+                #[cfg(feature = "log_kv")]
+                Field::Attributes => entries.extend(
+                    kv::collect(record.key_values())
+                        .map_err(anyhow::Error::msg)?
+                        .into_iter()
+                        .map(|(k, v)| (field, k, v)),
+                ),
+                _ => {}
+            }
+            for entry in &mut entries[start..] {
+                entry.1.insert_str(0, prefix);
+            }
+        }
+        #[cfg(not(feature = "log_kv"))]
+        let _ = record;
+
+        // an attribute takes the place of an MDC entry with the same key
+        let attributes = entries
+            .iter()
+            .filter(|entry| entry.0 != Field::Mdc)
+            .map(|entry| entry.1.clone())
+            .collect::<BTreeSet<_>>();
+        entries.retain(|(field, key, _)| *field != Field::Mdc || !attributes.contains(key));
+
+        let taken = self
+            .fields
+            .iter()
+            .filter(|field| !self.flatten.contains_key(field))
+            .map(|&field| self.name(field))
+            .chain(self.static_fields.keys().map(String::as_str))
+            .collect::<BTreeSet<_>>();
+        let mut flattened = Flattened::default();
+        for (field, key, value) in entries {
+            if !taken.contains(key.as_str()) {
+                flattened.entries.push((field, key, value));
+                continue;
+            }
+            match self.on_collision {
+                CollisionPolicy::Skip => {}
+                CollisionPolicy::Replace => {
+                    flattened.replaced.insert(key.clone());
+                    flattened.entries.push((field, key, value));
+                }
+                CollisionPolicy::Qualify => {
+                    let key = format!("{}.{}", self.name(field), key);
+                    flattened.entries.push((field, key, value));
+                }
+            }
+        }
+        Ok(flattened)
+    }
+}
+
+/// The entries of the flattened fields of a record.
+#[derive(Default)]
+struct Flattened {
+    /// The entries, with the field they belong to.
+    entries: Vec<(Field, String, serde_json::Value)>,
+    /// The names of the other fields which the entries take the place of.
+    replaced: BTreeSet<String>,
+}
+
+/// A builder for `JsonEncoder`s.
+#[derive(Clone, Debug)]
+pub struct JsonEncoderBuilder {
+    fields: Vec<Field>,
+    names: BTreeMap<Field, String>,
+    time_format: TimeFormat,
+    static_fields: BTreeMap<String, serde_json::Value>,
+    flatten: BTreeMap<Field, String>,
+    on_collision: CollisionPolicy,
+}
+
+impl JsonEncoderBuilder {
+    /// Sets the fields written, which are still written in the order of
+    /// `Field`.
+    ///
+    /// Defaults to all fields.
+    pub fn fields(mut self, fields: Vec<Field>) -> JsonEncoderBuilder {
+        self.fields = fields;
+        self
+    }
+
+    /// Writes `field` under a different name, such as `@timestamp` for the
+    /// time.
+    ///
+    /// If several fields would be written under the same name, only the
+    /// first of them in the order of `Field` is.
+    pub fn rename(mut self, field: Field, name: &str) -> JsonEncoderBuilder {
+        self.names.insert(field, name.to_owned());
+        self
+    }
+
+    /// Sets the format of the time.
+    ///
+    /// Defaults to `TimeFormat::Rfc3339`.
+    pub fn time_format(mut self, time_format: TimeFormat) -> JsonEncoderBuilder {
+        self.time_format = time_format;
+        self
+    }
+
+    /// Adds a field with the same value in every object, such as the name
+    /// of the service.
+    ///
+    /// Static fields are written after the fields of the record, and are
+    /// not written if their name is that of any field written.
+    pub fn static_field<V>(mut self, name: &str, value: V) -> JsonEncoderBuilder
+    where
+        V: Into<serde_json::Value>,
+    {
+        self.static_fields.insert(name.to_owned(), value.into());
+        self
+    }
+
+    /// Writes the entries of `field` into the object itself, with their keys
+    /// prefixed by `prefix`, rather than nested under the field.
+    ///
+    /// Only `Field::Mdc` and `Field::Attributes` can be flattened, and other
+    /// fields are unaffected. An attribute takes the place of an MDC entry
+    /// with the same key.
+    pub fn flatten(mut self, field: Field, prefix: &str) -> JsonEncoderBuilder {
+        self.flatten.insert(field, prefix.to_owned());
+        self
+    }
+
+    /// Sets what to do with a flattened key which is also the name of
+    /// another field written, or of a static field.
+    ///
+    /// Defaults to `CollisionPolicy::Skip`.
+    pub fn on_collision(mut self, on_collision: CollisionPolicy) -> JsonEncoderBuilder {
+        self.on_collision = on_collision;
+        self
+    }
+
+    /// Describes the first name written by more than one field, if any.
+    #[cfg(feature = "config_parsing")]
+    fn collision(&self) -> Option<String> {
+        let mut fields = self.fields.clone();
+        fields.sort();
+        fields.dedup();
+        let written = fields
+            .into_iter()
+            .filter(|field| !(self.flatten.contains_key(field) && is_flattenable(*field)))
+            .map(|field| {
+                let name = self.names.get(&field).map_or(field.name(), String::as_str);
+                (name, format!("the `{}` field", field.name()))
+            })
+            .chain(
+                self.static_fields
+                    .keys()
+                    .map(|name| (name.as_str(), "a static field".to_owned())),
+            );
+
+        let mut writers = BTreeMap::new();
+        for (name, writer) in written {
+            if let Some(first) = writers.get(name) {
+                return Some(format!(
+                    "the `{}` key is written by both {} and {}",
+                    name, first, writer
+                ));
+            }
+            writers.insert(name, writer);
+        }
+        None
+    }
+
+    /// Consumes the `JsonEncoderBuilder`, producing a `JsonEncoder`.
+    pub fn build(mut self) -> JsonEncoder {
+        self.fields.sort();
+        self.fields.dedup();
+        self.flatten.retain(|field, _| is_flattenable(*field));
+
+        // only the first field written under a name is kept
+        let mut taken = BTreeSet::new();
+        let (names, flatten) = (&self.names, &self.flatten);
+        self.fields.retain(|field| {
+            flatten.contains_key(field)
+                || taken.insert(names.get(field).map_or(field.name(), String::as_str))
+        });
+        self.static_fields
+            .retain(|name, _| !taken.contains(name.as_str()));
+
+        JsonEncoder {
+            fields: self.fields,
+            names: self.names,
+            time_format: self.time_format,
+            static_fields: self.static_fields,
+            flatten: self.flatten,
+            on_collision: self.on_collision,
+        }
+    }
 }
 
 impl Encode for JsonEncoder {
     fn encode(&self, w: &mut dyn Write, record: &Record<'_>) -> anyhow::Result<()> {
         self.encode_inner(w, Local::now(), record)
     }
+
+    #[cfg(feature = "config_parsing")]
+    fn config_value(&self) -> Option<serde_value::Value> {
+        let config = JsonEncoderConfig {
+            fields: Some(self.fields.clone()),
+            rename: self.names.clone(),
+            time_format: Some(self.time_format),
+            static_fields: self.static_fields.clone(),
+            flatten: self.flatten.clone(),
+            on_collision: Some(self.on_collision),
+        };
+        crate::config::component_config("json", &config)
+    }
 }
 
-#[derive(serde::Serialize)]
 struct Message<'a> {
-    #[serde(serialize_with = "ser_display")]
-    time: DelayedFormat<option::IntoIter<Item<'a>>>,
-    level: Level,
-    #[serde(serialize_with = "ser_display")]
-    message: &'a fmt::Arguments<'a>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    module_path: Option<&'a str>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    file: Option<&'a str>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    line: Option<u32>,
-    target: &'a str,
-    thread: Option<&'a str>,
-    #[cfg(not(target_family = "wasm"))]
-    thread_id: usize,
-    mdc: Mdc,
-    #[cfg(feature = "log_kv")]
-    attributes: kv::Attributes<'a>,
+    encoder: &'a JsonEncoder,
+    time: DateTime<Local>,
+    record: &'a Record<'a>,
 }
 
-fn ser_display<T, S>(v: &T, s: S) -> Result<S::Ok, S::Error>
+impl ser::Serialize for Message<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        let record = self.record;
+        let thread = thread::current();
+        let flattened = if self.encoder.flatten.is_empty() {
+            Flattened::default()
+        } else {
+            self.encoder.flattened(record).map_err(ser::Error::custom)?
+        };
+
+        let mut map = serializer.serialize_map(None)?;
+        for &field in &self.encoder.fields {
+            if self.encoder.flatten.contains_key(&field) {
+                for (_, key, value) in flattened.entries.iter().filter(|entry| entry.0 == field) {
+                    map.serialize_entry(key, value)?;
+                }
+                continue;
+            }
+
+            let name = self.encoder.name(field);
+            if flattened.replaced.contains(name) {
+                continue;
+            }
+            match field {
+                Field::Time => match self.encoder.time_format {
+                    TimeFormat::Rfc3339 => {
+                        let items = Some(Item::Fixed(Fixed::RFC3339)).into_iter();
+                        map.serialize_entry(name, &Displayed(self.time.format_with_items(items)))?
+                    }
+                    TimeFormat::Rfc3339Utc => map.serialize_entry(
+                        name,
+                        &self
+                            .time
+                            .with_timezone(&Utc)
+                            .to_rfc3339_opts(SecondsFormat::AutoSi, true),
+                    )?,
+                    TimeFormat::EpochMillis => {
+                        map.serialize_entry(name, &self.time.timestamp_millis())?
+                    }
+                    TimeFormat::EpochSeconds => {
+                        map.serialize_entry(name, &self.time.timestamp())?
+                    }
+                },
+                Field::Level => map.serialize_entry(name, &record.level())?,
+                Field::Message => map.serialize_entry(name, &Displayed(record.args()))?,
+                Field::ModulePath => {
+                    if let Some(module_path) = record.module_path() {
+                        map.serialize_entry(name, module_path)?
+                    }
+                }
+                Field::File => {
+                    if let Some(file) = record.file() {
+                        map.serialize_entry(name, file)?
+                    }
+                }
+                Field::Line => {
+                    if let Some(line) = record.line() {
+                        map.serialize_entry(name, &line)?
+                    }
+                }
+                Field::Target => map.serialize_entry(name, record.target())?,
+                Field::Thread => map.serialize_entry(name, &thread.name())?,
+                #[cfg(not(target_family = "wasm"))]
+                Field::ThreadId => map.serialize_entry(name, &thread_id::get())?,
+                #[cfg(target_family = "wasm")]
+                Field::ThreadId => {}
+                Field::Mdc => map.serialize_entry(name, &Mdc)?,
+                #[cfg(feature = "log_kv")]
+                Field::Attributes => {
+                    map.serialize_entry(name, &kv::Attributes(record.key_values()))?
+                }
+            }
+        }
+        for (name, value) in &self.encoder.static_fields {
+            if !flattened.replaced.contains(name) {
+                map.serialize_entry(name, value)?;
+            }
+        }
+        map.end()
+    }
+}
+
+fn is_flattenable(field: Field) -> bool {
+    match field {
+        Field::Mdc => true,
+        #[cfg(feature = "log_kv")]
+        Field::Attributes => true,
+        _ => false,
+    }
+}
+
+/// Returns the entries of the logging thread's MDC, ordered by key.
+pub(crate) fn mdc() -> Vec<(String, serde_json::Value)> {
+    let mut entries = vec![];
+    log_mdc::iter(|k, v| entries.push((k.to_owned(), serde_json::Value::from(v))));
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    entries
+}
+
+pub(crate) struct Displayed<T>(pub T);
+
+impl<T> ser::Serialize for Displayed<T>
 where
     T: fmt::Display,
-    S: ser::Serializer,
 {
-    s.collect_str(v)
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        serializer.collect_str(&self.0)
+    }
 }
 
 struct Mdc;
@@ -154,6 +610,39 @@ impl ser::Serialize for Mdc {
 ///
 /// ```yaml
 /// kind: json
+///
+/// # The fields to write, in any order. They are always written in the order
+/// # below. Defaults to all of them.
+/// fields: [time, level, message, module_path, file, line, target, thread, thread_id, mdc]
+///
+/// # Names to write fields under instead of their own, which must not be
+/// # those of other fields or static fields written. Defaults to none.
+/// rename:
+///   time: "@timestamp"
+///   level: severity
+///
+/// # The format of the time. One of `rfc3339` in the local time zone,
+/// # `rfc3339_utc`, `epoch_millis` or `epoch_seconds`. Defaults to `rfc3339`.
+/// time_format: epoch_millis
+///
+/// # Fields with the same value in every object, written after the others.
+/// # Their names must not be those of other fields written. Defaults to none.
+/// static_fields:
+///   service: api
+///   env: prod
+///
+/// # Fields whose entries are written into the object itself, rather than
+/// # nested under the field, with their keys prefixed by the given string.
+/// # Either `mdc` or `attributes`. Defaults to none.
+/// flatten:
+///   mdc: ""
+///   attributes: "kv."
+///
+/// # What to do with a flattened key which is also the name of another field
+/// # or a static field. One of `skip`, `replace` to write it in place of the
+/// # other field, or `qualify` to write it after the name of the field it
+/// # was flattened from, such as `mdc.level`. Defaults to `skip`.
+/// on_collision: skip
 /// ```
 #[cfg(feature = "config_parsing")]
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
@@ -167,14 +656,45 @@ impl Deserialize for JsonEncoderDeserializer {
 
     fn deserialize(
         &self,
-        _: JsonEncoderConfig,
+        config: JsonEncoderConfig,
         _: &Deserializers,
     ) -> anyhow::Result<Box<dyn Encode>> {
-        Ok(Box::<JsonEncoder>::default())
+        let mut builder = JsonEncoder::builder();
+        if let Some(fields) = config.fields {
+            builder = builder.fields(fields);
+        }
+        for (field, name) in &config.rename {
+            builder = builder.rename(*field, name);
+        }
+        if let Some(time_format) = config.time_format {
+            builder = builder.time_format(time_format);
+        }
+        for (name, value) in config.static_fields {
+            builder = builder.static_field(&name, value);
+        }
+        for (field, prefix) in &config.flatten {
+            if !is_flattenable(*field) {
+                anyhow::bail!("the `{}` field cannot be flattened", field.name());
+            }
+            builder = builder.flatten(*field, prefix);
+        }
+        if let Some(on_collision) = config.on_collision {
+            builder = builder.on_collision(on_collision);
+        }
+        if let Some(collision) = builder.collision() {
+            anyhow::bail!("{}", collision);
+        }
+        Ok(Box::new(builder.build()))
+    }
+
+    #[cfg(feature = "schema")]
+    fn schema(&self, generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        <JsonEncoderConfig as schemars::JsonSchema>::json_schema(generator)
     }
 }
+
 #[cfg(feature = "log_kv")]
-mod kv {
+pub(crate) mod kv {
     use log::kv::VisitSource;
     use serde::ser::{self, Error, SerializeMap};
 
@@ -195,6 +715,30 @@ mod kv {
         }
     }
 
+    struct Collector(Vec<(String, serde_json::Value)>);
+
+    impl<'kvs> VisitSource<'kvs> for Collector {
+        fn visit_pair(
+            &mut self,
+            key: log::kv::Key<'kvs>,
+            value: log::kv::Value<'kvs>,
+        ) -> Result<(), log::kv::Error> {
+            let value =
+                serde_json::to_value(&value).map_err(|e| log::kv::Error::boxed(e.to_string()))?;
+            self.0.push((key.as_str().to_owned(), value));
+            Ok(())
+        }
+    }
+
+    /// Returns the key-values of `source`, in order.
+    pub(crate) fn collect(
+        source: &dyn log::kv::Source,
+    ) -> Result<Vec<(String, serde_json::Value)>, log::kv::Error> {
+        let mut collector = Collector(vec![]);
+        source.visit(&mut collector)?;
+        Ok(collector.0)
+    }
+
     impl<'a> ser::Serialize for Attributes<'a> {
         fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
         where
@@ -291,4 +835,241 @@ mod test {
         );
         assert_eq!(expected, String::from_utf8(buf).unwrap().trim());
     }
+
+    #[test]
+    fn configured() {
+        let time = DateTime::parse_from_rfc3339("2016-03-20T14:22:20.644420340-08:00")
+            .unwrap()
+            .with_timezone(&Local);
+        let encoder = JsonEncoder::builder()
+            .fields(vec![Field::Message, Field::Level, Field::Time, Field::Line])
+            .rename(Field::Time, "@timestamp")
+            .rename(Field::Level, "severity")
+            .time_format(TimeFormat::EpochMillis)
+            .static_field("service", "api")
+            .static_field("port", 8080)
+            .build();
+
+        let mut buf = vec![];
+        encoder
+            .encode_inner(
+                &mut SimpleWriter(&mut buf),
+                time,
+                &Record::builder()
+                    .level(Level::Warn)
+                    .args(format_args!("message"))
+                    .build(),
+            )
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap().trim(),
+            "{\"@timestamp\":1458512540644,\"severity\":\"WARN\",\"message\":\"message\",\
+             \"port\":8080,\"service\":\"api\"}"
+        );
+
+        let encoder = JsonEncoder::builder()
+            .fields(vec![Field::Time])
+            .time_format(TimeFormat::Rfc3339Utc)
+            .build();
+        let mut buf = vec![];
+        encoder
+            .encode_inner(
+                &mut SimpleWriter(&mut buf),
+                time,
+                &Record::builder().args(format_args!("")).build(),
+            )
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap().trim(),
+            "{\"time\":\"2016-03-20T22:22:20.644420340Z\"}"
+        );
+    }
+
+    fn encode(encoder: &JsonEncoder, record: &Record<'_>) -> String {
+        let time = DateTime::parse_from_rfc3339("2016-03-20T14:22:20.644420340-08:00")
+            .unwrap()
+            .with_timezone(&Local);
+        let mut buf = vec![];
+        encoder
+            .encode_inner(&mut SimpleWriter(&mut buf), time, record)
+            .unwrap();
+        String::from_utf8(buf).unwrap().trim().to_owned()
+    }
+
+    #[test]
+    fn flatten() {
+        log_mdc::insert("user", "alice");
+        log_mdc::insert("level", "mdc");
+        log_mdc::insert("service", "mdc");
+        let record = Record::builder()
+            .level(Level::Info)
+            .args(format_args!("message"))
+            .build();
+        let builder = JsonEncoder::builder()
+            .fields(vec![Field::Level, Field::Message, Field::Mdc])
+            .static_field("service", "api");
+
+        let encoder = builder.clone().flatten(Field::Mdc, "ctx.").build();
+        assert_eq!(
+            encode(&encoder, &record),
+            "{\"level\":\"INFO\",\"message\":\"message\",\"ctx.level\":\"mdc\",\
+             \"ctx.service\":\"mdc\",\"ctx.user\":\"alice\",\"service\":\"api\"}"
+        );
+
+        let encoder = builder.clone().flatten(Field::Mdc, "").build();
+        assert_eq!(
+            encode(&encoder, &record),
+            "{\"level\":\"INFO\",\"message\":\"message\",\"user\":\"alice\",\
+             \"service\":\"api\"}"
+        );
+
+        let encoder = builder
+            .clone()
+            .flatten(Field::Mdc, "")
+            .on_collision(CollisionPolicy::Replace)
+            .build();
+        assert_eq!(
+            encode(&encoder, &record),
+            "{\"message\":\"message\",\"level\":\"mdc\",\"service\":\"mdc\",\
+             \"user\":\"alice\"}"
+        );
+
+        let encoder = builder
+            .flatten(Field::Mdc, "")
+            .rename(Field::Mdc, "context")
+            .on_collision(CollisionPolicy::Qualify)
+            .build();
+        assert_eq!(
+            encode(&encoder, &record),
+            "{\"level\":\"INFO\",\"message\":\"message\",\"context.level\":\"mdc\",\
+             \"context.service\":\"mdc\",\"user\":\"alice\",\"service\":\"api\"}"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "log_kv")]
+    fn native_attributes() {
+        #[derive(serde::Serialize)]
+        struct Request {
+            id: u32,
+            tags: Vec<&'static str>,
+        }
+
+        log_mdc::insert("user", "alice");
+        let request = Request {
+            id: 7,
+            tags: vec!["a", "b"],
+        };
+        let kvs = [
+            ("count", log::kv::Value::from(3)),
+            ("ok", log::kv::Value::from(true)),
+            ("ratio", log::kv::Value::from(0.5)),
+            ("user", log::kv::Value::from("bob")),
+            ("request", log::kv::Value::from_serde(&request)),
+        ];
+        let record = Record::builder()
+            .args(format_args!("message"))
+            .key_values(&kvs)
+            .build();
+        let builder = JsonEncoder::builder().fields(vec![Field::Mdc, Field::Attributes]);
+
+        assert_eq!(
+            encode(&builder.clone().build(), &record),
+            "{\"mdc\":{\"user\":\"alice\"},\"attributes\":{\"count\":3,\"ok\":true,\
+             \"ratio\":0.5,\"user\":\"bob\",\"request\":{\"id\":7,\"tags\":[\"a\",\"b\"]}}}"
+        );
+
+        let encoder = builder
+            .flatten(Field::Mdc, "")
+            .flatten(Field::Attributes, "")
+            .build();
+        assert_eq!(
+            encode(&encoder, &record),
+            "{\"count\":3,\"ok\":true,\"ratio\":0.5,\"user\":\"bob\",\
+             \"request\":{\"id\":7,\"tags\":[\"a\",\"b\"]}}"
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "config_parsing", feature = "yaml_format"))]
+    fn deserialize() {
+        let config = ::serde_yaml::from_str::<JsonEncoderConfig>(
+            "fields: [time, message]\nrename:\n  time: \"@timestamp\"\ntime_format: epoch_seconds\n\
+             static_fields:\n  env: prod\nflatten:\n  mdc: \"ctx.\"\non_collision: qualify\n",
+        )
+        .unwrap();
+        let encoder = JsonEncoderDeserializer
+            .deserialize(config, &Deserializers::default())
+            .unwrap();
+        let config = encoder.config_value().unwrap();
+        let expected = ::serde_yaml::from_str::<serde_value::Value>(
+            "kind: json\nfields: [time, message]\nrename:\n  time: \"@timestamp\"\n\
+             time_format: epoch_seconds\nstatic_fields:\n  env: prod\nflatten:\n  mdc: \"ctx.\"\n\
+             on_collision: qualify\n",
+        )
+        .unwrap();
+        assert_eq!(config, expected);
+
+        let config =
+            ::serde_yaml::from_str::<JsonEncoderConfig>("flatten:\n  message: \"\"\n").unwrap();
+        assert!(JsonEncoderDeserializer
+            .deserialize(config, &Deserializers::default())
+            .is_err());
+
+        for (config, error) in [
+            (
+                "rename:\n  level: time\n",
+                "the `time` key is written by both the `time` field and the `level` field",
+            ),
+            (
+                "rename:\n  level: x\n  target: x\n",
+                "the `x` key is written by both the `level` field and the `target` field",
+            ),
+            (
+                "rename:\n  level: env\nstatic_fields:\n  env: prod\n",
+                "the `env` key is written by both the `level` field and a static field",
+            ),
+            (
+                "fields: [message]\nstatic_fields:\n  message: x\n",
+                "the `message` key is written by both the `message` field and a static field",
+            ),
+        ] {
+            let config = ::serde_yaml::from_str::<JsonEncoderConfig>(config).unwrap();
+            let err = JsonEncoderDeserializer
+                .deserialize(config, &Deserializers::default())
+                .err()
+                .unwrap();
+            assert_eq!(err.to_string(), error);
+        }
+
+        // names are free once their field is not written or is flattened
+        let config = ::serde_yaml::from_str::<JsonEncoderConfig>(
+            "fields: [level, message, mdc]\nrename:\n  level: time\n  mdc: service\n\
+             static_fields:\n  service: api\nflatten:\n  mdc: \"\"\n",
+        )
+        .unwrap();
+        assert!(JsonEncoderDeserializer
+            .deserialize(config, &Deserializers::default())
+            .is_ok());
+    }
+
+    #[test]
+    fn duplicate_names() {
+        let record = Record::builder()
+            .level(Level::Info)
+            .target("target")
+            .args(format_args!("message"))
+            .build();
+        let encoder = JsonEncoder::builder()
+            .fields(vec![Field::Target, Field::Message, Field::Level])
+            .rename(Field::Target, "level")
+            .rename(Field::Message, "env")
+            .static_field("env", "prod")
+            .static_field("service", "api")
+            .build();
+        assert_eq!(
+            encode(&encoder, &record),
+            "{\"level\":\"INFO\",\"env\":\"message\",\"service\":\"api\"}"
+        );
+    }
 }
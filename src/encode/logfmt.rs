@@ -0,0 +1,541 @@
+//! An encoder which writes a logfmt line.
+//!
+//! Each log event will be written as space separated `key=value` pairs on
+//! its own line, such as:
+//!
+//! ```text
+//! time=2016-03-20T14:22:20.644420340-08:00 level=info target=foo::bar msg="the log message" request_id=42
+//! ```
+//!
+//! Values containing spaces, quotes, `=` or control characters, and empty
+//! values, are quoted, with quotes, backslashes and control characters
+//! escaped inside them. Characters of keys which are not allowed in logfmt
+//! are replaced with underscores.
+//!
+//! Requires the `logfmt_encoder` feature.
+//!
+//! The fields written and their order can be configured. The MDC, and the
+//! record's [log::kv][log_kv] key-values if the `log_kv` feature is enabled,
+//! are written as pairs of their own. Their keys which clash with the key of
+//! another field written are qualified with `mdc.` or `attributes.`, such as
+//! `mdc.level`. See `LogfmtEncoderBuilder`.
+//!
+//! [log_kv]: https://docs.rs/log/latest/log/kv/index.html
+
+use chrono::{
+    format::{Fixed, Item},
+    DateTime, Local,
+};
+use log::Record;
+use std::{borrow::Cow, fmt::Write as _, thread};
+
+#[cfg(feature = "config_parsing")]
+use crate::config::{Deserialize, Deserializers};
+use crate::encode::{Encode, Write, NEWLINE};
+
+/// The logfmt encoder's configuration
+#[cfg(feature = "config_parsing")]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct LogfmtEncoderConfig {
+    fields: Option<Vec<Field>>,
+}
+
+/// A field of the lines written by the `LogfmtEncoder`.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+#[non_exhaustive]
+#[cfg_attr(
+    feature = "config_parsing",
+    derive(serde::Deserialize, serde::Serialize)
+)]
+#[cfg_attr(feature = "config_parsing", serde(rename_all = "snake_case"))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "schema", schemars(rename = "LogfmtField"))]
+pub enum Field {
+    /// The time of the event, as `time`, in RFC 3339 format.
+    Time,
+    /// The level of the event, as `level`, such as `info`.
+    Level,
+    /// The target of the event, as `target`.
+    Target,
+    /// The log message, as `msg`.
+    Message,
+    /// The module path of the event, as `module_path`, if known.
+    ModulePath,
+    /// The source file of the event, as `file`, if known.
+    File,
+    /// The source line of the event, as `line`, if known.
+    Line,
+    /// The name of the logging thread, as `thread`, if it has one.
+    Thread,
+    /// The ID of the logging thread, as `thread_id`. Not written on
+    /// WebAssembly.
+    ThreadId,
+    /// The entries of the logging thread's MDC, ordered by key.
+    Mdc,
+    /// The record's key-values.
+    ///
+    /// Requires the `log_kv` feature.
+    #[cfg(feature = "log_kv")]
+    Attributes,
+}
+
+impl Field {
+    const DEFAULT: &'static [Field] = &[
+        Field::Time,
+        Field::Level,
+        Field::Target,
+        Field::Message,
+        Field::Mdc,
+        #[cfg(feature = "log_kv")]
+        Field::Attributes,
+    ];
+
+    /// Returns the key of the field, or `None` for the fields written as
+    /// pairs of their own.
+    pub fn key(self) -> Option<&'static str> {
+        match self {
+            Field::Time => Some("time"),
+            Field::Level => Some("level"),
+            Field::Target => Some("target"),
+            Field::Message => Some("msg"),
+            Field::ModulePath => Some("module_path"),
+            Field::File => Some("file"),
+            Field::Line => Some("line"),
+            Field::Thread => Some("thread"),
+            Field::ThreadId => Some("thread_id"),
+            Field::Mdc => None,
+            #[cfg(feature = "log_kv")]
+            Field::Attributes => None,
+        }
+    }
+}
+
+/// An `Encode`r which writes a logfmt line.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct LogfmtEncoder {
+    fields: Vec<Field>,
+}
+
+impl Default for LogfmtEncoder {
+    fn default() -> Self {
+        LogfmtEncoder::builder().build()
+    }
+}
+
+impl LogfmtEncoder {
+    /// Returns a new `LogfmtEncoder` with a default configuration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a new `LogfmtEncoderBuilder` with a default configuration.
+    pub fn builder() -> LogfmtEncoderBuilder {
+        LogfmtEncoderBuilder {
+            fields: Field::DEFAULT.to_vec(),
+        }
+    }
+
+    fn encode_inner(
+        &self,
+        w: &mut dyn Write,
+        time: DateTime<Local>,
+        record: &Record<'_>,
+    ) -> anyhow::Result<()> {
+        let mut line = Line(String::new());
+        for &field in &self.fields {
+            let key = field.key().unwrap_or("");
+            match field {
+                Field::Time => {
+                    let items = Some(Item::Fixed(Fixed::RFC3339)).into_iter();
+                    line.pair(key, time.format_with_items(items));
+                }
+                Field::Level => line.pair(key, record.level().as_str().to_ascii_lowercase()),
+                Field::Target => line.pair(key, record.target()),
+                Field::Message => line.pair(key, record.args()),
+                Field::ModulePath => {
+                    if let Some(module_path) = record.module_path() {
+                        line.pair(key, module_path);
+                    }
+                }
+                Field::File => {
+                    if let Some(file) = record.file() {
+                        line.pair(key, file);
+                    }
+                }
+                Field::Line => {
+                    if let Some(number) = record.line() {
+                        line.pair(key, number);
+                    }
+                }
+                Field::Thread => {
+                    if let Some(name) = thread::current().name() {
+                        line.pair(key, name);
+                    }
+                }
+                #[cfg(not(target_family = "wasm"))]
+                Field::ThreadId => line.pair(key, thread_id::get()),
+                #[cfg(target_family = "wasm")]
+                Field::ThreadId => {}
+                Field::Mdc => {
+                    let mut entries = vec![];
+                    log_mdc::iter(|k, v| entries.push((k.to_owned(), v.to_owned())));
+                    entries.sort();
+                    for (k, v) in entries {
+                        line.pair(&self.flattened_key("mdc", &k), v);
+                    }
+                }
+                #[cfg(feature = "log_kv")]
+                Field::Attributes => {
+                    record
+                        .key_values()
+                        .visit(&mut kv::LineVisitor {
+                            encoder: self,
+                            line: &mut line,
+                        })
+                        .map_err(anyhow::Error::msg)?;
+                }
+            }
+        }
+        line.0.push_str(NEWLINE);
+        w.write_all(line.0.as_bytes())?;
+        Ok(())
+    }
+
+    /// Returns the key of a pair of the MDC or key-values, qualified with
+    /// `prefix` if it clashes with the key of another field written.
+    fn flattened_key<'a>(&self, prefix: &str, key: &'a str) -> Cow<'a, str> {
+        if self.fields.iter().any(|field| field.key() == Some(key)) {
+            Cow::Owned(format!("{}.{}", prefix, key))
+        } else {
+            Cow::Borrowed(key)
+        }
+    }
+}
+
+/// A builder for `LogfmtEncoder`s.
+#[derive(Clone, Debug)]
+pub struct LogfmtEncoderBuilder {
+    fields: Vec<Field>,
+}
+
+impl LogfmtEncoderBuilder {
+    /// Sets the fields written, in the order they are written. Fields given
+    /// more than once are only written the first time.
+    ///
+    /// Defaults to the time, level, target, message, MDC and key-values.
+    pub fn fields(mut self, fields: Vec<Field>) -> LogfmtEncoderBuilder {
+        self.fields = fields;
+        self
+    }
+
+    /// Consumes the `LogfmtEncoderBuilder`, producing a `LogfmtEncoder`.
+    pub fn build(self) -> LogfmtEncoder {
+        let mut fields = Vec::with_capacity(self.fields.len());
+        for field in self.fields {
+            if !fields.contains(&field) {
+                fields.push(field);
+            }
+        }
+        LogfmtEncoder { fields }
+    }
+}
+
+impl Encode for LogfmtEncoder {
+    fn encode(&self, w: &mut dyn Write, record: &Record<'_>) -> anyhow::Result<()> {
+        self.encode_inner(w, Local::now(), record)
+    }
+
+    #[cfg(feature = "config_parsing")]
+    fn config_value(&self) -> Option<serde_value::Value> {
+        let config = LogfmtEncoderConfig {
+            fields: Some(self.fields.clone()),
+        };
+        crate::config::component_config("logfmt", &config)
+    }
+}
+
+/// A logfmt line being written.
+struct Line(String);
+
+impl Line {
+    fn pair<V>(&mut self, key: &str, value: V)
+    where
+        V: std::fmt::Display,
+    {
+        if !self.0.is_empty() {
+            self.0.push(' ');
+        }
+        write_key(&mut self.0, key);
+        self.0.push('=');
+        let start = self.0.len();
+        let _ = write!(self.0, "{}", value);
+        let value = self.0.split_off(start);
+        write_value(&mut self.0, &value);
+    }
+}
+
+/// Writes `key`, replacing the characters not allowed in keys.
+fn write_key(out: &mut String, key: &str) {
+    if key.is_empty() {
+        out.push('_');
+    }
+    out.extend(key.chars().map(|c| match c {
+        ' ' | '=' | '"' => '_',
+        c if c.is_control() => '_',
+        c => c,
+    }));
+}
+
+/// Writes `value`, quoted and escaped if necessary.
+fn write_value(out: &mut String, value: &str) {
+    let quote = value.is_empty()
+        || value
+            .chars()
+            .any(|c| matches!(c, ' ' | '=' | '"' | '\\') || c.is_control());
+    if !quote {
+        out.push_str(value);
+        return;
+    }
+
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// A deserializer for the `LogfmtEncoder`.
+///
+/// # Configuration
+///
+/// ```yaml
+/// kind: logfmt
+///
+/// # The fields to write, in the order they are written. One or more of
+/// # `time`, `level`, `target`, `message`, `module_path`, `file`, `line`,
+/// # `thread`, `thread_id`, `mdc` and `attributes`, which requires the
+/// # `log_kv` feature. Defaults to the fields below.
+/// fields: [time, level, target, message, mdc, attributes]
+/// ```
+#[cfg(feature = "config_parsing")]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct LogfmtEncoderDeserializer;
+
+#[cfg(feature = "config_parsing")]
+impl Deserialize for LogfmtEncoderDeserializer {
+    type Trait = dyn Encode;
+
+    type Config = LogfmtEncoderConfig;
+
+    fn deserialize(
+        &self,
+        config: LogfmtEncoderConfig,
+        _: &Deserializers,
+    ) -> anyhow::Result<Box<dyn Encode>> {
+        let mut builder = LogfmtEncoder::builder();
+        if let Some(fields) = config.fields {
+            builder = builder.fields(fields);
+        }
+        Ok(Box::new(builder.build()))
+    }
+
+    #[cfg(feature = "schema")]
+    fn schema(&self, generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        <LogfmtEncoderConfig as schemars::JsonSchema>::json_schema(generator)
+    }
+}
+
+#[cfg(feature = "log_kv")]
+mod kv {
+    use log::kv::VisitSource;
+
+    use super::{Line, LogfmtEncoder};
+
+    pub(crate) struct LineVisitor<'a> {
+        pub encoder: &'a LogfmtEncoder,
+        pub line: &'a mut Line,
+    }
+
+    impl<'kvs> VisitSource<'kvs> for LineVisitor<'_> {
+        fn visit_pair(
+            &mut self,
+            key: log::kv::Key<'kvs>,
+            value: log::kv::Value<'kvs>,
+        ) -> Result<(), log::kv::Error> {
+            let key = self.encoder.flattened_key("attributes", key.as_str());
+            self.line.pair(&key, value);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "simple_writer")]
+mod test {
+    use log::Level;
+
+    use super::*;
+    use crate::encode::writer::simple::SimpleWriter;
+
+    fn encode(encoder: &LogfmtEncoder, record: &Record<'_>) -> String {
+        let time = DateTime::parse_from_rfc3339("2016-03-20T14:22:20.644420340-08:00")
+            .unwrap()
+            .with_timezone(&Local);
+        let mut buf = vec![];
+        encoder
+            .encode_inner(&mut SimpleWriter(&mut buf), time, record)
+            .unwrap();
+        let line = String::from_utf8(buf).unwrap();
+        assert!(line.ends_with(NEWLINE));
+        line.trim_end().to_owned()
+    }
+
+    #[test]
+    fn default() {
+        let time = DateTime::parse_from_rfc3339("2016-03-20T14:22:20.644420340-08:00")
+            .unwrap()
+            .with_timezone(&Local);
+        log_mdc::insert("request_id", "42");
+        log_mdc::insert("user", "alice smith");
+        let record = Record::builder()
+            .level(Level::Warn)
+            .target("target")
+            .args(format_args!("the message"))
+            .build();
+
+        assert_eq!(
+            encode(&LogfmtEncoder::new(), &record),
+            format!(
+                "time={} level=warn target=target msg=\"the message\" request_id=42 \
+                 user=\"alice smith\"",
+                time.to_rfc3339()
+            )
+        );
+    }
+
+    #[test]
+    fn escaping() {
+        let mut out = String::new();
+        for value in [
+            "plain",
+            "",
+            "a b",
+            "a=b",
+            "say \"hi\"",
+            "C:\\dir",
+            "two\nlines\r\ttab",
+            "bell\u{7}",
+            "ünïcode",
+        ] {
+            write_value(&mut out, value);
+            out.push('|');
+        }
+        assert_eq!(
+            out,
+            "plain|\"\"|\"a b\"|\"a=b\"|\"say \\\"hi\\\"\"|\"C:\\\\dir\"|\
+             \"two\\nlines\\r\\ttab\"|\"bell\\u0007\"|ünïcode|"
+        );
+
+        let mut out = String::new();
+        write_key(&mut out, "a b=\"c\"\n");
+        out.push('|');
+        write_key(&mut out, "");
+        assert_eq!(out, "a_b__c__|_");
+    }
+
+    #[test]
+    fn fields() {
+        let encoder = LogfmtEncoder::builder()
+            .fields(vec![
+                Field::Message,
+                Field::Line,
+                Field::File,
+                Field::Level,
+                Field::ModulePath,
+                Field::Message,
+                Field::Thread,
+            ])
+            .build();
+        let record = Record::builder()
+            .level(Level::Error)
+            .file(Some("src/main.rs"))
+            .line(Some(7))
+            .args(format_args!("a=b"))
+            .build();
+
+        assert_eq!(
+            encode(&encoder, &record),
+            "msg=\"a=b\" line=7 file=src/main.rs level=error \
+             thread=encode::logfmt::test::fields"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "log_kv")]
+    fn attributes() {
+        let kvs = [
+            ("count", log::kv::Value::from(3)),
+            ("ok", log::kv::Value::from(true)),
+            ("path", log::kv::Value::from("/a b")),
+            ("msg", log::kv::Value::from("kv")),
+        ];
+        let record = Record::builder()
+            .args(format_args!("message"))
+            .key_values(&kvs)
+            .build();
+        let encoder = LogfmtEncoder::builder()
+            .fields(vec![Field::Message, Field::Attributes])
+            .build();
+
+        assert_eq!(
+            encode(&encoder, &record),
+            "msg=message count=3 ok=true path=\"/a b\" attributes.msg=kv"
+        );
+    }
+
+    #[test]
+    fn collisions() {
+        log_mdc::insert("level", "mdc");
+        log_mdc::insert("line", "mdc");
+        let record = Record::builder()
+            .level(Level::Info)
+            .args(format_args!("message"))
+            .build();
+        let encoder = LogfmtEncoder::builder()
+            .fields(vec![Field::Mdc, Field::Level, Field::Message])
+            .build();
+
+        assert_eq!(
+            encode(&encoder, &record),
+            "mdc.level=mdc line=mdc level=info msg=message"
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "config_parsing", feature = "yaml_format"))]
+    fn deserialize() {
+        let config =
+            ::serde_yaml::from_str::<LogfmtEncoderConfig>("fields: [level, message, line]\n")
+                .unwrap();
+        let encoder = LogfmtEncoderDeserializer
+            .deserialize(config, &Deserializers::default())
+            .unwrap();
+        let expected = ::serde_yaml::from_str::<serde_value::Value>(
+            "kind: logfmt\nfields: [level, message, line]\n",
+        )
+        .unwrap();
+        assert_eq!(encoder.config_value().unwrap(), expected);
+    }
+}
@@ -14,8 +14,14 @@ use std::collections::BTreeMap;
 #[cfg(feature = "config_parsing")]
 use crate::config::Deserializable;
 
+#[cfg(feature = "ecs_encoder")]
+pub mod ecs;
+#[cfg(feature = "gelf_encoder")]
+pub mod gelf;
 #[cfg(feature = "json_encoder")]
 pub mod json;
+#[cfg(feature = "logfmt_encoder")]
+pub mod logfmt;
 #[cfg(feature = "pattern_encoder")]
 pub mod pattern;
 pub mod writer;
@@ -36,6 +42,15 @@ const NEWLINE: &str = "\n";
 pub trait Encode: fmt::Debug + Send + Sync + 'static {
     /// Encodes the `Record` into bytes and writes them.
     fn encode(&self, w: &mut dyn Write, record: &Record<'_>) -> anyhow::Result<()>;
+
+    /// Returns the configuration of the encoder as it would appear in a
+    /// config file, including its `kind`, if it can be described.
+    ///
+    /// The default implementation returns `None`.
+    #[cfg(feature = "config_parsing")]
+    fn config_value(&self) -> Option<Value> {
+        None
+    }
 }
 
 #[cfg(feature = "config_parsing")]
@@ -76,6 +91,36 @@ impl<'de> de::Deserialize<'de> for EncoderConfig {
     }
 }
 
+#[cfg(feature = "config_parsing")]
+impl serde::Serialize for EncoderConfig {
+    fn serialize<S>(&self, s: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        crate::config::ser_component(&self.kind, &self.config, s)
+    }
+}
+
+#[cfg(feature = "schema")]
+impl schemars::JsonSchema for EncoderConfig {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "Encoder".into()
+    }
+
+    fn json_schema(generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        crate::config::schema::component(generator)
+    }
+}
+
+#[cfg(feature = "config_parsing")]
+impl EncoderConfig {
+    /// Returns the configuration of `encoder`, if it can be described.
+    #[allow(dead_code)] // unused when no appenders are enabled
+    pub(crate) fn describe(encoder: &dyn Encode) -> Option<EncoderConfig> {
+        encoder.config_value()?.deserialize_into().ok()
+    }
+}
+
 /// A text or background color.
 #[allow(missing_docs)]
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
@@ -166,8 +166,9 @@ thread_local!(
 
 /// The pattern encoder's configuration.
 #[cfg(feature = "config_parsing")]
-#[derive(Clone, Eq, PartialEq, Hash, Debug, Default, serde::Deserialize)]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default, serde::Deserialize, serde::Serialize)]
 #[serde(deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct PatternEncoderConfig {
     pattern: Option<String>,
 }
@@ -724,6 +725,14 @@ impl Encode for PatternEncoder {
         }
         Ok(())
     }
+
+    #[cfg(feature = "config_parsing")]
+    fn config_value(&self) -> Option<serde_value::Value> {
+        let config = PatternEncoderConfig {
+            pattern: Some(self.pattern.clone()),
+        };
+        crate::config::component_config("pattern", &config)
+    }
 }
 
 impl PatternEncoder {
@@ -770,6 +779,11 @@ impl Deserialize for PatternEncoderDeserializer {
         };
         Ok(Box::new(encoder))
     }
+
+    #[cfg(feature = "schema")]
+    fn schema(&self, generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        <PatternEncoderConfig as schemars::JsonSchema>::json_schema(generator)
+    }
 }
 
 #[cfg(test)]
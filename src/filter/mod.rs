@@ -22,6 +22,15 @@ pub mod threshold;
 pub trait Filter: fmt::Debug + Send + Sync + 'static {
     /// Filters a log event.
     fn filter(&self, record: &Record<'_>) -> Response;
+
+    /// Returns the configuration of the filter as it would appear in a config
+    /// file, including its `kind`, if it can be described.
+    ///
+    /// The default implementation returns `None`.
+    #[cfg(feature = "config_parsing")]
+    fn config_value(&self) -> Option<Value> {
+        None
+    }
 }
 
 #[cfg(feature = "config_parsing")]
@@ -80,6 +89,27 @@ impl<'de> de::Deserialize<'de> for FilterConfig {
     }
 }
 
+#[cfg(feature = "config_parsing")]
+impl serde::Serialize for FilterConfig {
+    fn serialize<S>(&self, s: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        crate::config::ser_component(&self.kind, &self.config, s)
+    }
+}
+
+#[cfg(feature = "schema")]
+impl schemars::JsonSchema for FilterConfig {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "Filter".into()
+    }
+
+    fn json_schema(generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        crate::config::schema::component(generator)
+    }
+}
+
 #[cfg(test)]
 mod test {
     #[cfg(feature = "config_parsing")]
@@ -10,8 +10,14 @@ use crate::filter::{Filter, Response};
 
 /// The threshold filter's configuration.
 #[cfg(feature = "config_parsing")]
-#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, serde::Deserialize)]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, serde::Deserialize, serde::Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct ThresholdFilterConfig {
+    #[serde(serialize_with = "crate::config::ser_level")]
+    #[cfg_attr(
+        feature = "schema",
+        schemars(schema_with = "crate::config::schema::level_filter")
+    )]
     level: LevelFilter,
 }
 
@@ -36,6 +42,12 @@ impl Filter for ThresholdFilter {
             Response::Neutral
         }
     }
+
+    #[cfg(feature = "config_parsing")]
+    fn config_value(&self) -> Option<serde_value::Value> {
+        let config = ThresholdFilterConfig { level: self.level };
+        crate::config::component_config("threshold", &config)
+    }
 }
 
 /// A deserializer for the `ThresholdFilter`.
@@ -65,6 +77,11 @@ impl Deserialize for ThresholdFilterDeserializer {
     ) -> anyhow::Result<Box<dyn Filter>> {
         Ok(Box::new(ThresholdFilter::new(config.level)))
     }
+
+    #[cfg(feature = "schema")]
+    fn schema(&self, generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        <ThresholdFilterConfig as schemars::JsonSchema>::json_schema(generator)
+    }
 }
 
 #[cfg(test)]
@@ -12,7 +12,9 @@
 //! file, the console, or the syslog.
 //!
 //! Implementations:
+//!   - [async](append/async/struct.AsyncAppenderDeserializer.html#configuration): requires the `async_appender` feature.
 //!   - [console](append/console/struct.ConsoleAppenderDeserializer.html#configuration): requires the `console_appender` feature.
+//!   - [failover](append/failover/struct.FailoverAppenderDeserializer.html#configuration): requires the `failover_appender` feature.
 //!   - [file](append/file/struct.FileAppenderDeserializer.html#configuration): requires the `file_appender` feature.
 //!   - [rolling_file](append/rolling_file/struct.RollingFileAppenderDeserializer.html#configuration): requires the `rolling_file_appender` feature and can be configured with the `compound_policy`.
 //!     - [compound](append/rolling_file/policy/compound/struct.CompoundPolicyDeserializer.html#configuration): requires the `compound_policy` feature
@@ -23,6 +25,16 @@
 //!         - [size](append/rolling_file/policy/compound/trigger/size/struct.SizeTriggerDeserializer.html#configuration): requires the `size_trigger` feature
 //!         - [time](append/rolling_file/policy/compound/trigger/tine/struct.TimeTriggerDeserializer.html#configuration): requires the `time_trigger` feature
 //!         - [onstartup](append/rolling_file/policy/compound/trigger/tine/struct.OnStartUpTriggerDeserializer.html#configuration): requires the `onstartup_trigger` feature
+//!   - [ring_buffer](append/ring_buffer/struct.RingBufferAppenderDeserializer.html#configuration): requires the `ring_buffer_appender` feature.
+//!   - [sifting](append/sifting/struct.SiftingAppenderDeserializer.html#configuration): requires the `sifting_appender` feature.
+//!   - [socket](append/socket/struct.SocketAppenderDeserializer.html#configuration): requires the `socket_appender` feature.
+//!   - [gelf](append/gelf/struct.GelfAppenderDeserializer.html#configuration): requires the `gelf_appender` feature.
+//!   - [syslog](append/syslog/struct.SyslogAppenderDeserializer.html#configuration): requires the `syslog_appender` feature.
+//!
+//! Some appenders can only be built from code:
+//!   - [ChannelAppender](append/channel/struct.ChannelAppender.html): requires the `channel_appender` feature.
+//!   - [FnAppender](append/function/struct.FnAppender.html): requires the `function_appender` feature.
+//!   - [CaptureAppender](append/capture/struct.CaptureAppender.html): requires the `testing` feature.
 //!
 //! ## Encoders
 //!
@@ -33,6 +45,9 @@
 //! Implementations:
 //!   - [pattern](encode/pattern/struct.PatternEncoderDeserializer.html#configuration): requires the `pattern_encoder` feature
 //!   - [json](encode/json/struct.JsonEncoderDeserializer.html#configuration): requires the `json_encoder` feature
+//!   - [ecs](encode/ecs/struct.EcsEncoderDeserializer.html#configuration): requires the `ecs_encoder` feature
+//!   - [gelf](encode/gelf/struct.GelfEncoderDeserializer.html#configuration): requires the `gelf_encoder` feature
+//!   - [logfmt](encode/logfmt/struct.LogfmtEncoderDeserializer.html#configuration): requires the `logfmt_encoder` feature
 //!
 //! ## Filters
 //!
@@ -303,7 +318,17 @@
 #![warn(missing_docs)]
 
 use std::{
-    cmp, collections::HashMap, fmt, hash::BuildHasherDefault, io, io::prelude::*, sync::Arc,
+    cmp,
+    collections::{BTreeMap, HashMap},
+    fmt,
+    hash::BuildHasherDefault,
+    io,
+    io::prelude::*,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Instant,
 };
 
 use arc_swap::ArcSwap;
@@ -314,15 +339,21 @@ pub mod append;
 pub mod config;
 pub mod encode;
 pub mod filter;
+#[cfg(any(feature = "syslog_appender", feature = "gelf_encoder"))]
+mod priv_host;
 #[cfg(feature = "console_writer")]
 mod priv_io;
 
 pub use config::{init_config, Config};
 
 #[cfg(feature = "config_parsing")]
-pub use config::{init_file, init_raw_config};
+pub use config::{init_file, init_files, init_raw_config};
 
-use self::{append::Append, filter::Filter};
+use self::{
+    append::Append,
+    config::{runtime::ConfigError, ErrorPolicy},
+    filter::Filter,
+};
 
 type FnvHashMap<K, V> = HashMap<K, V, BuildHasherDefault<FnvHasher>>;
 
@@ -397,12 +428,12 @@ impl ConfiguredLogger {
         &self,
         record: &log::Record<'_>,
         appenders: &[Appender],
-    ) -> Result<(), Vec<anyhow::Error>> {
+    ) -> Result<(), Vec<(usize, anyhow::Error)>> {
         let mut errors = vec![];
         if self.enabled(record.level()) {
             for &idx in &self.appenders {
                 if let Err(err) = appenders[idx].append(record) {
-                    errors.push(err);
+                    errors.push((idx, err));
                 }
             }
         }
@@ -417,8 +448,19 @@ impl ConfiguredLogger {
 
 #[derive(Debug)]
 struct Appender {
-    appender: Box<dyn Append>,
+    name: String,
+    appender: Arc<dyn Append>,
     filters: Vec<Box<dyn Filter>>,
+    errors: AppenderErrors,
+}
+
+#[derive(Debug)]
+struct AppenderErrors {
+    policy: ErrorPolicy,
+    count: AtomicU64,
+    // The last time an error was reported, and the number of errors
+    // suppressed since, for `ErrorPolicy::RateLimit`.
+    last_report: Mutex<(Option<Instant>, u64)>,
 }
 
 impl Appender {
@@ -437,12 +479,109 @@ impl Appender {
     fn flush(&self) {
         self.appender.flush();
     }
+
+    fn report(&self, err: anyhow::Error, err_handler: &dyn Fn(&anyhow::Error)) {
+        let count = self.errors.count.fetch_add(1, Ordering::Relaxed) + 1;
+
+        match &self.errors.policy {
+            ErrorPolicy::Report => err_handler(&err),
+            ErrorPolicy::Ignore => {}
+            ErrorPolicy::LogOnce => {
+                if count == 1 {
+                    err_handler(&err);
+                }
+            }
+            ErrorPolicy::RateLimit(interval) => {
+                let now = Instant::now();
+                let mut last_report = match self.errors.last_report.lock() {
+                    Ok(last_report) => last_report,
+                    Err(poisoned) => poisoned.into_inner(),
+                };
+                let (last, suppressed) = &mut *last_report;
+                if last.is_some_and(|last| now.duration_since(last) < *interval) {
+                    *suppressed += 1;
+                    return;
+                }
+
+                let err = match *suppressed {
+                    0 => err,
+                    n => anyhow::anyhow!(
+                        "{:#} ({} more errors from appender `{}` were suppressed)",
+                        err,
+                        n,
+                        self.name
+                    ),
+                };
+                *last = Some(now);
+                *suppressed = 0;
+                drop(last_report);
+                err_handler(&err);
+            }
+            ErrorPolicy::Custom(handler) => handler(&err),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct LoggerSpec {
+    name: String,
+    appenders: Vec<usize>,
+    additive: bool,
+    level: LevelFilter,
+}
+
+/// The configured loggers, along with any levels overridden through a
+/// `Handle`, from which the `ConfiguredLogger` tree is built.
+#[derive(Clone, Debug)]
+struct Hierarchy {
+    root_level: LevelFilter,
+    root_appenders: Arc<Vec<usize>>,
+    loggers: Arc<Vec<LoggerSpec>>,
+    root_override: Option<LevelFilter>,
+    overrides: BTreeMap<String, LevelFilter>,
+}
+
+impl Hierarchy {
+    fn build(&self) -> ConfiguredLogger {
+        let mut root = ConfiguredLogger {
+            level: self.root_override.unwrap_or(self.root_level),
+            appenders: self.root_appenders.to_vec(),
+            children: FnvHashMap::default(),
+        };
+
+        let configured = self.loggers.iter().map(|logger| {
+            let level = self.overrides.get(&logger.name).copied();
+            (
+                &*logger.name,
+                &*logger.appenders,
+                logger.additive,
+                level.unwrap_or(logger.level),
+            )
+        });
+        // overridden loggers which aren't configured behave as if they were,
+        // with no appenders of their own
+        let extra = self
+            .overrides
+            .iter()
+            .filter(|(name, _)| !self.loggers.iter().any(|l| l.name == **name))
+            .map(|(name, level)| (&**name, &[][..], true, *level));
+
+        let mut loggers = configured.chain(extra).collect::<Vec<_>>();
+        // sort loggers by name length to ensure that we initialize them top to bottom
+        loggers.sort_by_key(|l| l.0.len());
+        for (name, appenders, additive, level) in loggers {
+            root.add(name, appenders.to_vec(), additive, level);
+        }
+
+        root
+    }
 }
 
 struct SharedLogger {
     root: ConfiguredLogger,
-    appenders: Vec<Appender>,
-    err_handler: Box<dyn Send + Sync + Fn(&anyhow::Error)>,
+    hierarchy: Hierarchy,
+    appenders: Arc<Vec<Appender>>,
+    err_handler: Arc<dyn Send + Sync + Fn(&anyhow::Error)>,
 }
 
 impl fmt::Debug for SharedLogger {
@@ -467,51 +606,123 @@ impl SharedLogger {
         config: config::Config,
         err_handler: Box<dyn Send + Sync + Fn(&anyhow::Error)>,
     ) -> SharedLogger {
-        let (appenders, root, mut loggers) = config.unpack();
+        let (appenders, root, loggers) = config.unpack();
 
-        let root = {
+        let hierarchy = {
             let appender_map = appenders
                 .iter()
                 .enumerate()
                 .map(|(i, appender)| (appender.name(), i))
                 .collect::<HashMap<_, _>>();
-
-            let mut root = ConfiguredLogger {
-                level: root.level(),
-                appenders: root
-                    .appenders()
+            let indices = |names: &[String]| {
+                names
                     .iter()
                     .map(|appender| appender_map[&**appender])
-                    .collect(),
-                children: FnvHashMap::default(),
+                    .collect::<Vec<_>>()
             };
 
-            // sort loggers by name length to ensure that we initialize them top to bottom
-            loggers.sort_by_key(|l| l.name().len());
-            for logger in loggers {
-                let appenders = logger
-                    .appenders()
-                    .iter()
-                    .map(|appender| appender_map[&**appender])
-                    .collect();
-                root.add(logger.name(), appenders, logger.additive(), logger.level());
+            Hierarchy {
+                root_level: root.level(),
+                root_appenders: Arc::new(indices(root.appenders())),
+                loggers: Arc::new(
+                    loggers
+                        .iter()
+                        .map(|logger| LoggerSpec {
+                            name: logger.name().to_owned(),
+                            appenders: indices(logger.appenders()),
+                            additive: logger.additive(),
+                            level: logger.level(),
+                        })
+                        .collect(),
+                ),
+                root_override: None,
+                overrides: BTreeMap::new(),
             }
-
-            root
         };
 
         let appenders = appenders
             .into_iter()
             .map(|appender| {
-                let (_, appender, filters) = appender.unpack();
-                Appender { appender, filters }
+                let (name, appender, filters, policy) = appender.unpack();
+                Appender {
+                    name,
+                    appender,
+                    filters,
+                    errors: AppenderErrors {
+                        policy,
+                        count: AtomicU64::new(0),
+                        last_report: Mutex::new((None, 0)),
+                    },
+                }
             })
             .collect();
 
         SharedLogger {
-            root,
-            appenders,
-            err_handler,
+            root: hierarchy.build(),
+            hierarchy,
+            appenders: Arc::new(appenders),
+            err_handler: Arc::from(err_handler),
+        }
+    }
+
+    #[cfg(feature = "config_parsing")]
+    fn raw_config(&self) -> anyhow::Result<config::RawConfig> {
+        use anyhow::Context;
+
+        let mut appenders = HashMap::new();
+        for appender in self.appenders.iter() {
+            let raw = config::describe_appender(
+                &*appender.appender,
+                &appender.filters,
+                &appender.errors.policy,
+            )
+            .with_context(|| format!("unable to describe appender `{}`", appender.name))?;
+            appenders.insert(appender.name.clone(), raw);
+        }
+
+        let hierarchy = &self.hierarchy;
+        let names = |indices: &[usize]| {
+            indices
+                .iter()
+                .map(|&i| self.appenders[i].name.clone())
+                .collect::<Vec<_>>()
+        };
+        let root = config::Root::builder()
+            .appenders(names(&hierarchy.root_appenders))
+            .build(hierarchy.root_override.unwrap_or(hierarchy.root_level));
+        let mut loggers = hierarchy
+            .loggers
+            .iter()
+            .map(|logger| {
+                let level = hierarchy.overrides.get(&logger.name).copied();
+                config::Logger::builder()
+                    .appenders(names(&logger.appenders))
+                    .additive(logger.additive)
+                    .build(logger.name.clone(), level.unwrap_or(logger.level))
+            })
+            .collect::<Vec<_>>();
+        for (name, level) in &hierarchy.overrides {
+            if !hierarchy.loggers.iter().any(|logger| logger.name == *name) {
+                loggers.push(config::Logger::builder().build(name.clone(), *level));
+            }
+        }
+
+        Ok(config::RawConfig::from_parts(appenders, &root, &loggers))
+    }
+
+    /// Returns a copy of this logger with its levels changed by `f`, sharing
+    /// its appenders.
+    fn with_levels<F>(&self, f: F) -> SharedLogger
+    where
+        F: FnOnce(&mut Hierarchy),
+    {
+        let mut hierarchy = self.hierarchy.clone();
+        f(&mut hierarchy);
+        SharedLogger {
+            root: hierarchy.build(),
+            hierarchy,
+            appenders: self.appenders.clone(),
+            err_handler: self.err_handler.clone(),
         }
     }
 }
@@ -544,6 +755,7 @@ impl Logger {
     pub fn handle(&self) -> Handle {
         Handle {
             shared: self.0.clone(),
+            reloader: None,
         }
     }
 }
@@ -564,14 +776,14 @@ impl log::Log for Logger {
             .find(record.target())
             .log(record, &shared.appenders)
         {
-            for e in errs {
-                (shared.err_handler)(&e)
+            for (idx, e) in errs {
+                shared.appenders[idx].report(e, &*shared.err_handler);
             }
         }
     }
 
     fn flush(&self) {
-        for appender in &self.0.load().appenders {
+        for appender in self.0.load().appenders.iter() {
             appender.flush();
         }
     }
@@ -581,10 +793,16 @@ pub(crate) fn handle_error(e: &anyhow::Error) {
     let _ = writeln!(io::stderr(), "log4rs: {}", e);
 }
 
+/// A source of configuration which a `Handle` can reload on demand.
+pub(crate) trait Reload: fmt::Debug + Send + Sync {
+    fn reload(&self) -> anyhow::Result<()>;
+}
+
 /// A handle to the active logger.
 #[derive(Clone, Debug)]
 pub struct Handle {
     shared: Arc<ArcSwap<SharedLogger>>,
+    reloader: Option<Arc<dyn Reload>>,
 }
 
 impl Handle {
@@ -599,6 +817,122 @@ impl Handle {
     pub fn max_log_level(&self) -> LevelFilter {
         self.shared.load().root.max_log_level()
     }
+
+    /// Reloads the configuration from its source immediately, whether or not
+    /// it has changed, for example in response to `SIGHUP`.
+    ///
+    /// Reload hooks are notified as for any other reload, and the current
+    /// configuration is kept if the reload fails.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the logger was not configured from a file or a
+    /// `ConfigSource`, or if the configuration could not be reloaded.
+    pub fn reload_now(&self) -> anyhow::Result<()> {
+        match &self.reloader {
+            Some(reloader) => reloader.reload(),
+            None => Err(anyhow::anyhow!(
+                "the logger has no configuration source to reload from"
+            )),
+        }
+    }
+
+    #[cfg(feature = "config_parsing")]
+    pub(crate) fn set_reloader(&mut self, reloader: Arc<dyn Reload>) {
+        self.reloader = Some(reloader);
+    }
+
+    /// Overrides the level of the named logger, leaving the appenders of the
+    /// current configuration in place.
+    ///
+    /// The logger behaves as if it had been configured with `level`, so
+    /// loggers below it without a level of their own inherit the new level.
+    /// A logger which is not part of the configuration is added, with no
+    /// appenders of its own.
+    ///
+    /// Overrides last until they are removed or a new configuration is
+    /// applied.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `name` is not a valid logger name.
+    pub fn set_logger_level(&self, name: &str, level: LevelFilter) -> Result<(), ConfigError> {
+        config::runtime::check_logger_name(name)?;
+        self.update_levels(|hierarchy| {
+            hierarchy.overrides.insert(name.to_owned(), level);
+        });
+        Ok(())
+    }
+
+    /// Removes an override set by `set_logger_level`, restoring the level of
+    /// the named logger from the current configuration.
+    ///
+    /// Returns `false` if the logger's level was not overridden.
+    pub fn remove_logger_override(&self, name: &str) -> bool {
+        let mut removed = false;
+        self.update_levels(|hierarchy| {
+            removed = hierarchy.overrides.remove(name).is_some();
+        });
+        removed
+    }
+
+    /// Overrides the level of the root logger, leaving the appenders of the
+    /// current configuration in place.
+    ///
+    /// The override lasts until a new configuration is applied.
+    pub fn set_root_level(&self, level: LevelFilter) {
+        self.update_levels(|hierarchy| hierarchy.root_override = Some(level));
+    }
+
+    fn update_levels<F>(&self, mut f: F)
+    where
+        F: FnMut(&mut Hierarchy),
+    {
+        self.shared
+            .rcu(|shared| Arc::new(shared.with_levels(&mut f)));
+        log::set_max_level(self.shared.load().root.max_log_level());
+    }
+
+    /// Returns the number of errors returned by the named appender of the
+    /// current configuration, whether or not they were reported.
+    ///
+    /// Counts start from zero whenever a new configuration is applied.
+    pub fn appender_error_count(&self, name: &str) -> Option<u64> {
+        self.shared
+            .load()
+            .appenders
+            .iter()
+            .find(|appender| appender.name == name)
+            .map(|appender| appender.errors.count.load(Ordering::Relaxed))
+    }
+
+    /// Returns the number of errors returned by each appender of the current
+    /// configuration, by appender name.
+    ///
+    /// Counts start from zero whenever a new configuration is applied.
+    pub fn appender_error_counts(&self) -> HashMap<String, u64> {
+        self.shared
+            .load()
+            .appenders
+            .iter()
+            .map(|appender| {
+                let count = appender.errors.count.load(Ordering::Relaxed);
+                (appender.name.clone(), count)
+            })
+            .collect()
+    }
+
+    /// Describes the active configuration, including any levels overridden
+    /// through this handle, as a raw config which can be serialized.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an appender of the configuration cannot describe
+    /// its configuration. See `RawConfig::from_config`.
+    #[cfg(feature = "config_parsing")]
+    pub fn raw_config(&self) -> anyhow::Result<config::RawConfig> {
+        self.shared.load().raw_config()
+    }
 }
 
 #[cfg(test)]
@@ -714,4 +1048,144 @@ mod test {
                 .build()
         ));
     }
+
+    #[derive(Debug)]
+    struct FailingAppender;
+
+    impl append::Append for FailingAppender {
+        fn append(&self, _: &log::Record<'_>) -> anyhow::Result<()> {
+            anyhow::bail!("failed")
+        }
+
+        fn flush(&self) {}
+    }
+
+    fn error_policy_logger(policy: ErrorPolicy) -> (Logger, Arc<Mutex<Vec<String>>>) {
+        let config = config::Config::builder()
+            .appender(
+                config::Appender::builder()
+                    .error_policy(policy)
+                    .build("failing", Box::new(FailingAppender)),
+            )
+            .build(
+                config::Root::builder()
+                    .appender("failing")
+                    .build(LevelFilter::Info),
+            )
+            .unwrap();
+
+        let reported = Arc::new(Mutex::new(vec![]));
+        let logger = {
+            let reported = reported.clone();
+            Logger::new_with_err_handler(
+                config,
+                Box::new(move |e| reported.lock().unwrap().push(format!("{:#}", e))),
+            )
+        };
+        (logger, reported)
+    }
+
+    fn log_times(logger: &Logger, n: usize) {
+        for _ in 0..n {
+            logger.log(&log::Record::builder().level(Level::Info).build());
+        }
+    }
+
+    #[test]
+    fn error_policies() {
+        for (policy, expected) in [
+            (ErrorPolicy::Report, 3),
+            (ErrorPolicy::Ignore, 0),
+            (ErrorPolicy::LogOnce, 1),
+        ] {
+            let (logger, reported) = error_policy_logger(policy);
+            log_times(&logger, 3);
+
+            assert_eq!(reported.lock().unwrap().len(), expected);
+            assert_eq!(logger.handle().appender_error_count("failing"), Some(3));
+            assert_eq!(logger.handle().appender_error_count("missing"), None);
+        }
+    }
+
+    #[test]
+    fn rate_limited_errors() {
+        let (logger, reported) =
+            error_policy_logger(ErrorPolicy::RateLimit(std::time::Duration::from_millis(50)));
+        log_times(&logger, 3);
+        std::thread::sleep(std::time::Duration::from_millis(60));
+        log_times(&logger, 1);
+
+        assert_eq!(
+            *reported.lock().unwrap(),
+            [
+                "failed",
+                "failed (2 more errors from appender `failing` were suppressed)"
+            ]
+        );
+        assert_eq!(logger.handle().appender_error_counts()["failing"], 4);
+    }
+
+    #[test]
+    fn custom_error_policy() {
+        let custom = Arc::new(AtomicU64::new(0));
+        let (logger, reported) = {
+            let custom = custom.clone();
+            error_policy_logger(ErrorPolicy::Custom(Arc::new(move |_| {
+                custom.fetch_add(1, Ordering::Relaxed);
+            })))
+        };
+        log_times(&logger, 2);
+
+        assert!(reported.lock().unwrap().is_empty());
+        assert_eq!(custom.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn level_overrides() {
+        let config = config::Config::builder()
+            .appender(config::Appender::builder().build("failing", Box::new(FailingAppender)))
+            .logger(config::Logger::builder().build("foo::bar", LevelFilter::Warn))
+            .build(
+                config::Root::builder()
+                    .appender("failing")
+                    .build(LevelFilter::Info),
+            )
+            .unwrap();
+        let logger = Logger::new_with_err_handler(config, Box::new(|_| {}));
+        let handle = logger.handle();
+        let enabled = |level, target| {
+            logger.enabled(&Metadata::builder().level(level).target(target).build())
+        };
+
+        log_times(&logger, 1);
+        assert!(!enabled(Level::Debug, "foo::bar"));
+
+        handle.set_logger_level("foo", LevelFilter::Trace).unwrap();
+        assert!(enabled(Level::Trace, "foo::baz"));
+        assert!(!enabled(Level::Info, "foo::bar::baz"));
+        assert!(!enabled(Level::Debug, "qux"));
+        assert_eq!(handle.max_log_level(), LevelFilter::Trace);
+
+        handle
+            .set_logger_level("foo::bar", LevelFilter::Debug)
+            .unwrap();
+        assert!(enabled(Level::Debug, "foo::bar::baz"));
+        assert!(handle
+            .set_logger_level("foo:bar", LevelFilter::Debug)
+            .is_err());
+
+        handle.set_root_level(LevelFilter::Error);
+        assert!(!enabled(Level::Warn, "qux"));
+
+        assert!(handle.remove_logger_override("foo"));
+        assert!(!handle.remove_logger_override("foo"));
+        assert!(!enabled(Level::Trace, "foo::baz"));
+        assert!(handle.remove_logger_override("foo::bar"));
+        assert!(!enabled(Level::Debug, "foo::bar"));
+        assert!(enabled(Level::Warn, "foo::bar"));
+
+        // the appender was kept, along with its error count
+        logger.log(&log::Record::builder().level(Level::Error).build());
+        assert_eq!(handle.appender_error_count("failing"), Some(2));
+    }
 }
@@ -0,0 +1,22 @@
+#[cfg(not(unix))]
+use std::env;
+
+/// Returns the hostname of the machine, if it can be determined.
+#[cfg(unix)]
+pub fn hostname() -> Option<String> {
+    let mut buf = [0u8; 256];
+    let ret = unsafe { libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len()) };
+    if ret != 0 {
+        return None;
+    }
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    String::from_utf8(buf[..len].to_vec())
+        .ok()
+        .filter(|h| !h.is_empty())
+}
+
+/// Returns the hostname of the machine, if it can be determined.
+#[cfg(not(unix))]
+pub fn hostname() -> Option<String> {
+    env::var("COMPUTERNAME").ok()
+}
@@ -0,0 +1,43 @@
+use std::thread;
+
+use log::{Level, LevelFilter};
+use log4rs::append::capture;
+
+// `capture` installs the global logger, so it gets a test binary of its own.
+#[test]
+fn scoped_capture() {
+    let (records, guard) = capture::capture(LevelFilter::Info).unwrap();
+
+    log::debug!("filtered");
+    log::info!("kept");
+    {
+        let (inner, _guard) = capture::capture(LevelFilter::Trace).unwrap();
+        log::debug!("inner");
+        assert_eq!(inner.messages(), ["inner"]);
+    }
+    log::warn!("restored");
+
+    // Other threads are not captured.
+    thread::spawn(|| log::error!("elsewhere")).join().unwrap();
+
+    drop(guard);
+    log::error!("uninstalled");
+
+    assert_eq!(records.messages(), ["kept", "restored"]);
+    assert!(records.contains(Level::Warn, "store"));
+}
+
+#[test]
+fn concurrent_tests_are_isolated() {
+    let (records, _guard) = capture::capture(LevelFilter::Trace).unwrap();
+
+    let other = thread::spawn(|| {
+        let (records, _guard) = capture::capture(LevelFilter::Trace).unwrap();
+        log::info!("other");
+        records.messages()
+    });
+    log::info!("this");
+
+    assert_eq!(other.join().unwrap(), ["other"]);
+    assert_eq!(records.messages(), ["this"]);
+}